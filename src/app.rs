@@ -1,31 +1,189 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 use eframe::egui;
+use serde::{Deserialize, Serialize};
 
 use crate::clipboard;
-use crate::config::Config;
+use crate::config::{
+    self, Config, EnterAction, EscapeBehavior, InitialSelection, ListOrder, PasteTarget,
+    SearchMode, SearchPosition, Theme, WindowPlacement, WindowSize,
+};
+use crate::diff::{self, DiffLine, LineChange};
 use crate::fuzzy;
-use crate::history::History;
-use crate::hotkey;
-use crate::tray;
+use crate::highlight::{self, Language};
+use crate::history::{self, meets_min_length, ClipboardEntry, History, Removal};
+use crate::hooks;
+use crate::hotkey::{self, Bindings, HotkeyHandle};
+use crate::image::THUMBNAIL_SIZE;
+use crate::keymap::{Action, Keymap};
+use crate::kind::ContentKind;
+use crate::platform::{self, PowerEvent};
+use crate::search::{self, SearchWorker};
+use crate::storage;
+use crate::template::{expand_template, TemplateContext};
+use crate::text;
+use crate::transform::Transform;
+use crate::tray::{self, TrayStatus};
+use crate::url_title::{self, TitleFetcher};
+use crate::watchdog::Watchdog;
 
 const HEADER_HEIGHT: f32 = 56.0;
+const STATS_HEIGHT: f32 = 18.0;
 const ROW_HEIGHT: f32 = 24.0;
 const MIN_HEIGHT: f32 = 80.0;
-const MAX_HEIGHT: f32 = 500.0;
+const PREVIEW_HEIGHT: f32 = 140.0;
+/// Longer entries are cut off in the preview pane, so laying out the text
+/// stays cheap.
+const PREVIEW_MAX_CHARS: usize = 20_000;
+/// Characters of an entry read out by a screen reader.
+const ACCESSIBLE_MAX_CHARS: usize = 300;
+/// Longer entries are cut off in the detail view; laying out (and
+/// highlighting) a multi-MB copy every frame would stall the UI.
+const DETAIL_MAX_BYTES: usize = 256 * 1024;
+const UI_STATE_KEY: &str = "ui_state";
+/// Symbol palette cells: large glyphs in fixed-width cells.
+/// UI zoom in `Config::accessible` mode.
+const ACCESSIBLE_ZOOM: f32 = 1.5;
+/// Extra vertical padding of each row in `Config::accessible` mode.
+const ACCESSIBLE_ROW_PADDING: f32 = 3.0;
+const PALETTE_ROW_HEIGHT: f32 = 36.0;
+const PALETTE_CELL_WIDTH: f32 = 44.0;
+const PALETTE_FONT_SIZE: f32 = 22.0;
+/// How often the tray tooltip is refreshed while the window is hidden.
+const TOOLTIP_REFRESH: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Transient UI state kept in eframe's storage between runs. Anything the
+/// user sets in the config belongs in `Config`, which is used until there is
+/// a saved value; this only remembers what the window last looked like.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct UiState {
+    last_height: f32,
+    /// Last size picked with Ctrl+= / Ctrl+- or resized to.
+    window_size: Option<WindowSize>,
+    /// Last order picked with Alt+S.
+    list_order: Option<ListOrder>,
+    /// Whether the preview pane was open.
+    preview_open: Option<bool>,
+    /// Last mode picked with Ctrl+R.
+    search_mode: Option<SearchMode>,
+}
+
+/// Which of an entry's formats a paste puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PasteFormat {
+    /// The text and, if it was captured, its HTML.
+    AsIs,
+    /// Only the text (the PastePlain action, Shift+Enter).
+    PlainText,
+    /// Only the text, transformed (row context menu or its shortcut).
+    Transformed(Transform),
+}
+
+/// Id and byte length of each result, telling whether the results changed.
+type ResultKey = Vec<(u64, usize)>;
 
 pub struct ClipboardHistoryApp {
     history: Arc<Mutex<History>>,
     search_query: String,
     selected_index: usize,
+    /// Id of the entry at `selected_index` last frame, so the selection can
+    /// stay on it when a new capture shifts the list.
+    selected_id: Option<u64>,
     visible: Arc<Mutex<bool>>,
     config: Config,
+    keymap: Keymap,
     initialized: bool,
     was_visible: bool,
     focused_once: bool,
-    _tray: Option<tray_icon::TrayIcon>,
+    /// Whether the search field had keyboard focus on the previous frame.
+    search_has_focus: bool,
+    /// Shared with the UI timer that refreshes the tooltip while hidden.
+    tray: Option<Rc<RefCell<TrayStatus>>>,
+    /// Restarts the monitor and hotkey threads; started on the first frame.
+    watchdog: Option<Watchdog>,
+    /// Bindings of the global hotkey listener, `None` in safe mode.
+    hotkeys: Option<HotkeyHandle>,
     cursor_pos: Arc<Mutex<(f64, f64)>>,
     last_height: f32,
+    /// Current width and maximum height of the window.
+    window_size: WindowSize,
+    /// True until the background load started with `Config::lazy_load` finishes.
+    loading: Arc<Mutex<bool>>,
+    /// Cleared if the clipboard monitor thread stops.
+    monitor_running: Arc<Mutex<bool>>,
+    /// Set from the tray to stop recording copies for a while.
+    capture_paused: Arc<Mutex<bool>>,
+    /// Set when quitting; the clipboard monitor saves and stops.
+    quitting: Arc<Mutex<bool>>,
+    /// Show only symbol/emoji entries, as a grid of large glyphs.
+    palette: bool,
+    /// Filter chip picked in the search bar: only show entries of this kind.
+    kind_filter: Option<ContentKind>,
+    /// Entries picked with Ctrl+Click / Ctrl+Space or their checkbox, in the
+    /// order they were picked. Delete removes them all while there are any.
+    marked: Vec<u64>,
+    /// Line diff between two marked entries, shown instead of the list.
+    diff_view: Option<Vec<DiffLine>>,
+    /// Browsing the trash instead of the history.
+    trash_view: bool,
+    /// Id of the entry opened in full with `EnterAction::OpenDetail`.
+    detail_view: Option<u64>,
+    /// Ids of the filtered results waiting for the user to confirm deleting them.
+    confirm_delete: Option<Vec<u64>>,
+    /// Locked entries the last delete left in place, shown until the next
+    /// delete or hide so the skip isn't silent.
+    kept_locked: usize,
+    /// Ids of the first entry of similar-entry groups the user expanded.
+    expanded_groups: HashSet<u64>,
+    /// "12 / 100 matches" for the (result, entry) counts it was made for,
+    /// kept so the line isn't formatted again every frame.
+    match_status: Option<((usize, usize), String)>,
+    /// Search box hint for the (entry count, mode) it was made for.
+    search_hint: Option<((usize, SearchMode), String)>,
+    /// Counts for the `show_text_stats` line, for the entry (id, byte length)
+    /// they were made for; an incremental copy grows an entry in place.
+    text_stats: Option<((u64, usize), String)>,
+    /// `similar_runs` of the results for their (id, byte length) list, so
+    /// the pairwise comparison only runs when the results change.
+    similar_runs: Option<(ResultKey, Vec<Range<usize>>)>,
+    /// Code language guessed for the entry (id, byte length) shown in the
+    /// preview pane or detail view, with `Config::syntax_highlight`.
+    language: Option<((u64, usize), Option<Language>)>,
+    /// Highlighted layout of the detail view's text, for the (entry id,
+    /// text hash, dark mode) it was made for.
+    detail_job: Option<((u64, u64, bool), egui::text::LayoutJob)>,
+    /// Thumbnail textures of image entries, by entry id.
+    thumbnails: HashMap<u64, egui::TextureHandle>,
+    /// Top entry id rendered in the current showing of the window.
+    shown_top_id: Option<u64>,
+    /// Top entry id when the window was last hidden; a different top entry
+    /// is new to the user and gets the "new" badge.
+    seen_top_id: Option<u64>,
+    /// Page titles shown for URL entries with `Config::fetch_url_titles`.
+    url_titles: TitleFetcher,
+    /// Apply `Config::initial_selection` once the results of a new showing
+    /// are known.
+    preselect_pending: bool,
+    /// Whether the preview pane is open; starts as `Config::show_preview_pane`.
+    preview_open: bool,
+    /// Starts as `Config::search_mode`; Ctrl+R or the button cycles it.
+    search_mode: SearchMode,
+    /// Match the query's case exactly; toggled with Alt+C or the Aa button.
+    case_sensitive: bool,
+    /// Starts as `Config::list_order`; Alt+S or the order button cycles it.
+    list_order: ListOrder,
+    /// Searches large histories off the UI thread; started on first use.
+    search_worker: Option<SearchWorker>,
+    /// Theme to show, set from the config and by the tray's Theme submenu.
+    theme: Arc<Mutex<Theme>>,
+    /// Theme last applied to the context.
+    applied_theme: Option<Theme>,
 }
 
 impl ClipboardHistoryApp {
@@ -33,41 +191,757 @@ impl ClipboardHistoryApp {
         history: Arc<Mutex<History>>,
         visible: Arc<Mutex<bool>>,
         config: Config,
+        storage: Option<&dyn eframe::Storage>,
     ) -> Self {
+        let keymap = Keymap::from_overrides(&config.keybindings).unwrap_or_else(|e| {
+            eprintln!("Invalid keybindings ({e}), using defaults");
+            Keymap::default()
+        });
+        let loading = Arc::new(Mutex::new(config.lazy_load));
+        let ui_state: UiState = storage
+            .and_then(|s| eframe::get_value(s, UI_STATE_KEY))
+            .unwrap_or_default();
+        let window_size = ui_state.window_size.unwrap_or(WindowSize {
+            width: config.window_width,
+            height: config.window_height,
+        });
+        let preview_open = ui_state.preview_open.unwrap_or(config.show_preview_pane);
+        let search_mode = ui_state.search_mode.unwrap_or(config.search_mode);
+        let list_order = ui_state.list_order.unwrap_or(config.list_order);
+        let theme = Arc::new(Mutex::new(config.theme));
         Self {
             history,
             search_query: String::new(),
             selected_index: 0,
+            selected_id: None,
             visible,
             config,
+            keymap,
             initialized: false,
             was_visible: false,
             focused_once: false,
-            _tray: None,
+            search_has_focus: false,
+            tray: None,
+            watchdog: None,
+            hotkeys: None,
             cursor_pos: Arc::new(Mutex::new((0.0, 0.0))),
-            last_height: 0.0,
+            last_height: ui_state.last_height,
+            window_size,
+            loading,
+            monitor_running: Arc::new(Mutex::new(true)),
+            quitting: Arc::new(Mutex::new(false)),
+            capture_paused: Arc::new(Mutex::new(false)),
+            palette: false,
+            kind_filter: None,
+            marked: Vec::new(),
+            diff_view: None,
+            trash_view: false,
+            detail_view: None,
+            confirm_delete: None,
+            kept_locked: 0,
+            expanded_groups: HashSet::new(),
+            match_status: None,
+            search_hint: None,
+            text_stats: None,
+            similar_runs: None,
+            language: None,
+            detail_job: None,
+            thumbnails: HashMap::new(),
+            shown_top_id: None,
+            seen_top_id: None,
+            url_titles: TitleFetcher::default(),
+            preselect_pending: false,
+            preview_open,
+            search_mode,
+            case_sensitive: false,
+            list_order,
+            search_worker: None,
+            theme,
+            applied_theme: None,
+        }
+    }
+
+    /// Run a shortcut that edits the entry with the given id, saving the
+    /// history if it changed.
+    fn apply_entry_action(&mut self, action: Action, id: u64) {
+        let mut history = self.history.lock().unwrap();
+        let changed = match action {
+            Action::ToggleEphemeral => history.toggle_ephemeral(id),
+            Action::ToggleLock => history.toggle_lock(id),
+            Action::ToggleTemplate => history.toggle_template(id),
+            Action::TogglePin => history.toggle_pin(id),
+            // The next frame clamps the selection if it was the last result
+            Action::DeleteEntry => {
+                let removal = history.remove_by_id(id);
+                self.kept_locked = removal.locked;
+                removal.removed > 0
+            }
+            Action::ExportEntry => {
+                if let Some(entry) = history.entries().iter().find(|e| e.id == id) {
+                    let path = storage::export_dir().join(storage::export_file_name(entry));
+                    if let Err(e) = storage::export_entry(entry, &path) {
+                        eprintln!("Failed to export entry to {}: {e}", path.display());
+                    }
+                }
+                false
+            }
+            Action::SplitEntry => {
+                let split = history.split_entry(id) > 0;
+                if split {
+                    self.selected_index = 0;
+                }
+                split
+            }
+            _ => false,
+        };
+        if changed {
+            save_history(&history);
+        }
+    }
+
+    /// Delete the marked entries (locked ones are kept), saving once.
+    fn delete_marked(&mut self) {
+        let mut history = self.history.lock().unwrap();
+        let removal = delete_marked(&mut history, &mut self.marked);
+        self.kept_locked = removal.locked;
+        if removal.removed > 0 {
+            save_history(&history);
+        }
+    }
+
+    /// Hide the window and reset the search state for the next show.
+    fn hide(&mut self, ctx: &egui::Context) {
+        *self.visible.lock().unwrap() = false;
+        self.set_viewport_shown(ctx, false);
+        self.search_query.clear();
+        self.selected_index = 0;
+        self.selected_id = None;
+        self.kept_locked = 0;
+        self.palette = false;
+        self.kind_filter = None;
+        self.marked.clear();
+        self.diff_view = None;
+        self.trash_view = false;
+        self.detail_view = None;
+        self.confirm_delete = None;
+        self.expanded_groups.clear();
+    }
+
+    fn search_bar(&mut self, ui: &mut egui::Ui, entry_count: usize) {
+        let key = (entry_count, self.search_mode);
+        if self.search_hint.as_ref().is_none_or(|(k, _)| *k != key) {
+            self.search_hint = Some((key, search_hint(entry_count, self.search_mode)));
+        }
+        let hint = match &self.search_hint {
+            Some((_, hint)) if !self.palette => hint.as_str(),
+            _ => "Search symbols...",
+        };
+        let search_response = ui
+            .horizontal(|ui| {
+                let mode = ui
+                    .small_button(self.search_mode.label())
+                    .on_hover_text(with_shortcut(
+                        "Search mode: fuzzy, exact or regex",
+                        &self.keymap,
+                        Action::CycleSearchMode,
+                    ));
+                if mode.clicked() {
+                    self.search_mode = self.search_mode.next();
+                    self.selected_index = 0;
+                }
+                let case = ui
+                    .selectable_label(self.case_sensitive, "Aa")
+                    .on_hover_text(with_shortcut(
+                        "Match case",
+                        &self.keymap,
+                        Action::ToggleCaseSensitive,
+                    ));
+                if case.clicked() {
+                    self.case_sensitive = !self.case_sensitive;
+                    self.selected_index = 0;
+                }
+                let order = ui
+                    .small_button(self.list_order.label())
+                    .on_hover_text(with_shortcut(
+                        "Order while not searching: newest, oldest, most used",
+                        &self.keymap,
+                        Action::CycleListOrder,
+                    ));
+                if order.clicked() {
+                    self.list_order = self.list_order.next();
+                    self.selected_index = 0;
+                }
+                if !self.palette {
+                    for kind in ContentKind::ALL {
+                        let chip = ui
+                            .selectable_label(self.kind_filter == Some(kind), kind.icon())
+                            .on_hover_text(format!("Only {} entries", kind.label()));
+                        if chip.clicked() {
+                            self.kind_filter = (self.kind_filter != Some(kind)).then_some(kind);
+                            self.selected_index = 0;
+                        }
+                    }
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.search_query)
+                        .hint_text(hint)
+                        .desired_width(f32::INFINITY),
+                )
+            })
+            .inner;
+
+        if search_response.changed() {
+            // The pending deletion was for the old results
+            self.confirm_delete = None;
+        }
+
+        // Auto-focus the search bar, unless the window was shown without
+        // taking focus and the user hasn't clicked into it yet
+        self.search_has_focus = search_response.has_focus();
+        if !self.search_has_focus && (self.config.grab_focus_on_show || self.focused_once) {
+            search_response.request_focus();
+        }
+    }
+
+    /// Apply the theme if it changed (at startup, or picked from the tray).
+    /// A theme picked at runtime is saved to the config file.
+    fn apply_theme(&mut self, ctx: &egui::Context) {
+        let theme = *self.theme.lock().unwrap();
+        if self.applied_theme == Some(theme) {
+            return;
+        }
+        ctx.set_theme(match theme {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        });
+        self.applied_theme = Some(theme);
+        if theme != self.config.theme {
+            self.config.theme = theme;
+            if let Err(e) = config::save_keys(&self.config, &["theme"]) {
+                eprintln!("Failed to save theme: {e}");
+            }
+        }
+    }
+
+    /// Save where the window is, to reopen it there with
+    /// `Config::follow_cursor` off. The config file is only written when
+    /// the position changed.
+    fn remember_position(&mut self, ctx: &egui::Context) {
+        let Some(rect) = ctx.input(|i| i.viewport().outer_rect) else {
+            return;
+        };
+        let position = Some([rect.min.x, rect.min.y]);
+        if position == self.config.window_position {
+            return;
+        }
+        self.config.window_position = position;
+        if let Err(e) = config::save_keys(&self.config, &["window_position"]) {
+            eprintln!("Failed to save window position: {e}");
+        }
+    }
+
+    /// Save a size the user resized the window to, so it reopens (and
+    /// starts next time) at that size.
+    fn remember_size(&mut self, ctx: &egui::Context) {
+        let Some(rect) = ctx.input(|i| i.viewport().inner_rect) else {
+            return;
+        };
+        let Some(size) = resized_size(self.window_size, self.last_height, rect.size()) else {
+            return;
+        };
+        self.window_size = size;
+        self.last_height = rect.height();
+        self.config.window_width = size.width;
+        self.config.window_height = size.height;
+        if let Err(e) = config::save_keys(&self.config, &["window_width", "window_height"]) {
+            eprintln!("Failed to save window size: {e}");
+        }
+    }
+
+    /// Pick up hotkey settings edited in the config file since they were
+    /// last read. The listener looks its bindings up on every key event, so
+    /// new ones apply from the next key press without a restart.
+    fn reload_hotkeys(&mut self) {
+        let Some(hotkeys) = &self.hotkeys else {
+            return;
+        };
+        let edited = config::load();
+        if edited.hotkey == self.config.hotkey
+            && edited.capture_hotkey == self.config.capture_hotkey
+            && edited.hotkey_toggles == self.config.hotkey_toggles
+        {
+            return;
+        }
+        self.config.hotkey = edited.hotkey;
+        self.config.capture_hotkey = edited.capture_hotkey;
+        self.config.hotkey_toggles = edited.hotkey_toggles;
+        hotkeys.reconfigure(Bindings::from_config(&self.config));
+    }
+
+    /// Show or hide the OS window. With `Config::safe_mode` it is minimized
+    /// instead of hidden: without the native `ShowWindow` call a hidden
+    /// window is never repainted on Windows, so the tray couldn't bring it back.
+    fn set_viewport_shown(&self, ctx: &egui::Context, shown: bool) {
+        if self.config.safe_mode {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(!shown));
+            return;
+        }
+        if !shown {
+            // Hide natively first to avoid a black flash before egui
+            // presents the final frame.
+            platform::hide_window_native();
+        }
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(shown));
+    }
+
+    /// List trashed entries, most recently deleted first. Enter or a click
+    /// restores one to the top of the history.
+    fn show_trash(&mut self, ui: &mut egui::Ui, actions: &[Action]) {
+        let mut history = self.history.lock().unwrap();
+        let trash = history.trash();
+        let wrap = self.config.wrap_navigation;
+        for action in actions {
+            match action {
+                Action::NavUp => {
+                    self.selected_index = step_up(self.selected_index, trash.len(), 1, wrap)
+                }
+                Action::NavDown => {
+                    self.selected_index = step_down(self.selected_index, trash.len(), 1, wrap)
+                }
+                _ => {}
+            }
+        }
+        self.selected_index = self.selected_index.min(trash.len().saturating_sub(1));
+        let mut restore = trash
+            .get(self.selected_index)
+            .filter(|_| actions.contains(&Action::Select))
+            .map(|t| t.entry.id);
+
+        ui.small("Trash — Enter or click restores an entry (Ctrl+Shift+D or Escape to close)");
+        if trash.is_empty() {
+            ui.add_space(20.0);
+            ui.vertical_centered(|ui| ui.label("The trash is empty."));
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, trashed) in trash.iter().enumerate() {
+                let preview: String = trashed
+                    .entry
+                    .content
+                    .chars()
+                    .take(80)
+                    .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+                    .collect();
+                let deleted = trashed.deleted_at.with_timezone(&chrono::Local);
+                let text = format!("{preview}  · deleted {}", deleted.format("%Y-%m-%d %H:%M"));
+                let is_selected = i == self.selected_index;
+                let response = ui.add(egui::SelectableLabel::new(is_selected, text));
+                if response.clicked() {
+                    restore = Some(trashed.entry.id);
+                }
+                if is_selected {
+                    response.scroll_to_me(Some(egui::Align::Center));
+                }
+            }
+        });
+
+        if let Some(id) = restore {
+            if history.restore_from_trash(id) {
+                save_history(&history);
+            }
+        }
+    }
+
+    /// Paste `entry` (expanding it if it's a template, running
+    /// `Config::on_paste_cmd` if set), record the use and hide the window.
+    /// With `Config::auto_paste` it is then pasted into the previous app.
+    /// With `keep_open` (sticky paste) the entry only goes on the clipboard
+    /// and the window stays up for the next pick.
+    fn paste_entry(
+        &mut self,
+        ctx: &egui::Context,
+        entry: ClipboardEntry,
+        format: PasteFormat,
+        keep_open: bool,
+    ) {
+        let pending = match &entry.image {
+            Some(image) => {
+                clipboard::set_image(image);
+                None
+            }
+            None => self.paste_text(&entry, format),
+        };
+        let mut history = self.history.lock().unwrap();
+        history.mark_used(entry.id);
+        // The monitor skips our own paste, so move the entry up here. An
+        // expanded template or transformed text is a one-off and stays put,
+        // and with ignore_own_copies no paste moves its entry to the top
+        let one_off = entry.template || matches!(format, PasteFormat::Transformed(_));
+        if entry.image.is_none() && !one_off && !self.config.ignore_own_copies {
+            history.move_to_front(entry.id);
+        }
+        // Ephemeral entries self-destruct after their one paste
+        history.consume_ephemeral(entry.id);
+        save_history(&history);
+        drop(history);
+        if keep_open {
+            // Unlike hide(), leave search_query alone so the user can keep
+            // picking from the same filtered list; the search box takes the
+            // focus back on the next frame
+            return;
+        }
+        self.hide(ctx);
+        if self.config.auto_paste {
+            hotkey::paste_into_previous_app(pending);
+        }
+    }
+
+    /// Put a text entry on the clipboard in `format`. Returns the thread
+    /// running `Config::on_paste_cmd`, which writes the clipboard when the
+    /// hook is done.
+    fn paste_text(
+        &self,
+        entry: &ClipboardEntry,
+        format: PasteFormat,
+    ) -> Option<std::thread::JoinHandle<()>> {
+        let content = if entry.template {
+            let template_ctx = TemplateContext {
+                now: chrono::Local::now(),
+                clipboard: clipboard::current_text().unwrap_or_default(),
+            };
+            expand_template(&entry.content, &template_ctx)
+        } else {
+            entry.content.clone()
+        };
+        let content = match format {
+            PasteFormat::Transformed(transform) => transform.apply(&content),
+            PasteFormat::AsIs | PasteFormat::PlainText => content,
+        };
+        let html = paste_html(entry, format).map(str::to_string);
+        let target = self.config.paste_target;
+        let clear_after = self
+            .config
+            .clear_clipboard_after_secs
+            .filter(|_| entry.ephemeral || self.config.clear_clipboard_on_all_pastes)
+            .map(std::time::Duration::from_secs);
+        match self.config.on_paste_cmd.clone() {
+            Some(cmd) => {
+                // Off the UI thread, so a slow hook can't freeze the window
+                Some(std::thread::spawn(move || {
+                    let pasted =
+                        hooks::run(&cmd, &content, hooks::HOOK_TIMEOUT).unwrap_or_else(|e| {
+                            eprintln!("Paste hook failed, pasting unchanged: {e}");
+                            content.clone()
+                        });
+                    // Changed output loses the HTML, which no longer matches it
+                    let html = html.filter(|_| pasted == content);
+                    paste(&pasted, html.as_deref(), target, clear_after);
+                }))
+            }
+            None => {
+                paste(&content, html.as_deref(), target, clear_after);
+                None
+            }
+        }
+    }
+
+    /// The full content of entry `id`, selectable so part of it can be
+    /// copied with Ctrl+C. PasteSelected or the button pastes all of it.
+    fn show_detail(&mut self, ui: &mut egui::Ui, id: u64, actions: &[Action]) {
+        let history = Arc::clone(&self.history);
+        let history = history.lock().unwrap();
+        let Some(entry) = history.get_by_id(id) else {
+            self.detail_view = None;
+            return;
+        };
+        let language = self.code_language(entry);
+        let shown = truncate_bytes(&entry.content, DETAIL_MAX_BYTES).to_string();
+        let total_len = entry.content.len();
+        let truncated = entry.truncated;
+        drop(history);
+
+        let mut paste = actions.contains(&Action::PasteSelected);
+        let plain = actions.contains(&Action::PastePlain);
+        if truncated {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "✂ Only the start of this copy was kept (Config::max_entry_bytes)",
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.small("Select text and Ctrl+C to copy part of it (Escape to close)");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                paste |= ui.button("Paste (Ctrl+Enter)").clicked();
+            });
+        });
+        if shown.len() < total_len {
+            ui.small(format!(
+                "Showing the first {} of {}; pasting uses all of it",
+                history::format_size(shown.len()),
+                history::format_size(total_len)
+            ));
+        }
+        let key = (id, text_hash(&shown), ui.visuals().dark_mode);
+        let cached_job = &mut self.detail_job;
+        egui::ScrollArea::both().show(ui, |ui| {
+            let mut content = shown.as_str();
+            let mut edit = egui::TextEdit::multiline(&mut content)
+                .code_editor()
+                .desired_width(f32::INFINITY);
+            let mut layouter = language.map(|lang| {
+                move |ui: &egui::Ui, code: &str, wrap_width: f32| {
+                    let (_, job) = match cached_job.take() {
+                        Some(cached) if cached.0 == key => cached_job.insert(cached),
+                        _ => {
+                            cached_job.insert((key, highlight::layout_job(code, lang, ui.style())))
+                        }
+                    };
+                    let mut job = job.clone();
+                    job.wrap.max_width = wrap_width;
+                    ui.fonts(|fonts| fonts.layout_job(job))
+                }
+            });
+            if let Some(layouter) = &mut layouter {
+                edit = edit.layouter(layouter);
+            }
+            ui.add(edit);
+        });
+        if paste || plain {
+            let Some(entry) = self.history.lock().unwrap().get_by_id(id).cloned() else {
+                return;
+            };
+            let format = if plain {
+                PasteFormat::PlainText
+            } else {
+                PasteFormat::AsIs
+            };
+            self.paste_entry(ui.ctx(), entry, format, false);
+        }
+    }
+
+    /// The full content of the selected entry, wrapped and scrollable, in
+    /// a fixed-height pane at the bottom of the window. Nothing while no
+    /// entry is selected.
+    fn preview_pane(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.selected_id else {
+            return;
+        };
+        let history = Arc::clone(&self.history);
+        let history = history.lock().unwrap();
+        let Some(entry) = history.get_by_id(id) else {
+            return;
+        };
+        let language = self.code_language(entry);
+        let content = preview_text(&entry.content, PREVIEW_MAX_CHARS).into_owned();
+        drop(history);
+        egui::TopBottomPanel::bottom("preview")
+            .exact_height(PREVIEW_HEIGHT)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let label = match language {
+                            Some(lang) => {
+                                let job = highlight::layout_job(&content, lang, ui.style());
+                                egui::Label::new(job)
+                            }
+                            None => egui::Label::new(egui::RichText::new(content).monospace()),
+                        };
+                        ui.add(label.wrap());
+                    });
+            });
+    }
+
+    /// The language to highlight `entry` as, `None` for prose or with
+    /// `Config::syntax_highlight` off. Guessed once per shown entry.
+    fn code_language(&mut self, entry: &ClipboardEntry) -> Option<Language> {
+        if !self.config.syntax_highlight || entry.image.is_some() {
+            return None;
         }
+        let key = (entry.id, entry.content.len());
+        match self.language {
+            Some((k, language)) if k == key => language,
+            _ => {
+                let language = highlight::guess_language(&entry.content);
+                self.language = Some((key, language));
+                language
+            }
+        }
+    }
+
+    /// Diff the last two marked entries, older one first. `None` if fewer
+    /// than two marked entries still exist.
+    fn diff_marked(&self) -> Option<Vec<DiffLine>> {
+        let [a, b] = self.marked.last_chunk::<2>()?;
+        let history = self.history.lock().unwrap();
+        let (a, b) = (history.get_by_id(*a)?, history.get_by_id(*b)?);
+        let (old, new) = if a.created_at <= b.created_at {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        Some(diff::line_diff(&old.content, &new.content))
     }
 }
 
 impl eframe::App for ClipboardHistoryApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let state = UiState {
+            last_height: self.last_height,
+            window_size: Some(self.window_size),
+            list_order: Some(self.list_order),
+            preview_open: Some(self.preview_open),
+            search_mode: Some(self.search_mode),
+        };
+        eframe::set_value(storage, UI_STATE_KEY, &state);
+    }
+
+    /// Closed from the tray's Quit (or by the OS): let the clipboard monitor
+    /// save what it held back before the process ends.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        clipboard::shut_down(&self.history, &self.quitting, &self.monitor_running);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Start background threads and tray on first frame (now we have the real Context)
         if !self.initialized {
             self.initialized = true;
 
-            // Start clipboard monitor
-            clipboard::start_monitor(
+            if self.config.accessible {
+                // Zoom scales text and hit targets alike, and keeps sizes in
+                // points so the height calculation below still holds
+                ctx.set_zoom_factor(ACCESSIBLE_ZOOM);
+                ctx.style_mut(|style| style.spacing.button_padding.y += ACCESSIBLE_ROW_PADDING);
+            }
+
+            // Start clipboard monitor. With lazy loading the history is read
+            // on the monitor's thread first, so the monitor can never push into
+            // (and save over history.json) the empty placeholder before the
+            // real history has been swapped in.
+            let poll_interval = std::time::Duration::from_millis(self.config.poll_interval_ms);
+            let debounce = std::time::Duration::from_millis(self.config.capture_debounce_ms);
+            let watchdog = Watchdog::start(self.config.max_thread_restarts);
+            let start_monitor = {
+                let history = Arc::clone(&self.history);
+                let ctx = ctx.clone();
+                let running = Arc::clone(&self.monitor_running);
+                let paused = Arc::clone(&self.capture_paused);
+                let quit = Arc::clone(&self.quitting);
+                move || {
+                    clipboard::start_monitor(
+                        Arc::clone(&history),
+                        poll_interval,
+                        debounce,
+                        ctx.clone(),
+                        Arc::clone(&running),
+                        Arc::clone(&paused),
+                        Arc::clone(&quit),
+                    )
+                }
+            };
+            if self.config.lazy_load {
+                let history = Arc::clone(&self.history);
+                let loading = Arc::clone(&self.loading);
+                let config = self.config.clone();
+                let ctx = ctx.clone();
+                let watchdog = watchdog.clone();
+                std::thread::spawn(move || {
+                    let loaded = storage::load_configured(&config);
+                    *history.lock().unwrap() = loaded;
+                    *loading.lock().unwrap() = false;
+                    ctx.request_repaint();
+                    watchdog.watch("clipboard monitor", start_monitor);
+                });
+            } else {
+                watchdog.watch("clipboard monitor", start_monitor);
+            }
+
+            if self.config.sync_on_power_events {
+                let history = Arc::clone(&self.history);
+                let loading = Arc::clone(&self.loading);
+                let paused = Arc::clone(&self.capture_paused);
+                let ctx = ctx.clone();
+                platform::on_power_event(move |event| match event {
+                    // Never save over history.json with the lazy-load placeholder
+                    PowerEvent::Suspend if !*loading.lock().unwrap() => {
+                        save_history(&clipboard::lock_recovering(&history));
+                    }
+                    PowerEvent::Suspend => {}
+                    // The clipboard may have changed while asleep, e.g. synced from another device
+                    PowerEvent::Resume if !*clipboard::lock_recovering(&paused) => {
+                        clipboard::capture_now(&history, &ctx)
+                    }
+                    PowerEvent::Resume => {}
+                });
+            }
+
+            // Start hotkey listener (also tracks global mouse cursor position)
+            if self.config.safe_mode {
+                if self.config.capture_hotkey.is_some() {
+                    eprintln!("Capture hotkey disabled: global hooks are off in safe mode");
+                }
+            } else {
+                let visible = Arc::clone(&self.visible);
+                let ctx = ctx.clone();
+                let cursor_pos = Arc::clone(&self.cursor_pos);
+                let history = Arc::clone(&self.history);
+                let hotkeys = HotkeyHandle::new(Bindings::from_config(&self.config));
+                self.hotkeys = Some(hotkeys.clone());
+                watchdog.watch("hotkey listener", move || {
+                    hotkey::start_listener(
+                        Arc::clone(&visible),
+                        ctx.clone(),
+                        Arc::clone(&cursor_pos),
+                        Arc::clone(&history),
+                        hotkeys.clone(),
+                    )
+                });
+            }
+            self.watchdog = Some(watchdog.clone());
+
+            // Build system tray with the real egui Context
+            let icon = tray::build_tray(
+                Arc::clone(&self.visible),
                 Arc::clone(&self.history),
-                std::time::Duration::from_millis(self.config.poll_interval_ms),
+                Arc::clone(&self.theme),
+                Arc::clone(&self.capture_paused),
+                Arc::clone(&self.quitting),
+                Arc::clone(&self.monitor_running),
                 ctx.clone(),
             );
+            let status = Rc::new(RefCell::new(TrayStatus::new(
+                icon,
+                Arc::clone(&self.history),
+                watchdog,
+            )));
+            // A hidden window runs no frames on Windows, so a timer keeps
+            // the tooltip's count current meanwhile
+            let ticking = Rc::clone(&status);
+            platform::every_ui_tick(TOOLTIP_REFRESH, move || {
+                if let Ok(mut status) = ticking.try_borrow_mut() {
+                    status.refresh();
+                }
+            });
+            self.tray = Some(status);
 
-            // Start hotkey listener (also tracks global mouse cursor position)
-            hotkey::start_listener(Arc::clone(&self.visible), ctx.clone(), Arc::clone(&self.cursor_pos));
+            // Start at the size remembered from the last run instead of
+            // the configured one, so the first frame doesn't jump.
+            if self.last_height > 0.0 {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
+                    self.window_size.width,
+                    self.last_height,
+                )));
+            }
+        }
 
-            // Build system tray with the real egui Context
-            self._tray = Some(tray::build_tray(Arc::clone(&self.visible), ctx.clone()));
+        self.apply_theme(ctx);
+
+        // Report the entry count and thread restarts in the tray tooltip.
+        // The tray icon belongs to this (the main) thread, so it's updated here.
+        if let Some(tray) = &self.tray {
+            if let Ok(mut tray) = tray.try_borrow_mut() {
+                tray.refresh();
+            }
         }
 
         // Poll periodically to check visibility flag changes from hotkey/tray threads
@@ -78,39 +952,85 @@ impl eframe::App for ClipboardHistoryApp {
 
         if is_visible && !self.was_visible {
             // Just became visible — show window, move to cursor, reset state
+            self.reload_hotkeys();
             self.focused_once = false;
-            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
-            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
-
-            // Move window near mouse cursor using globally tracked position.
-            // If the window would extend below/right of the screen, flip/clamp accordingly.
-            let (cx, cy) = *self.cursor_pos.lock().unwrap();
-            let cx = cx as f32;
-            let cy = cy as f32;
-            let win_w = self.config.window_width;
-            let win_h = ctx.screen_rect().height();
-            let monitor = ctx.input(|i| i.viewport().monitor_size).unwrap_or(egui::vec2(1920.0, 1080.0));
-            let y = if cy - 50.0 + win_h > monitor.y {
-                // Not enough space below — show window above the cursor
-                (cy - win_h).max(0.0)
-            } else {
-                cy - 50.0
-            };
-            let x = if cx - 200.0 + win_w > monitor.x {
-                // Not enough space to the right — shift window left to stay on screen
-                (monitor.x - win_w).max(0.0)
-            } else {
-                cx - 200.0
-            };
-            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
+            self.set_viewport_shown(ctx, true);
+            if self.config.grab_focus_on_show {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
+
+            let tray_rect = self
+                .tray
+                .as_ref()
+                .filter(|_| self.config.window_placement == WindowPlacement::NearTray)
+                .and_then(|tray| tray.borrow().rect());
+            if let Some(rect) = tray_rect {
+                // The tray reports physical pixels; viewport commands take points
+                let ppp = ctx.pixels_per_point();
+                let tray = egui::Rect::from_min_size(
+                    egui::pos2(rect.position.x as f32, rect.position.y as f32) / ppp,
+                    egui::vec2(rect.size.width as f32, rect.size.height as f32) / ppp,
+                );
+                let window = egui::vec2(self.window_size.width, ctx.screen_rect().height());
+                let monitor = ctx
+                    .input(|i| i.viewport().monitor_size)
+                    .unwrap_or(egui::vec2(1920.0, 1080.0));
+                let pos = near_tray_position(tray, window, monitor);
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(pos));
+            } else if let Some([x, y]) = self
+                .config
+                .window_position
+                .filter(|_| !self.config.follow_cursor)
+            {
+                // Reopen where it was hidden, unless that monitor is gone
+                let ppp = ctx.pixels_per_point();
+                let on_monitor = platform::point_on_monitor(x * ppp, y * ppp);
+                let window = egui::vec2(self.window_size.width, ctx.screen_rect().height());
+                let monitor = ctx
+                    .input(|i| i.viewport().monitor_size)
+                    .unwrap_or(egui::vec2(1920.0, 1080.0));
+                let pos = restore_position(egui::pos2(x, y), window, monitor, on_monitor);
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(pos));
+            } else if !self.config.safe_mode {
+                // Move window near mouse cursor using globally tracked position.
+                // If the window would extend below/right of the screen, flip/clamp accordingly.
+                // Safe mode doesn't track the cursor, so the window stays put.
+                let (cx, cy) = *self.cursor_pos.lock().unwrap();
+                let cx = cx as f32;
+                let cy = cy as f32;
+                let win_w = self.window_size.width;
+                let win_h = ctx.screen_rect().height();
+                let monitor = ctx
+                    .input(|i| i.viewport().monitor_size)
+                    .unwrap_or(egui::vec2(1920.0, 1080.0));
+                let y = if cy - 50.0 + win_h > monitor.y {
+                    // Not enough space below — show window above the cursor
+                    (cy - win_h).max(0.0)
+                } else {
+                    cy - 50.0
+                };
+                let x = if cx - 200.0 + win_w > monitor.x {
+                    // Not enough space to the right — shift window left to stay on screen
+                    (monitor.x - win_w).max(0.0)
+                } else {
+                    cx - 200.0
+                };
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
+            }
 
             self.search_query.clear();
             self.selected_index = 0;
+            self.selected_id = None;
+            self.preselect_pending =
+                self.config.initial_selection == InitialSelection::PreviousClipboard;
         } else if !is_visible && self.was_visible {
-            // Just became hidden — hide natively first to avoid a black flash
-            // before egui presents the final frame.
-            crate::platform::hide_window_native();
-            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+            // Just became hidden
+            if !self.config.follow_cursor {
+                self.remember_position(ctx);
+            }
+            self.remember_size(ctx);
+            self.set_viewport_shown(ctx, false);
+            self.seen_top_id = self.shown_top_id;
         }
 
         self.was_visible = is_visible;
@@ -126,69 +1046,429 @@ impl eframe::App for ClipboardHistoryApp {
             self.focused_once = true;
         } else if self.focused_once {
             // Window had focus but lost it — hide
-            *self.visible.lock().unwrap() = false;
-            crate::platform::hide_window_native();
-            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
-            self.search_query.clear();
-            self.selected_index = 0;
+            self.hide(ctx);
             return;
         }
 
-        // Handle Escape key to hide
-        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
-            *self.visible.lock().unwrap() = false;
-            crate::platform::hide_window_native();
-            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        // Resolve shortcuts before the search field is drawn so the TextEdit
+        // never sees them (otherwise ArrowUp/Down would also move its cursor).
+        // Editing keys such as Home/End are left to the search field while it
+        // has text for them to act on.
+        let search_uses_editing_keys = self.search_has_focus && !self.search_query.is_empty();
+        // Left/Right only move the selection across a grid
+        let grid = self.palette || self.config.columns > 1;
+        let inactive: &[Action] = if grid {
+            &[]
+        } else {
+            &[Action::NavLeft, Action::NavRight]
+        };
+        let actions = self.keymap.resolve(ctx, search_uses_editing_keys, inactive);
+        let quick_select = actions
+            .iter()
+            .find_map(|action| action.quick_select_index());
+        if quick_select.is_some() {
+            // Alt+digit also types a character on some layouts (macOS Option)
+            ctx.input_mut(|i| {
+                i.events
+                    .retain(|event| !matches!(event, egui::Event::Text(_)))
+            });
+        }
+
+        if actions.contains(&Action::Hide) {
+            if self.diff_view.is_some() {
+                self.diff_view = None;
+            } else if self.detail_view.is_some() {
+                self.detail_view = None;
+            } else if self.confirm_delete.is_some() {
+                self.confirm_delete = None;
+            } else if self.trash_view {
+                self.trash_view = false;
+                self.selected_index = 0;
+            } else if self.config.escape_behavior == EscapeBehavior::ClearThenHide
+                && !self.search_query.is_empty()
+            {
+                self.search_query.clear();
+                self.selected_index = 0;
+            } else {
+                self.hide(ctx);
+                return;
+            }
+        }
+        if actions.contains(&Action::ClearSearch) {
             self.search_query.clear();
             self.selected_index = 0;
-            return;
+        }
+        for (action, grow) in [(Action::GrowWindow, true), (Action::ShrinkWindow, false)] {
+            if !actions.contains(&action) {
+                continue;
+            }
+            let presets = &self.config.window_presets;
+            if let Some(i) = self.window_size.step_preset(presets, grow) {
+                self.window_size = presets[i];
+                // Forces the resize below to send the new size
+                self.last_height = 0.0;
+            }
+        }
+        if actions.contains(&Action::ShowDiff) {
+            self.diff_view = match self.diff_view {
+                Some(_) => None,
+                None => self.diff_marked(),
+            };
+        }
+        if actions.contains(&Action::ToggleTrash) {
+            self.trash_view = !self.trash_view;
+            self.selected_index = 0;
+        }
+        if actions.contains(&Action::TogglePalette) {
+            self.palette = !self.palette;
+            self.selected_index = 0;
+        }
+        if actions.contains(&Action::CycleSearchMode) {
+            self.search_mode = self.search_mode.next();
+            self.selected_index = 0;
+        }
+        if actions.contains(&Action::ToggleCaseSensitive) {
+            self.case_sensitive = !self.case_sensitive;
+            self.selected_index = 0;
+        }
+        if actions.contains(&Action::CycleListOrder) {
+            self.list_order = self.list_order.next();
+            self.selected_index = 0;
+        }
+        if actions.contains(&Action::TogglePreview) {
+            self.preview_open = !self.preview_open;
+            self.last_height = 0.0;
+        }
+        if actions.contains(&Action::UndoDelete) {
+            let mut history = self.history.lock().unwrap();
+            if history.undo_remove() {
+                save_history(&history);
+            }
+        }
+
+        // With the search box at the bottom, results are drawn bottom-up so
+        // the best match sits right above it
+        let search_at_bottom = self.config.search_position == SearchPosition::Bottom;
+        let entry_count = self.history.lock().unwrap().entries().len();
+        if let Some(budget) = self.config.max_total_bytes {
+            let used = self.history.lock().unwrap().total_bytes();
+            egui::TopBottomPanel::bottom("footer").show(ctx, |ui| {
+                ui.small(format!(
+                    "{} of {} used",
+                    history::format_size(used),
+                    history::format_size(budget)
+                ));
+            });
+        }
+        if search_at_bottom {
+            egui::TopBottomPanel::bottom("search").show(ctx, |ui| {
+                ui.add_space(4.0);
+                self.search_bar(ui, entry_count);
+                ui.add_space(4.0);
+            });
+        }
+
+        // Added before the central panel so the list keeps the remaining height
+        let list_shown = self.diff_view.is_none() && !self.trash_view && self.detail_view.is_none();
+        let previewed_id = self.selected_id;
+        if self.preview_open && list_shown {
+            self.preview_pane(ctx);
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Search bar
-            let search_response = ui.add(
-                egui::TextEdit::singleline(&mut self.search_query)
-                    .hint_text("Search clipboard history...")
-                    .desired_width(f32::INFINITY),
-            );
+            if !search_at_bottom {
+                self.search_bar(ui, entry_count);
+            }
 
-            // Auto-focus the search bar
-            if !search_response.has_focus() {
-                search_response.request_focus();
+            if !*self.monitor_running.lock().unwrap() {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "⚠ Clipboard monitoring stopped — new copies aren't being saved",
+                );
+            } else if *self.capture_paused.lock().unwrap() {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "⏸ Capture paused — resume it from the tray menu",
+                );
+            }
+            if self.kept_locked > 0 {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    kept_locked_text(self.kept_locked),
+                );
             }
 
-            ui.add_space(4.0);
-            ui.separator();
+            if !search_at_bottom {
+                ui.add_space(4.0);
+                ui.separator();
+            }
+
+            // The diff, trash and detail views use the full window height
+            if (self.diff_view.is_some() || self.trash_view || self.detail_view.is_some())
+                && (self.window_size.height - self.last_height).abs() > 0.5
+            {
+                self.last_height = self.window_size.height;
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
+                    self.window_size.width,
+                    self.window_size.height,
+                )));
+            }
+            if let Some(lines) = &self.diff_view {
+                show_diff(ui, lines);
+                return;
+            }
+            if self.trash_view {
+                self.show_trash(ui, &actions);
+                return;
+            }
+            if let Some(id) = self.detail_view {
+                self.show_detail(ui, id, &actions);
+                return;
+            }
 
             // Get filtered entries
             let history = self.history.lock().unwrap();
             let entries = history.entries();
-            let results = fuzzy::search(&self.search_query, entries);
+            let id_query = fuzzy::parse_id_query(&self.search_query);
+            let mut pattern_error = None;
+            let background = id_query.is_none()
+                && !self.search_query.is_empty()
+                && entries.len() >= search::BACKGROUND_MIN_ENTRIES;
+            let finished = if background {
+                let worker = self.search_worker.get_or_insert_with(|| {
+                    let ctx = ctx.clone();
+                    SearchWorker::start(move || ctx.request_repaint())
+                });
+                let now = std::time::Instant::now();
+                let (query, mode) = (&self.search_query, self.search_mode);
+                if let Some(delay) = worker.update(query, mode, self.case_sensitive, entries, now) {
+                    ctx.request_repaint_after(delay);
+                }
+                worker.latest()
+            } else {
+                None
+            };
+            let mut results = match (id_query, finished) {
+                (Some(id), _) => history.get_by_id(id).map(|e| (e, 0)).into_iter().collect(),
+                // Last finished results, possibly for an older query, until
+                // the worker catches up
+                (None, Some(done)) => match done.ranked {
+                    Ok(ranked) => {
+                        let by_id: HashMap<u64, &ClipboardEntry> =
+                            entries.iter().map(|e| (e.id, e)).collect();
+                        ranked
+                            .into_iter()
+                            .filter_map(|(id, score)| Some((*by_id.get(&id)?, score)))
+                            .collect()
+                    }
+                    Err(e) => {
+                        pattern_error = Some(e);
+                        Vec::new()
+                    }
+                },
+                (None, None) => fuzzy::search_with(
+                    &self.search_query,
+                    entries,
+                    self.search_mode,
+                    self.case_sensitive,
+                )
+                .unwrap_or_else(|e| {
+                    pattern_error = Some(e);
+                    Vec::new()
+                }),
+            };
+            if self.search_query.is_empty() {
+                let min = self.config.min_display_length;
+                results.retain(|(entry, _)| meets_min_length(&entry.content, min));
+                apply_list_order(&mut results, self.list_order, |(entry, _)| entry.use_count);
+            }
+            if self.palette {
+                results.retain(|(entry, _)| entry.is_symbol());
+            } else if let Some(kind) = self.kind_filter {
+                results.retain(|(entry, _)| entry.kind == Some(kind));
+            }
+            // Pinned entries go first whatever their score; the sort is stable
+            results.sort_by_key(|(entry, _)| !entry.pinned);
+
+            let top_id = entries.first().map(|entry| entry.id);
+            let top_changed = top_id != self.shown_top_id;
+
+            // Badge a top entry captured since the window was last open,
+            // fading out over new_entry_badge_secs
+            self.shown_top_id = top_id;
+            let new_badge = self.config.new_entry_badge_secs.and_then(|secs| {
+                let top = entries.first()?;
+                let age = (chrono::Utc::now() - top.created_at).to_std().ok()?;
+                let fade = 1.0 - age.as_secs_f32() / secs as f32;
+                (Some(top.id) != self.seen_top_id && fade > 0.0).then_some((top.id, fade))
+            });
+
+            // Upload thumbnails of new image entries, forget removed ones
+            self.thumbnails
+                .retain(|id, _| entries.iter().any(|entry| entry.id == *id));
+            for (entry, _) in &results {
+                let Some(image) = &entry.image else {
+                    continue;
+                };
+                self.thumbnails.entry(entry.id).or_insert_with(|| {
+                    let thumb = image.thumbnail(THUMBNAIL_SIZE);
+                    let pixels = egui::ColorImage::from_rgba_unmultiplied(
+                        [thumb.width, thumb.height],
+                        thumb.bytes(),
+                    );
+                    ctx.load_texture(
+                        format!("thumbnail-{}", entry.id),
+                        pixels,
+                        egui::TextureOptions::LINEAR,
+                    )
+                });
+            }
+
+            // Collapse runs of near-duplicates behind their newest entry;
+            // maps that entry's id to how many similar ones follow it
+            let mut groups: HashMap<u64, usize> = HashMap::new();
+            if let Some(threshold) = self.config.collapse_similar_threshold {
+                if !self.palette {
+                    let key: ResultKey = results
+                        .iter()
+                        .map(|(entry, _)| (entry.id, entry.content.len()))
+                        .collect();
+                    if self.similar_runs.as_ref().is_none_or(|(k, _)| *k != key) {
+                        let contents: Vec<&str> = results
+                            .iter()
+                            .map(|(entry, _)| entry.content.as_str())
+                            .collect();
+                        self.similar_runs = Some((key, similar_runs(&contents, threshold)));
+                    }
+                    let runs = self.similar_runs.as_ref().map_or(&[][..], |(_, r)| r);
+                    let mut keep = vec![true; results.len()];
+                    for run in runs.iter().cloned() {
+                        let head = results[run.start].0.id;
+                        groups.insert(head, run.len() - 1);
+                        if !self.expanded_groups.contains(&head) {
+                            keep[run.start + 1..run.end].fill(false);
+                        }
+                    }
+                    let mut keep = keep.into_iter();
+                    results.retain(|_| keep.next().unwrap_or(true));
+                }
+            }
+
+            // A capture while the window is open pushes everything down a row;
+            // keep the selection on the entry it was on
+            if top_changed {
+                let ids: Vec<u64> = results.iter().map(|(entry, _)| entry.id).collect();
+                self.selected_index = follow_selection(&ids, self.selected_id, self.selected_index);
+            }
 
-            // Resize window height based on number of results
+            // Resize window height based on number of result rows
+            let (columns, row_height) = if self.palette {
+                let columns = (self.window_size.width / PALETTE_CELL_WIDTH) as usize;
+                (columns.max(1), PALETTE_ROW_HEIGHT)
+            } else {
+                let row_height = if self.config.accessible {
+                    ROW_HEIGHT + 2.0 * ACCESSIBLE_ROW_PADDING
+                } else {
+                    ROW_HEIGHT
+                };
+                (self.config.columns.max(1), row_height)
+            };
+            let filtering = !self.search_query.is_empty() || self.kind_filter.is_some();
             let desired_height = if results.is_empty() {
                 MIN_HEIGHT
             } else {
-                (HEADER_HEIGHT + results.len() as f32 * ROW_HEIGHT).min(MAX_HEIGHT)
+                let rows = results.len().div_ceil(columns);
+                let stats_height = if self.config.show_text_stats {
+                    STATS_HEIGHT
+                } else {
+                    0.0
+                };
+                let status_height = if filtering { STATS_HEIGHT } else { 0.0 };
+                let footer_height = if self.config.max_total_bytes.is_some() {
+                    STATS_HEIGHT
+                } else {
+                    0.0
+                };
+                let preview_height = if self.preview_open {
+                    PREVIEW_HEIGHT
+                } else {
+                    0.0
+                };
+                let chrome =
+                    HEADER_HEIGHT + stats_height + status_height + footer_height + preview_height;
+                (chrome + rows as f32 * row_height)
+                    .min(self.window_size.height)
+                    .max(self.config.window_min_height)
             };
             if (desired_height - self.last_height).abs() > 0.5 {
                 self.last_height = desired_height;
                 ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(
-                    self.config.window_width,
+                    self.window_size.width,
                     desired_height,
                 )));
             }
 
-            // Handle keyboard navigation
-            let up = ctx.input(|i| i.key_pressed(egui::Key::ArrowUp));
-            let down = ctx.input(|i| i.key_pressed(egui::Key::ArrowDown));
-            let enter = ctx.input(|i| i.key_pressed(egui::Key::Enter));
-
-            if up && self.selected_index > 0 {
-                self.selected_index -= 1;
+            if std::mem::take(&mut self.preselect_pending) {
+                let current = clipboard::current_text();
+                let mut newest_first: Vec<&ClipboardEntry> =
+                    results.iter().map(|(entry, _)| *entry).collect();
+                // Look for the previous clipboard newest first whatever the order
+                if self.search_query.is_empty() && self.list_order != ListOrder::NewestFirst {
+                    let position: HashMap<u64, usize> =
+                        entries.iter().enumerate().map(|(i, e)| (e.id, i)).collect();
+                    newest_first.sort_by_key(|entry| position.get(&entry.id));
+                }
+                let contents: Vec<&str> = newest_first.iter().map(|e| e.content.as_str()).collect();
+                let index = previous_clipboard_index(&contents, current.as_deref());
+                let ids: Vec<u64> = results.iter().map(|(entry, _)| entry.id).collect();
+                let previous = newest_first.get(index).map(|entry| entry.id);
+                self.selected_index = follow_selection(&ids, previous, index);
             }
-            if down && self.selected_index + 1 < results.len() {
-                self.selected_index += 1;
+
+            // Handle keyboard navigation
+            let mut select = false;
+            let mut paste_selected = false;
+            let mut paste_plain = false;
+            let mut keep_open = false;
+            let mut transform = None;
+            let mut delete_filtered = false;
+            let mut mark_selected = false;
+            let mut toggle_selected_group = false;
+            let mut entry_actions = Vec::new();
+            let wrap = self.config.wrap_navigation;
+            let len = results.len();
+            // Arrows move on screen, so step in display order
+            let flip = |i: usize| display_index(i, len, search_at_bottom);
+            for action in &actions {
+                let shown = flip(self.selected_index);
+                match action {
+                    Action::NavUp => self.selected_index = flip(step_up(shown, len, columns, wrap)),
+                    Action::NavDown => {
+                        self.selected_index = flip(step_down(shown, len, columns, wrap))
+                    }
+                    Action::NavLeft => self.selected_index = flip(step_up(shown, len, 1, wrap)),
+                    Action::NavRight => self.selected_index = flip(step_down(shown, len, 1, wrap)),
+                    Action::NavFirst => self.selected_index = 0,
+                    Action::NavLast => self.selected_index = results.len().saturating_sub(1),
+                    Action::Select => select = true,
+                    Action::PasteSelected => paste_selected = true,
+                    Action::PastePlain => paste_plain = true,
+                    Action::PasteKeepOpen => keep_open = true,
+                    Action::PasteUppercase => transform = Some(Transform::Uppercase),
+                    Action::PasteLowercase => transform = Some(Transform::Lowercase),
+                    Action::PasteTrimmed => transform = Some(Transform::Trim),
+                    Action::ToggleMark => mark_selected = true,
+                    Action::ToggleGroup => toggle_selected_group = true,
+                    Action::DeleteFiltered => delete_filtered = true,
+                    Action::ToggleEphemeral
+                    | Action::SplitEntry
+                    | Action::ToggleLock
+                    | Action::ToggleTemplate
+                    | Action::DeleteEntry
+                    | Action::ExportEntry
+                    | Action::TogglePin => entry_actions.push(*action),
+                    _ => {}
+                }
             }
 
             // Clamp selected index
@@ -196,58 +1476,1109 @@ impl eframe::App for ClipboardHistoryApp {
                 self.selected_index = results.len() - 1;
             }
 
+            if filtering && !results.is_empty() {
+                let counts = (results.len(), entries.len());
+                if self.match_status.as_ref().is_none_or(|(c, _)| *c != counts) {
+                    self.match_status = Some((counts, match_count_text(counts.0, counts.1)));
+                }
+                if let Some((_, status)) = &self.match_status {
+                    ui.small(status.as_str());
+                }
+            }
+
+            if self.config.show_text_stats {
+                if let Some((entry, _)) = results.get(self.selected_index) {
+                    let key = (entry.id, entry.content.len());
+                    if self.text_stats.as_ref().is_none_or(|(k, _)| *k != key) {
+                        let stats = text::text_stats(&entry.content);
+                        let line = format!(
+                            "{} chars · {} words · {} lines",
+                            stats.chars, stats.words, stats.lines
+                        );
+                        self.text_stats = Some((key, line));
+                    }
+                    if let Some((_, line)) = &self.text_stats {
+                        ui.small(line.as_str());
+                    }
+                }
+            }
+
+            if delete_filtered && !results.is_empty() {
+                self.confirm_delete = Some(results.iter().map(|(entry, _)| entry.id).collect());
+            }
+            let mut delete_confirmed = None;
+            if let Some(ids) = &self.confirm_delete {
+                // The ids stored when it was asked, not whatever matches now
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!(
+                        "Delete {} matching entries (locked ones are kept)? \
+                         Enter to confirm, Escape to cancel",
+                        ids.len()
+                    ),
+                );
+                if select {
+                    delete_confirmed = Some(ids.clone());
+                    select = false;
+                }
+            }
+
             // Handle Enter key selection
-            let mut selected_content: Option<String> = None;
-            if enter && !results.is_empty() {
-                selected_content = Some(results[self.selected_index].0.content.clone());
+            let mut selected_entry: Option<ClipboardEntry> = None;
+            let selected_id = results.get(self.selected_index).map(|(entry, _)| entry.id);
+            self.selected_id = selected_id;
+            if self.preview_open && selected_id != previewed_id {
+                // The pane was drawn for the previous selection
+                ctx.request_repaint();
+            }
+            let open_detail = self.config.enter_action == EnterAction::OpenDetail;
+            if select && open_detail {
+                self.detail_view = selected_id;
+            } else if (select || paste_selected || paste_plain || keep_open || transform.is_some())
+                && !results.is_empty()
+            {
+                selected_entry = Some(results[self.selected_index].0.clone());
+            }
+            if let Some((entry, _)) = quick_select.and_then(|i| results.get(i)) {
+                selected_entry = Some((*entry).clone());
+            }
+
+            let mut delete_marked_clicked = false;
+            if !self.marked.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} selected", self.marked.len()));
+                    delete_marked_clicked = ui
+                        .small_button("🗑 Delete selected")
+                        .on_hover_text("Delete (locked entries are kept)")
+                        .clicked();
+                    if ui.small_button("Clear selection").clicked() {
+                        self.marked.clear();
+                    }
+                });
             }
 
             // Scrollable entry list
+            let mut clicked_mark = None;
+            let mut clicked_delete = None;
+            let mut toggled_group =
+                selected_id.filter(|id| toggle_selected_group && groups.contains_key(id));
             if results.is_empty() {
                 ui.add_space(20.0);
+                let message = if *self.loading.lock().unwrap() {
+                    "Loading history...".to_string()
+                } else if let Some(id) = id_query {
+                    format!("No entry with id {id}")
+                } else if let Some(e) = &pattern_error {
+                    format!("No results: {e}")
+                } else if !self.search_query.is_empty() && !entries.is_empty() {
+                    format!("No matches for \"{}\"", self.search_query)
+                } else if self.palette {
+                    "No symbols in history yet. Copy an emoji!".to_string()
+                } else if let Some(kind) = self.kind_filter {
+                    format!("No {} entries", kind.label())
+                } else if !entries.is_empty() {
+                    "Every entry is shorter than min_display_length".to_string()
+                } else {
+                    "No clipboard history yet. Copy some text!".to_string()
+                };
                 ui.vertical_centered(|ui| {
-                    ui.label("No clipboard history yet. Copy some text!");
+                    ui.label(message);
                 });
             } else {
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    for (i, (entry, _score)) in results.iter().enumerate() {
-                        let is_selected = i == self.selected_index;
-
-                        // Truncate content for display (single line preview)
-                        let preview: String = entry
-                            .content
-                            .chars()
-                            .take(80)
-                            .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
-                            .collect();
-
-                        let label = egui::SelectableLabel::new(is_selected, &preview);
-                        let response = ui.add(label);
-
-                        if response.clicked() {
-                            selected_content = Some(entry.content.clone());
+                // Narrower previews in grid mode so columns stay readable
+                let preview_chars = (80 / columns).max(10);
+                let selected_index = self.selected_index;
+                let palette = self.palette;
+                let marked = &self.marked;
+                let expanded_groups = &self.expanded_groups;
+                let thumbnails = &self.thumbnails;
+                let accessible = self.config.accessible;
+                let url_titles = self.config.fetch_url_titles.then_some(&self.url_titles);
+                let age_dots = self.config.show_age_dots.then(chrono::Utc::now);
+                let timestamps =
+                    (self.config.show_timestamps && columns == 1).then(chrono::Utc::now);
+                let mut show_entry = |ui: &mut egui::Ui, i: usize, entry: &ClipboardEntry| {
+                    let is_selected = i == selected_index;
+                    // Screen readers get more of the content than the one-line preview
+                    let describe = |response: &egui::Response| {
+                        if accessible {
+                            let label = accessible_label(entry, marked.contains(&entry.id));
+                            response.widget_info(|| {
+                                egui::WidgetInfo::selected(
+                                    egui::WidgetType::SelectableLabel,
+                                    true,
+                                    is_selected,
+                                    &label,
+                                )
+                            });
                         }
+                    };
 
-                        // Auto-scroll to selected item
+                    if palette {
+                        let glyph =
+                            egui::RichText::new(entry.content.trim()).size(PALETTE_FONT_SIZE);
+                        let response = ui.add_sized(
+                            [PALETTE_CELL_WIDTH - 8.0, PALETTE_ROW_HEIGHT - 4.0],
+                            egui::SelectableLabel::new(is_selected, glyph),
+                        );
+                        describe(&response);
+                        if response.clicked() && ui.input(|i| i.modifiers.command) {
+                            clicked_mark = Some(entry.id);
+                        } else if response.clicked() {
+                            keep_open |= ui.input(|i| is_sticky_click(i.modifiers));
+                            selected_entry = Some(entry.clone());
+                        }
                         if is_selected {
                             response.scroll_to_me(Some(egui::Align::Center));
                         }
+                        return;
                     }
-                });
+
+                    // Truncate content for display (single line preview)
+                    let preview: String = entry
+                        .content
+                        .chars()
+                        .take(preview_chars)
+                        .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+                        .collect();
+                    // The raw URL is shown until its title has been fetched
+                    let title = url_titles
+                        .filter(|_| url_title::is_url(&entry.content))
+                        .and_then(|titles| titles.get(entry.content.trim(), ui.ctx()));
+                    let preview = match title {
+                        Some(title) => format!("{title} — {preview}"),
+                        None => preview,
+                    };
+
+                    let mut text = preview;
+                    if entry.truncated {
+                        text.insert_str(0, "✂ ");
+                    }
+                    if entry.ephemeral {
+                        text.insert_str(0, "🔥 ");
+                    }
+                    if entry.template {
+                        text.insert_str(0, "🧩 ");
+                    }
+                    if entry.locked {
+                        text.insert_str(0, "🔒 ");
+                    }
+                    if entry.pinned {
+                        text.insert_str(0, "📌 ");
+                    }
+                    let label = egui::SelectableLabel::new(is_selected, text);
+                    let row = ui.horizontal(|ui| {
+                        // Checkboxes while selecting, so a click can add rows
+                        // without pasting them
+                        if !marked.is_empty() {
+                            let mut checked = marked.contains(&entry.id);
+                            if ui.checkbox(&mut checked, "").changed() {
+                                clicked_mark = Some(entry.id);
+                            }
+                        }
+                        if let Some(texture) = thumbnails.get(&entry.id) {
+                            ui.add(egui::Image::new(texture).max_height(ROW_HEIGHT - 4.0));
+                        }
+                        let response = ui.add(label);
+                        if let Some(&hidden) = groups.get(&entry.id) {
+                            let toggle = if expanded_groups.contains(&entry.id) {
+                                "▴ collapse".to_string()
+                            } else {
+                                format!("… and {hidden} similar")
+                            };
+                            if ui.small_button(toggle).clicked() {
+                                toggled_group = Some(entry.id);
+                            }
+                        }
+                        let mut trailing = |ui: &mut egui::Ui| {
+                            // Locked entries can't be deleted, so they get no button
+                            let delete = !entry.locked && ui.small_button("✕").clicked();
+                            if delete {
+                                clicked_delete = Some(entry.id);
+                            }
+                            let now = timestamps?;
+                            let created = entry.created_at.with_timezone(&chrono::Local);
+                            let stamp = ui
+                                .weak(relative_time(entry.created_at, now))
+                                .on_hover_text(created.format("%Y-%m-%d %H:%M").to_string());
+                            Some(stamp.rect.left_center())
+                        };
+                        // Right-aligned when there's a timestamp
+                        let stamp_left = if timestamps.is_some() {
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                trailing,
+                            )
+                            .inner
+                        } else {
+                            trailing(ui)
+                        };
+                        (response, stamp_left)
+                    });
+                    let ((response, stamp_left), row_rect) = (row.inner, row.response.rect);
+                    describe(&response);
+                    if let Some(now) = age_dots {
+                        // In the panel margin, so the row keeps its full width
+                        ui.painter().circle_filled(
+                            row_rect.left_center() - egui::vec2(4.0, 0.0),
+                            2.5,
+                            age_color(entry.created_at, now),
+                        );
+                    }
+                    if let Some((_, fade)) = new_badge.filter(|(id, _)| *id == entry.id) {
+                        // Before the timestamp, or else after the row
+                        let (anchor, align) = match stamp_left {
+                            Some(left) => (left - egui::vec2(6.0, 0.0), egui::Align2::RIGHT_CENTER),
+                            None => (
+                                row_rect.right_center() + egui::vec2(6.0, 0.0),
+                                egui::Align2::LEFT_CENTER,
+                            ),
+                        };
+                        ui.painter().text(
+                            anchor,
+                            align,
+                            "new",
+                            egui::FontId::proportional(11.0),
+                            ui.visuals().hyperlink_color.gamma_multiply(fade),
+                        );
+                    }
+
+                    // Ctrl+Click marks the entry (e.g. for a diff) instead of
+                    // pasting it; Alt+Click pastes and keeps the window open
+                    if response.clicked() && ui.input(|i| i.modifiers.command) {
+                        clicked_mark = Some(entry.id);
+                    } else if response.clicked() {
+                        keep_open |= ui.input(|i| is_sticky_click(i.modifiers));
+                        selected_entry = Some(entry.clone());
+                    }
+                    if entry.image.is_none() {
+                        response.context_menu(|ui| {
+                            for picked in Transform::ALL {
+                                if ui.button(picked.label()).clicked() {
+                                    transform = Some(picked);
+                                    selected_entry = Some(entry.clone());
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+
+                    // Auto-scroll to selected item
+                    if is_selected {
+                        response.scroll_to_me(Some(egui::Align::Center));
+                    }
+                };
+
+                let display_order = (0..results.len()).map(flip);
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(search_at_bottom)
+                    .show(ui, |ui| {
+                        if columns == 1 && !palette {
+                            for i in display_order {
+                                show_entry(ui, i, results[i].0);
+                            }
+                        } else {
+                            egui::Grid::new("results")
+                                .num_columns(columns)
+                                .min_col_width(if palette {
+                                    PALETTE_CELL_WIDTH - 8.0
+                                } else {
+                                    self.window_size.width / columns as f32 - 12.0
+                                })
+                                .show(ui, |ui| {
+                                    for (pos, i) in display_order.enumerate() {
+                                        show_entry(ui, i, results[i].0);
+                                        if (pos + 1) % columns == 0 {
+                                            ui.end_row();
+                                        }
+                                    }
+                                });
+                        }
+                    });
             }
 
             // Handle selection (set clipboard and hide)
             drop(history); // Release lock before clipboard operation
-            if let Some(content) = selected_content {
-                if let Ok(mut clip) = arboard::Clipboard::new() {
-                    let _ = clip.set_text(&content);
+            if let Some(ids) = delete_confirmed {
+                self.confirm_delete = None;
+                let mut history = self.history.lock().unwrap();
+                let removal = history.remove_all(&ids);
+                self.kept_locked = removal.locked;
+                if removal.removed > 0 {
+                    save_history(&history);
                 }
-                *self.visible.lock().unwrap() = false;
-                crate::platform::hide_window_native();
-                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
-                self.search_query.clear();
                 self.selected_index = 0;
             }
+            if let Some(id) = toggled_group {
+                if !self.expanded_groups.remove(&id) {
+                    self.expanded_groups.insert(id);
+                }
+            }
+            if let Some(id) = clicked_mark.or(selected_id.filter(|_| mark_selected)) {
+                toggle_mark(&mut self.marked, id);
+            }
+            if let Some(id) = selected_id {
+                for action in entry_actions {
+                    if action == Action::DeleteEntry && !self.marked.is_empty() {
+                        self.delete_marked();
+                    } else {
+                        self.apply_entry_action(action, id);
+                    }
+                }
+            }
+            if delete_marked_clicked {
+                self.delete_marked();
+            }
+            if let Some(id) = clicked_delete {
+                self.apply_entry_action(Action::DeleteEntry, id);
+            }
+            if let Some(entry) = selected_entry {
+                if entry.truncated {
+                    // Only part of the copy was kept; paste it deliberately from the detail view
+                    self.detail_view = Some(entry.id);
+                } else {
+                    let format = match transform {
+                        Some(transform) => PasteFormat::Transformed(transform),
+                        None if paste_plain => PasteFormat::PlainText,
+                        None => PasteFormat::AsIs,
+                    };
+                    self.paste_entry(ctx, entry, format, keep_open);
+                }
+            }
         });
     }
 }
+
+/// What a screen reader announces for an entry: when it was copied, its
+/// flags and the start of its content.
+fn accessible_label(entry: &ClipboardEntry, marked: bool) -> String {
+    let flags = [
+        (marked, "marked"),
+        (entry.pinned, "pinned"),
+        (entry.locked, "locked"),
+        (entry.template, "template"),
+        (entry.ephemeral, "ephemeral"),
+        (entry.truncated, "truncated"),
+    ];
+    let mut label = format!(
+        "Copied {}",
+        entry
+            .created_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+    );
+    for (_, name) in flags.iter().filter(|(set, _)| *set) {
+        label.push_str(", ");
+        label.push_str(name);
+    }
+    match entry.content.char_indices().nth(ACCESSIBLE_MAX_CHARS) {
+        None => format!("{label}: {}", entry.content),
+        Some((end, _)) => format!("{label}: {}…", &entry.content[..end]),
+    }
+}
+
+/// A tooltip naming the key currently bound to `action`, if any.
+fn with_shortcut(text: &str, keymap: &Keymap, action: Action) -> String {
+    match keymap.combo_for(action) {
+        Some(combo) => format!("{text} ({combo})"),
+        None => text.to_string(),
+    }
+}
+
+/// Add `id` to the marked entries, or take it out if it was marked.
+fn toggle_mark(marked: &mut Vec<u64>, id: u64) {
+    match marked.iter().position(|&m| m == id) {
+        Some(pos) => {
+            marked.remove(pos);
+        }
+        None => marked.push(id),
+    }
+}
+
+/// Remove the marked entries from `history`, skipping locked ones, and
+/// clear the marks.
+fn delete_marked(history: &mut History, marked: &mut Vec<u64>) -> Removal {
+    let removed = history.remove_all(marked);
+    marked.clear();
+    removed
+}
+
+/// Notice after a delete that skipped locked entries.
+fn kept_locked_text(locked: usize) -> String {
+    let noun = if locked == 1 {
+        "entry was"
+    } else {
+        "entries were"
+    };
+    format!("🔒 {locked} locked {noun} kept — unlock to delete")
+}
+
+/// Status line while searching, e.g. "12 / 100 matches".
+fn match_count_text(matches: usize, total: usize) -> String {
+    let noun = if matches == 1 { "match" } else { "matches" };
+    format!("{matches} / {total} {noun}")
+}
+
+/// Search box hint naming how many entries there are to search and how,
+/// e.g. "Search 100 items (Fuzzy)...".
+fn search_hint(entry_count: usize, mode: SearchMode) -> String {
+    match entry_count {
+        0 => format!("Search clipboard history ({})...", mode.label()),
+        1 => format!("Search 1 item ({})...", mode.label()),
+        n => format!("Search {n} items ({})...", mode.label()),
+    }
+}
+
+/// Whether a click on an entry is a sticky paste (Alt+Click), which keeps
+/// the window open. Ctrl+Click is taken by marking.
+fn is_sticky_click(modifiers: egui::Modifiers) -> bool {
+    modifiers.alt && !modifiers.command
+}
+
+/// The HTML to put on the clipboard along with `entry`'s text: none for a
+/// plain-text paste, and none for a template, whose expansion no longer
+/// matches the stored HTML.
+fn paste_html(entry: &ClipboardEntry, format: PasteFormat) -> Option<&str> {
+    entry
+        .html
+        .as_deref()
+        .filter(|_| format == PasteFormat::AsIs && !entry.template)
+}
+
+/// Put a picked entry's text on the clipboard, clearing it again after
+/// `clear_after` if set.
+fn paste(
+    content: &str,
+    html: Option<&str>,
+    target: PasteTarget,
+    clear_after: Option<std::time::Duration>,
+) {
+    clipboard::set_text(content, html, target);
+    if let Some(delay) = clear_after {
+        clipboard::schedule_clear(content.to_string(), delay);
+    }
+}
+
+/// Render a line diff with added lines in green and removed ones in red.
+fn show_diff(ui: &mut egui::Ui, lines: &[DiffLine]) {
+    ui.small("Diff of the marked entries, older → newer (Ctrl+D or Escape to close)");
+    egui::ScrollArea::both().show(ui, |ui| {
+        for line in lines {
+            let (prefix, color) = match line.change {
+                LineChange::Same => (' ', ui.visuals().text_color()),
+                LineChange::Added => ('+', egui::Color32::from_rgb(80, 180, 80)),
+                LineChange::Removed => ('-', egui::Color32::from_rgb(220, 80, 80)),
+            };
+            ui.label(
+                egui::RichText::new(format!("{prefix} {}", line.text))
+                    .monospace()
+                    .color(color),
+            );
+        }
+    });
+}
+
+fn save_history(history: &History) {
+    if let Err(e) = storage::save(history) {
+        eprintln!("Failed to save history: {e}");
+    }
+}
+
+/// `content` cut off after `max_chars` characters, with a note of how much
+/// was left out.
+fn preview_text(content: &str, max_chars: usize) -> Cow<'_, str> {
+    match content.char_indices().nth(max_chars) {
+        None => Cow::Borrowed(content),
+        Some((end, _)) => {
+            let rest = content[end..].chars().count();
+            Cow::Owned(format!("{}\n… ({rest} more characters)", &content[..end]))
+        }
+    }
+}
+
+/// The longest start of `content` within `max_bytes`, cut at a character
+/// boundary.
+fn truncate_bytes(content: &str, max_bytes: usize) -> &str {
+    if content.len() <= max_bytes {
+        return content;
+    }
+    let mut end = max_bytes;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    &content[..end]
+}
+
+fn text_hash(text: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Where result `index` is drawn among `len` results: the same slot, or
+/// counted from the end when the list is drawn bottom-up. Its own inverse.
+fn display_index(index: usize, len: usize, reversed: bool) -> usize {
+    if reversed && index < len {
+        len - 1 - index
+    } else {
+        index
+    }
+}
+
+/// Short age of an entry for its row: seconds, minutes or hours ("45s",
+/// "2m", "3h"), "yesterday" for 24 to 48 hours, days up to a week ("4d"),
+/// and the local date after that.
+fn relative_time(
+    created_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    // A clock set back makes entries from "the future"
+    let age = (now - created_at).max(chrono::TimeDelta::zero());
+    if age < chrono::TimeDelta::minutes(1) {
+        format!("{}s", age.num_seconds())
+    } else if age < chrono::TimeDelta::hours(1) {
+        format!("{}m", age.num_minutes())
+    } else if age < chrono::TimeDelta::days(1) {
+        format!("{}h", age.num_hours())
+    } else if age < chrono::TimeDelta::days(2) {
+        "yesterday".to_string()
+    } else if age < chrono::TimeDelta::weeks(1) {
+        format!("{}d", age.num_days())
+    } else {
+        let created = created_at.with_timezone(&chrono::Local);
+        created.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Color of an entry's age dot: green while it is minutes old, yellow for
+/// hours and gray once it is a day old.
+fn age_color(
+    created_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> egui::Color32 {
+    let age = now - created_at;
+    if age < chrono::TimeDelta::hours(1) {
+        egui::Color32::from_rgb(80, 180, 80)
+    } else if age < chrono::TimeDelta::days(1) {
+        egui::Color32::from_rgb(220, 180, 50)
+    } else {
+        egui::Color32::GRAY
+    }
+}
+
+/// Top-left corner for a `window` shown next to the `tray` icon on a
+/// `monitor`: centered on the icon, above it when the icon is in the lower
+/// half of the screen (a bottom taskbar) and below it otherwise, kept on screen.
+fn near_tray_position(tray: egui::Rect, window: egui::Vec2, monitor: egui::Vec2) -> egui::Pos2 {
+    let x = tray.center().x - window.x / 2.0;
+    let y = if tray.center().y > monitor.y / 2.0 {
+        tray.min.y - window.y
+    } else {
+        tray.max.y
+    };
+    egui::pos2(
+        x.min(monitor.x - window.x).max(0.0),
+        y.min(monitor.y - window.y).max(0.0),
+    )
+}
+
+/// The size to remember after the window was shown at `size` (and last
+/// set to `last_height` tall) and is now `inner`, or None if the user
+/// didn't resize it. A resized height becomes the new upper bound.
+fn resized_size(size: WindowSize, last_height: f32, inner: egui::Vec2) -> Option<WindowSize> {
+    let width_changed = (inner.x - size.width).abs() > 0.5;
+    let height_changed = (inner.y - last_height).abs() > 0.5;
+    if !width_changed && !height_changed {
+        return None;
+    }
+    Some(WindowSize {
+        width: inner.x,
+        height: if height_changed { inner.y } else { size.height },
+    })
+}
+
+/// Top-left corner to reopen a `window` at its `saved` position. If that
+/// is off screen (its monitor was disconnected) it is clamped onto the
+/// primary `monitor`. `on_monitor` is the platform's answer where known;
+/// otherwise only the primary monitor's bounds count as on screen.
+fn restore_position(
+    saved: egui::Pos2,
+    window: egui::Vec2,
+    monitor: egui::Vec2,
+    on_monitor: Option<bool>,
+) -> egui::Pos2 {
+    let primary = egui::Rect::from_min_size(egui::Pos2::ZERO, monitor);
+    if on_monitor.unwrap_or_else(|| primary.contains(saved)) {
+        return saved;
+    }
+    egui::pos2(
+        saved.x.min(monitor.x - window.x).max(0.0),
+        saved.y.min(monitor.y - window.y).max(0.0),
+    )
+}
+
+/// Index of the newest of `contents` that isn't the `current` clipboard
+/// text, i.e. what was on the clipboard before it. 0 if there is none.
+fn previous_clipboard_index(contents: &[&str], current: Option<&str>) -> usize {
+    contents
+        .iter()
+        .position(|content| Some(*content) != current)
+        .unwrap_or(0)
+}
+
+/// Put the unfiltered list, which `fuzzy::search` returns newest first, in
+/// `order`. Navigation and selection then follow the displayed order.
+fn apply_list_order<T>(results: &mut [T], order: ListOrder, use_count: impl Fn(&T) -> u32) {
+    match order {
+        ListOrder::NewestFirst => {}
+        ListOrder::OldestFirst => results.reverse(),
+        // Stable, so ties stay newest first
+        ListOrder::MostUsed => results.sort_by_key(|result| std::cmp::Reverse(use_count(result))),
+    }
+}
+
+/// Index of `selected_id` among the result `ids`, or `index` unchanged if
+/// nothing was selected or the entry is gone.
+fn follow_selection(ids: &[u64], selected_id: Option<u64>, index: usize) -> usize {
+    selected_id
+        .and_then(|selected| ids.iter().position(|&id| id == selected))
+        .unwrap_or(index)
+}
+
+/// Selection index after moving up one row in a grid of `len` results laid
+/// out `columns` wide (1 for the plain list). Stops at the top unless `wrap`
+/// is set, in which case it continues from the bottom of the same column.
+fn step_up(index: usize, len: usize, columns: usize, wrap: bool) -> usize {
+    if index >= columns {
+        index - columns
+    } else if wrap && len > 0 {
+        let bottom = (len - 1) / columns * columns + index;
+        if bottom < len {
+            bottom
+        } else {
+            bottom - columns
+        }
+    } else {
+        index
+    }
+}
+
+/// Selection index after moving down one row; the mirror of [`step_up`].
+fn step_down(index: usize, len: usize, columns: usize, wrap: bool) -> usize {
+    if index + columns < len {
+        index + columns
+    } else if wrap {
+        index % columns
+    } else {
+        index
+    }
+}
+
+/// Runs of two or more adjacent items whose content is at least `threshold`
+/// similar to the first item of the run, in order.
+fn similar_runs(contents: &[&str], threshold: f32) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < contents.len() {
+        let end = (start + 1..contents.len())
+            .find(|&i| diff::similarity(contents[start], contents[i]) < threshold)
+            .unwrap_or(contents.len());
+        if end - start > 1 {
+            runs.push(start..end);
+        }
+        start = end;
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_step_clamps_without_wrap() {
+        assert_eq!(step_up(0, 3, 1, false), 0);
+        assert_eq!(step_up(2, 3, 1, false), 1);
+        assert_eq!(step_down(2, 3, 1, false), 2);
+        assert_eq!(step_down(0, 3, 1, false), 1);
+    }
+
+    #[test]
+    fn test_step_wraps_at_ends() {
+        assert_eq!(step_up(0, 3, 1, true), 2);
+        assert_eq!(step_down(2, 3, 1, true), 0);
+    }
+
+    #[test]
+    fn test_step_on_empty_list() {
+        assert_eq!(step_up(0, 0, 1, true), 0);
+        assert_eq!(step_down(0, 0, 1, true), 0);
+    }
+
+    #[test]
+    fn test_step_in_grid() {
+        // 3 columns, 7 results:
+        // 0 1 2
+        // 3 4 5
+        // 6
+        assert_eq!(step_down(1, 7, 3, false), 4);
+        assert_eq!(step_down(4, 7, 3, false), 4);
+        assert_eq!(step_up(4, 7, 3, false), 1);
+        assert_eq!(step_down(4, 7, 3, true), 1);
+        assert_eq!(step_up(0, 7, 3, true), 6);
+        assert_eq!(step_up(2, 7, 3, true), 5);
+    }
+
+    #[test]
+    fn test_preview_text_cuts_long_content() {
+        assert_eq!(preview_text("short", 10), "short");
+        assert_eq!(preview_text("exactly10!", 10), "exactly10!");
+        assert_eq!(preview_text("ééééé", 3), "ééé\n… (2 more characters)");
+        assert!(matches!(preview_text("a\nb", 10), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_truncate_bytes_at_char_boundary() {
+        assert_eq!(truncate_bytes("hello", 10), "hello");
+        assert_eq!(truncate_bytes("hello", 3), "hel");
+        // "é" is two bytes; a cut inside it backs off
+        assert_eq!(truncate_bytes("aé", 2), "a");
+        assert_eq!(truncate_bytes("aé", 3), "aé");
+    }
+
+    #[test]
+    fn test_relative_time_boundaries() {
+        let now = chrono::Utc::now();
+        let ago = |seconds| relative_time(now - chrono::TimeDelta::seconds(seconds), now);
+        assert_eq!(ago(0), "0s");
+        assert_eq!(ago(59), "59s");
+        assert_eq!(ago(60), "1m");
+        assert_eq!(ago(59 * 60 + 59), "59m");
+        assert_eq!(ago(60 * 60), "1h");
+        assert_eq!(ago(24 * 60 * 60 - 1), "23h");
+        assert_eq!(ago(24 * 60 * 60), "yesterday");
+        assert_eq!(ago(2 * 24 * 60 * 60 - 1), "yesterday");
+        assert_eq!(ago(2 * 24 * 60 * 60), "2d");
+        assert_eq!(ago(7 * 24 * 60 * 60 - 1), "6d");
+        // From a week on, the date
+        let old = now - chrono::TimeDelta::weeks(1);
+        let date = old
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d")
+            .to_string();
+        assert_eq!(relative_time(old, now), date);
+        // Clock skew
+        assert_eq!(ago(-30), "0s");
+    }
+
+    #[test]
+    fn test_age_color() {
+        let now = chrono::Utc::now();
+        let ago = |minutes| now - chrono::TimeDelta::minutes(minutes);
+        let green = age_color(ago(0), now);
+        let yellow = age_color(ago(60), now);
+        let gray = age_color(ago(24 * 60), now);
+        assert_eq!(age_color(ago(59), now), green);
+        assert_eq!(age_color(ago(23 * 60 + 59), now), yellow);
+        assert_eq!(age_color(ago(30 * 24 * 60), now), gray);
+        assert!(green != yellow && yellow != gray && green != gray);
+    }
+
+    #[test]
+    fn test_near_tray_position() {
+        let monitor = egui::vec2(1920.0, 1080.0);
+        let window = egui::vec2(400.0, 500.0);
+        let tray = |x, y| egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(20.0, 20.0));
+        // Bottom taskbar: above the icon
+        assert_eq!(
+            near_tray_position(tray(1000.0, 1050.0), window, monitor),
+            egui::pos2(810.0, 550.0)
+        );
+        // Top menu bar: below the icon
+        assert_eq!(
+            near_tray_position(tray(1000.0, 0.0), window, monitor),
+            egui::pos2(810.0, 20.0)
+        );
+        // Icon near the right edge: kept on screen
+        assert_eq!(
+            near_tray_position(tray(1890.0, 1050.0), window, monitor),
+            egui::pos2(1520.0, 550.0)
+        );
+    }
+
+    #[test]
+    fn test_resized_size() {
+        let size = WindowSize {
+            width: 400.0,
+            height: 500.0,
+        };
+        // Shrunk to fit a few results: not a resize
+        assert_eq!(resized_size(size, 180.0, egui::vec2(400.0, 180.0)), None);
+        assert_eq!(
+            resized_size(size, 180.0, egui::vec2(520.0, 180.0)),
+            Some(WindowSize {
+                width: 520.0,
+                height: 500.0
+            })
+        );
+        assert_eq!(
+            resized_size(size, 180.0, egui::vec2(400.0, 640.0)),
+            Some(WindowSize {
+                width: 400.0,
+                height: 640.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_restore_position() {
+        let monitor = egui::vec2(1920.0, 1080.0);
+        let window = egui::vec2(400.0, 500.0);
+        let saved = egui::pos2(300.0, 200.0);
+        assert_eq!(restore_position(saved, window, monitor, None), saved);
+        // A monitor left of the primary one is still there
+        let left = egui::pos2(-1500.0, 200.0);
+        assert_eq!(restore_position(left, window, monitor, Some(true)), left);
+        // Disconnected monitors: back onto the primary one
+        assert_eq!(
+            restore_position(left, window, monitor, Some(false)),
+            egui::pos2(0.0, 200.0)
+        );
+        assert_eq!(
+            restore_position(egui::pos2(3000.0, 1200.0), window, monitor, None),
+            egui::pos2(1520.0, 580.0)
+        );
+    }
+
+    #[test]
+    fn test_previous_clipboard_index() {
+        let contents = ["now", "before", "older"];
+        assert_eq!(previous_clipboard_index(&contents, Some("now")), 1);
+        // The clipboard holds something that was never captured (or no text)
+        assert_eq!(previous_clipboard_index(&contents, Some("image")), 0);
+        assert_eq!(previous_clipboard_index(&contents, None), 0);
+        assert_eq!(previous_clipboard_index(&["now"], Some("now")), 0);
+        assert_eq!(previous_clipboard_index(&[], Some("now")), 0);
+    }
+
+    #[test]
+    fn test_navigation_follows_list_order() {
+        // Entry ids as the history lists them, newest first
+        let newest_first = [3, 2, 1];
+        for (order, top, second, last) in [
+            (ListOrder::NewestFirst, 3, 2, 1),
+            (ListOrder::OldestFirst, 1, 2, 3),
+            (ListOrder::MostUsed, 3, 2, 1),
+        ] {
+            let mut ids = newest_first;
+            apply_list_order(&mut ids, order, |_| 0);
+            assert_eq!(ids[0], top, "{order:?}");
+            assert_eq!(ids[step_down(0, ids.len(), 1, false)], second, "{order:?}");
+            assert_eq!(ids[ids.len() - 1], last, "{order:?}");
+        }
+    }
+
+    #[test]
+    fn test_most_used_order() {
+        // (id, use_count), newest first
+        let mut results = [(6, 0), (5, 2), (4, 7), (3, 2), (2, 0), (1, 7)];
+        apply_list_order(&mut results, ListOrder::MostUsed, |&(_, uses)| uses);
+        let ids: Vec<u64> = results.iter().map(|&(id, _)| id).collect();
+        // Equally used entries keep their recency order
+        assert_eq!(ids, [4, 1, 5, 3, 6, 2]);
+    }
+
+    #[test]
+    fn test_follow_selection_across_new_captures() {
+        // Entry 7 was selected at index 1, then two new entries arrived
+        assert_eq!(follow_selection(&[9, 8, 10, 7, 6], Some(7), 1), 3);
+        // A duplicate moved to the front: the selection moves up with it
+        assert_eq!(follow_selection(&[7, 10, 6], Some(7), 1), 0);
+        // The selected entry was trimmed or deleted
+        assert_eq!(follow_selection(&[9, 8, 10], Some(7), 1), 1);
+        assert_eq!(follow_selection(&[9, 8, 10], None, 2), 2);
+    }
+
+    #[test]
+    fn test_similar_runs_groups_adjacent_near_duplicates() {
+        let contents = [
+            "cargo test --workspace",
+            "cargo test --workspace -q",
+            "cargo test --workspac",
+            "git status",
+            "cargo test --workspace",
+        ];
+        // The last entry is similar too, but not adjacent to the run
+        assert_eq!(similar_runs(&contents, 0.8), vec![0..3]);
+    }
+
+    #[test]
+    fn test_similar_runs_threshold() {
+        let contents = ["abcdef", "abcxyz", "abcxyq"];
+        assert_eq!(similar_runs(&contents, 0.5), vec![0..3]);
+        assert_eq!(similar_runs(&contents, 0.9), Vec::<Range<usize>>::new());
+        assert_eq!(similar_runs(&[], 0.5), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn test_display_index_bottom_up() {
+        assert_eq!(display_index(0, 5, false), 0);
+        assert_eq!(display_index(0, 5, true), 4);
+        assert_eq!(display_index(4, 5, true), 0);
+        assert_eq!(display_index(0, 0, true), 0);
+        // Up on screen from the best result (drawn last) reaches the next one
+        let shown = display_index(0, 5, true);
+        assert_eq!(display_index(step_up(shown, 5, 1, false), 5, true), 1);
+    }
+
+    #[test]
+    fn test_accessible_label_has_flags_and_content() {
+        let mut history = History::new(10);
+        history.push("line one\nline two".into());
+        let id = history.entries()[0].id;
+        history.toggle_lock(id);
+        let label = accessible_label(&history.entries()[0], true);
+        assert!(label.starts_with("Copied "));
+        assert!(label.ends_with(", marked, locked: line one\nline two"));
+
+        history.push("x".repeat(ACCESSIBLE_MAX_CHARS + 50));
+        let label = accessible_label(&history.entries()[0], false);
+        assert!(label.ends_with(&format!("{}…", "x".repeat(ACCESSIBLE_MAX_CHARS))));
+    }
+
+    #[test]
+    fn test_tooltip_shows_current_binding() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            with_shortcut("Match case", &keymap, Action::ToggleCaseSensitive),
+            "Match case (Alt+C)"
+        );
+        let overrides = BTreeMap::from([(Action::ToggleCaseSensitive, "Ctrl+I".to_string())]);
+        let keymap = Keymap::from_overrides(&overrides).unwrap();
+        assert_eq!(
+            with_shortcut("Match case", &keymap, Action::ToggleCaseSensitive),
+            "Match case (Ctrl+I)"
+        );
+    }
+
+    #[test]
+    fn test_ui_state_from_older_save() {
+        // Saved before the toggles were remembered: they fall back to the config
+        let state: UiState = serde_json::from_str(r#"{"last_height": 300.0}"#).unwrap();
+        assert_eq!(state.last_height, 300.0);
+        assert_eq!(state.list_order, None);
+        assert_eq!(state.preview_open, None);
+        assert_eq!(state.search_mode, None);
+    }
+
+    #[test]
+    fn test_toggle_and_delete_marked() {
+        let mut history = History::new(10);
+        for text in ["one", "two", "three", "four"] {
+            history.push(text.to_string());
+        }
+        // Newest first: four, three, two, one
+        let ids: Vec<u64> = history.entries().iter().map(|e| e.id).collect();
+        let (three, two, one) = (ids[1], ids[2], ids[3]);
+        history.toggle_lock(three);
+
+        let mut marked = Vec::new();
+        for id in [one, two, three, one] {
+            toggle_mark(&mut marked, id);
+        }
+        assert_eq!(marked, vec![two, three]);
+        toggle_mark(&mut marked, one);
+        assert_eq!(marked, vec![two, three, one]);
+
+        // The locked entry is kept, and nothing stays marked
+        assert_eq!(
+            delete_marked(&mut history, &mut marked),
+            Removal {
+                removed: 2,
+                locked: 1
+            }
+        );
+        assert!(marked.is_empty());
+        let left: Vec<u64> = history.entries().iter().map(|e| e.id).collect();
+        assert_eq!(left, vec![ids[0], three]);
+        // Deleted together, undone one at a time
+        assert!(history.undo_remove());
+        assert_eq!(history.entries().len(), 3);
+    }
+
+    #[test]
+    fn test_match_count_text() {
+        assert_eq!(match_count_text(12, 100), "12 / 100 matches");
+        assert_eq!(match_count_text(1, 100), "1 / 100 match");
+        assert_eq!(match_count_text(0, 0), "0 / 0 matches");
+    }
+
+    #[test]
+    fn test_kept_locked_text() {
+        assert_eq!(
+            kept_locked_text(1),
+            "🔒 1 locked entry was kept — unlock to delete"
+        );
+        assert_eq!(
+            kept_locked_text(3),
+            "🔒 3 locked entries were kept — unlock to delete"
+        );
+    }
+
+    #[test]
+    fn test_search_hint() {
+        assert_eq!(
+            search_hint(100, SearchMode::Fuzzy),
+            "Search 100 items (Fuzzy)..."
+        );
+        assert_eq!(
+            search_hint(1, SearchMode::Substring),
+            "Search 1 item (Exact)..."
+        );
+        assert_eq!(
+            search_hint(0, SearchMode::Regex),
+            "Search clipboard history (Regex)..."
+        );
+        for mode in [SearchMode::Fuzzy, SearchMode::Substring, SearchMode::Regex] {
+            for count in [0, 1, 2, 20_000] {
+                let hint = search_hint(count, mode);
+                assert!(hint.contains(mode.label()), "{hint}");
+                assert_eq!(hint.contains(&count.to_string()), count > 0, "{hint}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sticky_click_modifiers() {
+        assert!(is_sticky_click(egui::Modifiers::ALT));
+        assert!(is_sticky_click(
+            egui::Modifiers::ALT | egui::Modifiers::SHIFT
+        ));
+        assert!(!is_sticky_click(egui::Modifiers::NONE));
+        assert!(!is_sticky_click(egui::Modifiers::COMMAND));
+        assert!(!is_sticky_click(
+            egui::Modifiers::ALT | egui::Modifiers::COMMAND
+        ));
+    }
+
+    #[test]
+    fn test_paste_html_by_format() {
+        let mut history = History::new(10);
+        history.push_with_html("bold".into(), Some("<b>bold</b>".into()));
+        history.push("plain".into());
+        let rich = history.entries()[1].clone();
+        let plain = &history.entries()[0];
+        assert_eq!(paste_html(&rich, PasteFormat::AsIs), Some("<b>bold</b>"));
+        assert_eq!(paste_html(&rich, PasteFormat::PlainText), None);
+        let upper = PasteFormat::Transformed(Transform::Uppercase);
+        assert_eq!(paste_html(&rich, upper), None);
+        assert_eq!(paste_html(plain, PasteFormat::AsIs), None);
+
+        let mut template = rich;
+        template.template = true;
+        assert_eq!(paste_html(&template, PasteFormat::AsIs), None);
+    }
+}
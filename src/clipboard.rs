@@ -1,51 +1,1206 @@
-use std::sync::{Arc, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use arboard::Clipboard;
 
-use crate::history::History;
+use crate::config::{Config, PasteTarget};
+use crate::history::{fnv1a, History};
+use crate::hooks;
+use crate::image::ImageContent;
+use crate::platform;
 use crate::storage;
+use crate::text::looks_like_secret;
+
+/// Shortest settle time with `Config::ignore_drag_clipboard`, long enough
+/// for a drag's temporary clipboard value to be restored.
+const DRAG_SETTLE: Duration = Duration::from_secs(1);
+
+/// Longest wait between checks while change notifications are on, in case
+/// one is missed.
+const NOTIFIED_RECHECK: Duration = Duration::from_secs(5);
+
+/// How often the monitor deletes entries past `Config::expire_after_days`
+/// and purges the trash.
+const EXPIRY_CHECK: Duration = Duration::from_secs(60);
+
+/// Longest wait on quitting for the monitor's final save.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Tries of a clipboard call while another process holds the clipboard
+/// (`ClipboardOccupied`, common on Windows), and the wait after the first;
+/// it doubles after each further try.
+const BUSY_ATTEMPTS: u32 = 5;
+const BUSY_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Longest wait between the monitor's attempts to open a clipboard that
+/// keeps failing.
+const MAX_REOPEN_DELAY: Duration = Duration::from_secs(30);
+
+/// Coalesces the saves of captures from the monitor and the capture shortcut.
+static SAVE_THROTTLE: Mutex<SaveThrottle> = Mutex::new(SaveThrottle {
+    last_save: None,
+    pending: false,
+});
+
+/// Text the app itself last put on the clipboard, skipped once when the
+/// monitor sees it so a paste doesn't come back as a capture.
+static OWN_WRITE: Mutex<Option<String>> = Mutex::new(None);
+
+/// The clipboard as it was when [`toggle_pause`] resumed capture, for the
+/// monitor to take as its baseline on its next check.
+static RESUME_BASELINE: Mutex<Option<Baseline>> = Mutex::new(None);
+
+/// Bumped by every `schedule_clear`, so only the most recent timer may fire.
+static CLEAR_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Config values needed on the monitor and hotkey threads.
+#[derive(Debug, Default)]
+struct Settings {
+    preserve_formats: bool,
+    keep_format_only_updates: bool,
+    ignore_own_copies: bool,
+    ignore_drag_clipboard: bool,
+    capture_images: bool,
+    ignore_sensitive: bool,
+    save_debounce: Duration,
+    on_capture_cmd: Option<String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Capture the clipboard-related parts of `config`. Call once from `main`.
+pub fn configure(config: &Config) {
+    let _ = SETTINGS.set(Settings {
+        preserve_formats: config.preserve_formats,
+        keep_format_only_updates: config.keep_format_only_updates,
+        ignore_own_copies: config.ignore_own_copies,
+        ignore_drag_clipboard: config.ignore_drag_clipboard,
+        capture_images: config.capture_images,
+        ignore_sensitive: config.ignore_sensitive,
+        save_debounce: Duration::from_millis(config.save_debounce_ms),
+        on_capture_cmd: config.on_capture_cmd.clone(),
+    });
+}
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
 
 /// Start clipboard monitoring in a background thread.
-/// Polls the clipboard at the given interval and pushes new text to history.
+/// Checks the clipboard when the OS reports a change (Windows, see
+/// `platform::watch_clipboard`) or else every `poll_interval`, and pushes new text to history,
+/// and new images with `Config::capture_images` while there is no text.
 /// Calls `request_repaint` on the egui context when history changes.
+///
+/// With a non-zero `debounce`, a new value is only recorded once it has
+/// stayed on the clipboard that long, so rapid re-copies collapse into one entry.
+/// `Config::ignore_drag_clipboard` raises it to at least `DRAG_SETTLE`.
+///
+/// Captures are saved at most once per `Config::save_debounce_ms`; each poll
+/// saves any that were held back once that has passed.
+///
+/// Expired entries (`Config::expire_after_days`) and trashed entries past
+/// `Config::trash_retention_days` are pruned every `EXPIRY_CHECK`.
+///
+/// While `paused` is set the clipboard isn't read at all. On resuming,
+/// whatever was on it at that moment (read by [`toggle_pause`], or else on
+/// the next check) becomes the baseline instead of being recorded, so
+/// nothing copied during the pause is captured.
+///
+/// A panic while handling one poll is caught and logged so capture keeps
+/// going. `running` is set to false while the clipboard can't be opened and
+/// if the thread ever stops, so the UI can show that capture is off.
+/// Once `quit` is set the thread saves any held back captures and stops
+/// (see [`shut_down`]).
 pub fn start_monitor(
     history: Arc<Mutex<History>>,
     poll_interval: Duration,
+    debounce: Duration,
     ctx: eframe::egui::Context,
+    running: Arc<Mutex<bool>>,
+    paused: Arc<Mutex<bool>>,
+    quit: Arc<Mutex<bool>>,
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        let mut clipboard = match Clipboard::new() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Failed to initialize clipboard: {e}");
-                return;
-            }
+        // Back on after a restart by the watchdog
+        *lock_recovering(&running) = true;
+        let _guard = RunningGuard {
+            running: Arc::clone(&running),
+            ctx: ctx.clone(),
+        };
+
+        let Some(mut clipboard) = open_until_quit(&running, &quit, &ctx) else {
+            return;
         };
 
         let mut last_text = clipboard.get_text().unwrap_or_default();
+        let mut last_html = read_html(&mut clipboard);
+        let mut last_image = read_image_hash(&mut clipboard);
+        let mut last_sequence = platform::clipboard_sequence();
+        let mut debouncer = Debouncer::default();
+        let debounce = settle_delay(debounce, settings().ignore_drag_clipboard);
+        let mut changes = platform::watch_clipboard();
+        let mut last_expiry_check = Instant::now();
+        let mut pause = PauseGate::default();
 
         loop {
-            thread::sleep(poll_interval);
+            let busy = debouncer.pending.is_some() || lock_recovering(&SAVE_THROTTLE).pending;
+            let timeout = next_check(changes.is_some(), busy, poll_interval);
+            wait_for_change(&mut changes, timeout);
+            if *lock_recovering(&quit) {
+                flush_pending_save(&history);
+                return;
+            }
 
-            let current_text = match clipboard.get_text() {
-                Ok(t) => t,
-                Err(_) => continue,
-            };
+            let mut step = pause.step(*lock_recovering(&paused));
+            if step != PauseStep::Skip {
+                if let Some(baseline) = lock_recovering(&RESUME_BASELINE).take() {
+                    // Read when capture resumed, maybe well before this
+                    // check, so a copy made since then is still new
+                    last_text = baseline.text;
+                    last_html = baseline.html;
+                    last_image = baseline.image;
+                    debouncer.reset();
+                    step = PauseStep::Poll;
+                }
+            }
+            // Reading and hashing an image is slow, so it waits for a change
+            // where that can be told (Windows)
+            let sequence = platform::clipboard_sequence();
+            let changed = sequence.is_none() || sequence != last_sequence;
+            last_sequence = sequence;
+            let polled = panic::catch_unwind(AssertUnwindSafe(|| {
+                let text = gated_text(
+                    &mut clipboard,
+                    step,
+                    &mut last_text,
+                    &mut debouncer,
+                    debounce,
+                );
+                match step {
+                    PauseStep::Poll => {}
+                    PauseStep::Skip => return,
+                    PauseStep::Resume => {
+                        last_html = read_html(&mut clipboard);
+                        last_image = read_image_hash(&mut clipboard);
+                        return;
+                    }
+                }
+                if let Some(text) = text {
+                    last_text = text.clone();
+                    last_html = read_html(&mut clipboard);
+                    last_image = None;
+                    if !is_own_write(&OWN_WRITE, &text) {
+                        record(text, last_html.clone(), &history, &ctx);
+                    }
+                } else if settings().capture_images && changed && clipboard.get_text().is_err() {
+                    if let Some(image) = read_new_image(&mut clipboard, last_image) {
+                        last_image = Some(image.hash());
+                        // Copying the last text again after the image is a new copy
+                        last_text.clear();
+                        record_image(image, &history, &ctx);
+                    }
+                } else if settings().keep_format_only_updates {
+                    let html = read_html(&mut clipboard);
+                    if is_format_only_update(last_html.as_deref(), html.as_deref())
+                        && clipboard.get_text().is_ok_and(|text| text == last_text)
+                    {
+                        last_html = html.clone();
+                        record(last_text.clone(), html, &history, &ctx);
+                    }
+                }
+            }));
+            if polled.is_err() {
+                eprintln!("Clipboard monitor recovered from a panic; capture continues");
+            }
 
-            if current_text != last_text && !current_text.is_empty() {
-                last_text = current_text.clone();
+            let due =
+                lock_recovering(&SAVE_THROTTLE).take_due(Instant::now(), settings().save_debounce);
+            if due {
+                save(&lock_recovering(&history));
+            }
 
-                let mut hist = history.lock().unwrap();
-                if hist.push(current_text) {
-                    // Save on every change
-                    if let Err(e) = storage::save(&hist) {
-                        eprintln!("Failed to save history: {e}");
-                    }
+            if last_expiry_check.elapsed() >= EXPIRY_CHECK {
+                last_expiry_check = Instant::now();
+                let mut history = lock_recovering(&history);
+                let removed = history.prune_expired(chrono::Utc::now()) + history.purge_trash();
+                if removed > 0 {
+                    save(&history);
                     ctx.request_repaint();
                 }
             }
         }
     })
 }
+
+/// Open the clipboard for the monitor, trying again with growing pauses
+/// while it fails. `running` is false meanwhile, so the UI warns that
+/// capture is off. `None` if `quit` is set first.
+fn open_until_quit(
+    running: &Mutex<bool>,
+    quit: &Mutex<bool>,
+    ctx: &eframe::egui::Context,
+) -> Option<Clipboard> {
+    let mut failures = 0;
+    loop {
+        match retry_busy(Clipboard::new, thread::sleep) {
+            Ok(clipboard) => {
+                if failures > 0 {
+                    eprintln!("Clipboard available again; capture resumes");
+                    *lock_recovering(running) = true;
+                    ctx.request_repaint();
+                }
+                return Some(clipboard);
+            }
+            Err(e) => {
+                if failures == 0 {
+                    eprintln!("Failed to initialize clipboard: {e}; retrying");
+                    *lock_recovering(running) = false;
+                    ctx.request_repaint();
+                }
+                failures += 1;
+                thread::sleep(reopen_delay(failures));
+                if *lock_recovering(quit) {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Pause before the monitor's next attempt to open the clipboard after
+/// `failures` failed ones: doubling from a second up to [`MAX_REOPEN_DELAY`].
+fn reopen_delay(failures: u32) -> Duration {
+    Duration::from_secs(1)
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(MAX_REOPEN_DELAY)
+}
+
+/// Run `op` until it succeeds or fails for a reason other than the clipboard
+/// being held by another process, at most [`BUSY_ATTEMPTS`] times, calling
+/// `sleep` with a doubling backoff in between.
+fn retry_busy<T>(
+    mut op: impl FnMut() -> Result<T, arboard::Error>,
+    mut sleep: impl FnMut(Duration),
+) -> Result<T, arboard::Error> {
+    let mut delay = BUSY_BACKOFF;
+    for _ in 1..BUSY_ATTEMPTS {
+        match op() {
+            Err(arboard::Error::ClipboardOccupied) => {
+                sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+/// Open the clipboard, waiting briefly while another process holds it.
+fn open() -> Result<Clipboard, arboard::Error> {
+    retry_busy(Clipboard::new, thread::sleep)
+}
+
+/// The clipboard's current text, or `None` if it has none or can't be read.
+pub fn current_text() -> Option<String> {
+    open()
+        .and_then(|mut clipboard| retry_busy(|| clipboard.get_text(), thread::sleep))
+        .ok()
+}
+
+/// How long the monitor waits before its next check. With change
+/// notifications it only needs to wake on its own while a new value is
+/// settling or a save is held back.
+fn next_check(notified: bool, busy: bool, poll_interval: Duration) -> Duration {
+    if notified && !busy {
+        NOTIFIED_RECHECK.max(poll_interval)
+    } else {
+        poll_interval
+    }
+}
+
+/// Wait up to `timeout` for a clipboard change notification, or just sleep
+/// without `changes`. Falls back to polling if the notifications stop.
+fn wait_for_change(changes: &mut Option<Receiver<()>>, timeout: Duration) {
+    let Some(receiver) = changes else {
+        thread::sleep(timeout);
+        return;
+    };
+    match receiver.recv_timeout(timeout) {
+        // One check covers every change queued meanwhile
+        Ok(()) => while receiver.try_recv().is_ok() {},
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => {
+            eprintln!("Clipboard notifications stopped; polling instead");
+            *changes = None;
+        }
+    }
+}
+
+/// The clipboard text as the monitor reads it; a trait so tests can script
+/// what is on the clipboard.
+trait TextSource {
+    fn text(&mut self) -> Option<String>;
+}
+
+impl TextSource for Clipboard {
+    fn text(&mut self) -> Option<String> {
+        retry_busy(|| self.get_text(), thread::sleep).ok()
+    }
+}
+
+/// What the monitor does on a check, depending on the paused flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseStep {
+    /// Capturing: check the clipboard as usual.
+    Poll,
+    /// Paused: don't touch the clipboard.
+    Skip,
+    /// First check after a pause: take the clipboard as the new baseline.
+    Resume,
+}
+
+/// Tracks the paused flag across checks to spot the resume.
+#[derive(Debug, Default)]
+struct PauseGate {
+    paused: bool,
+}
+
+impl PauseGate {
+    fn step(&mut self, paused: bool) -> PauseStep {
+        let was_paused = std::mem::replace(&mut self.paused, paused);
+        match (was_paused, paused) {
+            (_, true) => PauseStep::Skip,
+            (true, false) => PauseStep::Resume,
+            (false, false) => PauseStep::Poll,
+        }
+    }
+}
+
+/// The text side of a check: a new settled value to record, if any. Doesn't
+/// read `source` while paused, and on resume makes its current text the
+/// baseline (`last_text`) without recording it.
+fn gated_text(
+    source: &mut impl TextSource,
+    step: PauseStep,
+    last_text: &mut String,
+    debouncer: &mut Debouncer,
+    debounce: Duration,
+) -> Option<String> {
+    match step {
+        PauseStep::Poll => read_settled(source, last_text, debouncer, debounce),
+        PauseStep::Skip => None,
+        PauseStep::Resume => {
+            *last_text = source.text().unwrap_or_default();
+            debouncer.reset();
+            None
+        }
+    }
+}
+
+/// Read the clipboard and return its text if it is a new value that has
+/// settled (see [`Debouncer`]).
+fn read_settled(
+    source: &mut impl TextSource,
+    last_text: &str,
+    debouncer: &mut Debouncer,
+    debounce: Duration,
+) -> Option<String> {
+    let current_text = source.text()?;
+    settle_observed(current_text, last_text, debouncer, Instant::now(), debounce)
+}
+
+/// Feed one observed clipboard value to `debouncer`. A value that reverts
+/// to `last_text` (the last recorded one) before settling is dropped.
+fn settle_observed(
+    current_text: String,
+    last_text: &str,
+    debouncer: &mut Debouncer,
+    now: Instant,
+    debounce: Duration,
+) -> Option<String> {
+    if current_text == last_text || current_text.is_empty() {
+        // Changed back before it settled
+        debouncer.reset();
+        return None;
+    }
+    debouncer.settle(current_text, now, debounce)
+}
+
+/// How long a new value must stay on the clipboard before it is recorded.
+fn settle_delay(debounce: Duration, ignore_drag_clipboard: bool) -> Duration {
+    if ignore_drag_clipboard {
+        debounce.max(DRAG_SETTLE)
+    } else {
+        debounce
+    }
+}
+
+/// What the monitor compares the clipboard with after a pause.
+#[derive(Debug)]
+struct Baseline {
+    text: String,
+    html: Option<String>,
+    image: Option<u64>,
+}
+
+/// Pause or resume capture (flip `paused`). Resuming reads the clipboard
+/// as the monitor's baseline right away: the monitor may only check again
+/// once the next copy wakes it, which would otherwise become the baseline
+/// and be lost.
+pub fn toggle_pause(paused: &Mutex<bool>) {
+    let mut paused = lock_recovering(paused);
+    let baseline = if *paused {
+        match open() {
+            Ok(mut clipboard) => Some(Baseline {
+                text: clipboard.get_text().unwrap_or_default(),
+                html: read_html(&mut clipboard),
+                image: read_image_hash(&mut clipboard),
+            }),
+            Err(e) => {
+                eprintln!("Failed to read clipboard on resume: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    *lock_recovering(&RESUME_BASELINE) = baseline;
+    *paused = !*paused;
+}
+
+/// Push whatever text is on the clipboard right now, even if the monitor
+/// already saw it (e.g. the copy came and went between two polls, or an entry
+/// further down should move back to the top).
+pub fn capture_now(history: &Mutex<History>, ctx: &eframe::egui::Context) {
+    let read = open().and_then(|mut clipboard| {
+        let text = retry_busy(|| clipboard.get_text(), thread::sleep)?;
+        Ok((text, read_html(&mut clipboard)))
+    });
+    match read {
+        Ok((text, html)) if !text.is_empty() => record(text, html, history, ctx),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to read clipboard for capture: {e}"),
+    }
+}
+
+/// The HTML on the clipboard, if `Config::preserve_formats` is on and the
+/// copy has any.
+fn read_html(clipboard: &mut Clipboard) -> Option<String> {
+    if !settings().preserve_formats {
+        return None;
+    }
+    clipboard.get().html().ok()
+}
+
+/// Hash of the image on the clipboard, if `Config::capture_images` is on.
+fn read_image_hash(clipboard: &mut Clipboard) -> Option<u64> {
+    if !settings().capture_images {
+        return None;
+    }
+    clipboard.get_image().ok().map(|image| fnv1a(&image.bytes))
+}
+
+/// The image on the clipboard, unless it hashes to `last` (the last image
+/// recorded). Hashing notices a new image between polls without keeping a
+/// copy of the last one.
+fn read_new_image(clipboard: &mut Clipboard, last: Option<u64>) -> Option<ImageContent> {
+    let image = clipboard.get_image().ok()?;
+    let image = ImageContent::new(image.width, image.height, image.bytes.into_owned());
+    (Some(image.hash()) != last).then_some(image)
+}
+
+/// Whether the clipboard now carrying `html`, with the same text as the last
+/// capture that had `last_html`, is an update to that capture's formatting.
+fn is_format_only_update(last_html: Option<&str>, html: Option<&str>) -> bool {
+    html.is_some() && html != last_html
+}
+
+/// Whether `text`, just seen on the clipboard, is what the app itself wrote
+/// there last. Empties `own_write` either way: the next change is someone
+/// else's, even if it happens to be the same text.
+fn is_own_write(own_write: &Mutex<Option<String>>, text: &str) -> bool {
+    lock_recovering(own_write)
+        .take()
+        .is_some_and(|own| own == text)
+}
+
+/// Whether a copy came from our own window and `Config::ignore_own_copies`
+/// says to skip it. `own_window_is_foreground` is only asked when the
+/// setting is on.
+fn is_ignored_own_copy(
+    ignore_own_copies: bool,
+    own_window_is_foreground: impl FnOnce() -> bool,
+) -> bool {
+    ignore_own_copies && own_window_is_foreground()
+}
+
+/// Whether to drop a capture before it reaches the history: the copying app
+/// asked for it to be kept out of clipboard history (always respected), or
+/// it is our own copy (see [`is_ignored_own_copy`]).
+fn is_skipped_capture(
+    excluded_from_history: impl FnOnce() -> bool,
+    ignore_own_copies: bool,
+    own_window_is_foreground: impl FnOnce() -> bool,
+) -> bool {
+    excluded_from_history() || is_ignored_own_copy(ignore_own_copies, own_window_is_foreground)
+}
+
+fn record(
+    text: String,
+    html: Option<String>,
+    history: &Mutex<History>,
+    ctx: &eframe::egui::Context,
+) {
+    if is_skipped_capture(
+        platform::clipboard_excluded_from_history,
+        settings().ignore_own_copies,
+        platform::own_window_is_foreground,
+    ) {
+        return;
+    }
+    if settings().ignore_sensitive && looks_like_secret(&text) {
+        return;
+    }
+    let hook_input = settings().on_capture_cmd.as_ref().map(|_| text.clone());
+    let mut hist = lock_recovering(history);
+    if hist.push_with_html(text, html) {
+        if let (Some(cmd), Some(text)) = (&settings().on_capture_cmd, hook_input) {
+            hooks::spawn(cmd.clone(), text);
+        }
+        let save_now =
+            lock_recovering(&SAVE_THROTTLE).changed(Instant::now(), settings().save_debounce);
+        if save_now {
+            save(&hist);
+        }
+        ctx.request_repaint();
+    }
+}
+
+fn record_image(image: ImageContent, history: &Mutex<History>, ctx: &eframe::egui::Context) {
+    if is_skipped_capture(
+        platform::clipboard_excluded_from_history,
+        settings().ignore_own_copies,
+        platform::own_window_is_foreground,
+    ) {
+        return;
+    }
+    let mut hist = lock_recovering(history);
+    if hist.push_image(image) {
+        let save_now =
+            lock_recovering(&SAVE_THROTTLE).changed(Instant::now(), settings().save_debounce);
+        if save_now {
+            save(&hist);
+        }
+        ctx.request_repaint();
+    }
+}
+
+/// Save captures that are still waiting for the save debounce, e.g. before
+/// quitting.
+pub fn flush_pending_save(history: &Mutex<History>) {
+    flush_pending(&SAVE_THROTTLE, history, save);
+}
+
+/// Hand `history` to `save` if `throttle` is holding back a change.
+fn flush_pending(
+    throttle: &Mutex<SaveThrottle>,
+    history: &Mutex<History>,
+    save: impl FnOnce(&History),
+) {
+    let pending = lock_recovering(throttle).take_pending(Instant::now());
+    if pending {
+        save(&lock_recovering(history));
+    }
+}
+
+/// Quit without losing captures: set `quit`, give the monitor up to
+/// [`SHUTDOWN_TIMEOUT`] to make its final save and stop (`running` turns
+/// false), then save whatever is still pending. That covers a monitor that
+/// isn't running or is in a long wait, which only happens with nothing
+/// pending.
+pub fn shut_down(history: &Mutex<History>, quit: &Mutex<bool>, running: &Mutex<bool>) {
+    *lock_recovering(quit) = true;
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    while *lock_recovering(running) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(20));
+    }
+    flush_pending_save(history);
+}
+
+fn save(history: &History) {
+    if let Err(e) = storage::save(history) {
+        eprintln!("Failed to save history: {e}");
+    }
+}
+
+/// Decides when history changes are written to disk, so a burst of captures
+/// costs one save instead of one each. Only decides; callers save outside the
+/// lock so it is never held together with the history lock.
+#[derive(Debug, Default)]
+struct SaveThrottle {
+    last_save: Option<Instant>,
+    /// A change hasn't been saved yet.
+    pending: bool,
+}
+
+impl SaveThrottle {
+    /// History changed at `now`. Returns whether to save right away: the
+    /// first change after a quiet `interval` is, later ones wait for
+    /// [`SaveThrottle::take_due`].
+    fn changed(&mut self, now: Instant, interval: Duration) -> bool {
+        self.pending = true;
+        self.take_due(now, interval)
+    }
+
+    /// Whether a held back change should be saved now that it is `now`.
+    fn take_due(&mut self, now: Instant, interval: Duration) -> bool {
+        let quiet = self
+            .last_save
+            .is_none_or(|last| now.duration_since(last) >= interval);
+        self.pending && quiet && self.take_pending(now)
+    }
+
+    /// Whether any change is unsaved, marking it saved at `now`.
+    fn take_pending(&mut self, now: Instant) -> bool {
+        if std::mem::take(&mut self.pending) {
+            self.last_save = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Holds back a changed clipboard value until it has been observed unchanged
+/// for the debounce delay. Values replaced before then are never recorded.
+#[derive(Debug, Default)]
+struct Debouncer {
+    pending: Option<(String, Instant)>,
+}
+
+impl Debouncer {
+    /// Feed the current (changed) clipboard value seen at `now`. Returns it
+    /// once it has been stable for `delay`; a zero delay returns it at once.
+    fn settle(&mut self, text: String, now: Instant, delay: Duration) -> Option<String> {
+        if delay.is_zero() {
+            return Some(text);
+        }
+        match &self.pending {
+            Some((pending, since)) if *pending == text => {
+                if now.duration_since(*since) >= delay {
+                    self.pending = None;
+                    Some(text)
+                } else {
+                    None
+                }
+            }
+            _ => {
+                self.pending = Some((text, now));
+                None
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// Lock `mutex`, recovering the data if another thread panicked while
+/// holding it. History stays usable: the worst case is one half-applied push.
+pub fn lock_recovering<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        eprintln!("History lock was poisoned by a panic; recovering");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Clears the monitor's running flag when its thread exits for any reason.
+struct RunningGuard {
+    running: Arc<Mutex<bool>>,
+    ctx: eframe::egui::Context,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        *lock_recovering(&self.running) = false;
+        self.ctx.request_repaint();
+    }
+}
+
+/// Put a picked image entry back on the clipboard.
+pub fn set_image(image: &ImageContent) {
+    let data = arboard::ImageData {
+        width: image.width,
+        height: image.height,
+        bytes: image.bytes().into(),
+    };
+    let set = open()
+        .and_then(|mut clipboard| retry_busy(|| clipboard.set_image(data.clone()), thread::sleep));
+    if let Err(e) = set {
+        eprintln!("Failed to set image: {e}");
+    }
+}
+
+/// Put `content` on the clipboard as the result of picking an entry, with
+/// `html` as its rich-text version if given. On Linux `target` selects the
+/// CLIPBOARD and/or PRIMARY selection; elsewhere there is only one clipboard
+/// and it is ignored.
+pub fn set_text(content: &str, html: Option<&str>, target: PasteTarget) {
+    let mut clipboard = match open() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            eprintln!("Failed to open clipboard for paste: {e}");
+            return;
+        }
+    };
+
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::{LinuxClipboardKind, SetExtLinux};
+
+        if target != PasteTarget::Clipboard {
+            let primary = clipboard.set().clipboard(LinuxClipboardKind::Primary);
+            let set = match html {
+                Some(html) => primary.html(html, Some(content)),
+                None => primary.text(content),
+            };
+            if let Err(e) = set {
+                eprintln!("Failed to set primary selection: {e}");
+            }
+        }
+        if target == PasteTarget::Primary {
+            return;
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = target;
+
+    *lock_recovering(&OWN_WRITE) = Some(content.to_string());
+    let set = retry_busy(
+        || match html {
+            Some(html) => clipboard.set_html(html, Some(content)),
+            None => clipboard.set_text(content),
+        },
+        thread::sleep,
+    );
+    if let Err(e) = set {
+        eprintln!("Failed to set clipboard: {e}");
+    }
+}
+
+/// Clear the OS clipboard after `delay`, but only if it still holds `content`
+/// and no newer paste has scheduled its own clear in the meantime. Anything the
+/// user copied after the paste is left alone.
+pub fn schedule_clear(content: String, delay: Duration) {
+    let generation = CLEAR_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    thread::spawn(move || {
+        thread::sleep(delay);
+        if CLEAR_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let Ok(mut clipboard) = open() else {
+            return;
+        };
+        let current = retry_busy(|| clipboard.get_text(), thread::sleep);
+        if current.is_ok_and(|current| current == content) {
+            if let Err(e) = clipboard.clear() {
+                eprintln!("Failed to clear clipboard: {e}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_recovering_survives_poison() {
+        let history = Arc::new(Mutex::new(History::new(10)));
+        let poisoner = Arc::clone(&history);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(history.is_poisoned());
+
+        assert!(lock_recovering(&history).push("still works".into()));
+        assert!(!history.is_poisoned());
+        assert_eq!(history.lock().unwrap().entries().len(), 1);
+    }
+
+    /// Scripted clipboard contents, counting reads.
+    struct ScriptedClipboard {
+        text: String,
+        reads: usize,
+    }
+
+    impl TextSource for ScriptedClipboard {
+        fn text(&mut self) -> Option<String> {
+            self.reads += 1;
+            Some(self.text.clone())
+        }
+    }
+
+    #[test]
+    fn test_paused_copies_are_never_recorded() {
+        let mut source = ScriptedClipboard {
+            text: "before".into(),
+            reads: 0,
+        };
+        let mut gate = PauseGate::default();
+        let mut debouncer = Debouncer::default();
+        let mut last_text = "before".to_string();
+        let mut recorded = Vec::new();
+        // (paused, clipboard text at this check)
+        let script = [
+            (false, "one"),
+            (true, "secret"),
+            (true, "another secret"),
+            (false, "another secret"),
+            (false, "another secret"),
+            (false, "two"),
+        ];
+        let mut reads_while_paused = 0;
+        for (paused, text) in script {
+            source.text = text.into();
+            let reads = source.reads;
+            let step = gate.step(paused);
+            let captured = gated_text(
+                &mut source,
+                step,
+                &mut last_text,
+                &mut debouncer,
+                Duration::ZERO,
+            );
+            if paused {
+                reads_while_paused += source.reads - reads;
+            }
+            if let Some(text) = captured {
+                last_text = text.clone();
+                recorded.push(text);
+            }
+        }
+        assert_eq!(recorded, vec!["one", "two"]);
+        assert_eq!(reads_while_paused, 0);
+    }
+
+    #[test]
+    fn test_pause_gate_steps() {
+        let mut gate = PauseGate::default();
+        assert_eq!(gate.step(false), PauseStep::Poll);
+        assert_eq!(gate.step(true), PauseStep::Skip);
+        assert_eq!(gate.step(true), PauseStep::Skip);
+        assert_eq!(gate.step(false), PauseStep::Resume);
+        assert_eq!(gate.step(false), PauseStep::Poll);
+    }
+
+    #[test]
+    fn test_debounce_zero_records_immediately() {
+        let mut debouncer = Debouncer::default();
+        let now = Instant::now();
+        assert_eq!(
+            debouncer.settle("a".into(), now, Duration::ZERO),
+            Some("a".into())
+        );
+    }
+
+    #[test]
+    fn test_debounce_keeps_only_settled_value() {
+        let mut debouncer = Debouncer::default();
+        let delay = Duration::from_millis(500);
+        let t0 = Instant::now();
+
+        assert_eq!(debouncer.settle("draft".into(), t0, delay), None);
+        let t1 = t0 + Duration::from_millis(200);
+        assert_eq!(debouncer.settle("draft 2".into(), t1, delay), None);
+        let t2 = t1 + Duration::from_millis(300);
+        assert_eq!(debouncer.settle("final".into(), t2, delay), None);
+        assert_eq!(
+            debouncer.settle("final".into(), t2 + Duration::from_millis(200), delay),
+            None
+        );
+        assert_eq!(
+            debouncer.settle("final".into(), t2 + Duration::from_millis(500), delay),
+            Some("final".into())
+        );
+        // Settled values aren't reported twice
+        assert_eq!(
+            debouncer.settle("final".into(), t2 + Duration::from_millis(600), delay),
+            None
+        );
+    }
+
+    #[test]
+    fn test_debounce_reset_drops_pending() {
+        let mut debouncer = Debouncer::default();
+        let delay = Duration::from_millis(100);
+        let t0 = Instant::now();
+        debouncer.settle("x".into(), t0, delay);
+        debouncer.reset();
+        assert_eq!(debouncer.settle("x".into(), t0 + delay, delay), None);
+    }
+
+    /// Run `settle_observed` over clipboard values polled every 500ms,
+    /// starting from "original", and return what would be recorded.
+    fn recorded(polls: &[&str], delay: Duration) -> Vec<String> {
+        let mut debouncer = Debouncer::default();
+        let mut last_text = "original".to_string();
+        let t0 = Instant::now();
+        let mut recorded = Vec::new();
+        for (i, text) in polls.iter().enumerate() {
+            let now = t0 + Duration::from_millis(500 * i as u64);
+            if let Some(text) =
+                settle_observed(text.to_string(), &last_text, &mut debouncer, now, delay)
+            {
+                last_text = text.clone();
+                recorded.push(text);
+            }
+        }
+        recorded
+    }
+
+    #[test]
+    fn test_drag_revert_within_window_is_dropped() {
+        let delay = settle_delay(Duration::ZERO, true);
+        assert_eq!(delay, DRAG_SETTLE);
+        // A drag briefly put "dragged" on the clipboard, then restored it
+        assert!(recorded(&["dragged", "original", "original"], delay).is_empty());
+        assert!(recorded(&["dragged", "dragged", "original"], delay).is_empty());
+        // Without the setting the transient value is recorded
+        assert_eq!(
+            recorded(
+                &["dragged", "original"],
+                settle_delay(Duration::ZERO, false)
+            ),
+            vec!["dragged", "original"]
+        );
+    }
+
+    #[test]
+    fn test_drag_settle_keeps_real_copies() {
+        let delay = settle_delay(Duration::ZERO, true);
+        assert_eq!(
+            recorded(&["copy", "copy", "copy", "next", "next", "next"], delay),
+            vec!["copy", "next"]
+        );
+        // A longer configured debounce wins
+        let long = Duration::from_secs(2);
+        assert_eq!(settle_delay(long, true), long);
+    }
+
+    #[test]
+    fn test_rapid_changes_coalesce_into_few_saves() {
+        let mut throttle = SaveThrottle::default();
+        let interval = Duration::from_secs(2);
+        let t0 = Instant::now();
+        let mut saves = 0;
+
+        // 50 captures 20ms apart, with the monitor polling every 500ms
+        for i in 0..50 {
+            let now = t0 + Duration::from_millis(20 * i);
+            saves += throttle.changed(now, interval) as usize;
+            if i % 25 == 0 {
+                saves += throttle.take_due(now, interval) as usize;
+            }
+        }
+        // Only the first capture saved at once; the rest wait for the interval
+        assert_eq!(saves, 1);
+        assert!(!throttle.take_due(t0 + Duration::from_millis(1500), interval));
+        assert!(throttle.take_due(t0 + Duration::from_millis(2000), interval));
+        // Nothing left to save
+        assert!(!throttle.take_due(t0 + Duration::from_secs(10), interval));
+    }
+
+    #[test]
+    fn test_save_throttle_final_flush() {
+        let mut throttle = SaveThrottle::default();
+        let interval = Duration::from_secs(2);
+        let t0 = Instant::now();
+        assert!(throttle.changed(t0, interval));
+        assert!(!throttle.changed(t0 + Duration::from_millis(100), interval));
+        // Quitting before the interval passed still saves the held back change
+        assert!(throttle.take_pending(t0 + Duration::from_millis(200)));
+        assert!(!throttle.take_pending(t0 + Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_own_write_skipped_once() {
+        let slot = Mutex::new(None);
+        assert!(!is_own_write(&slot, "pasted"));
+
+        *slot.lock().unwrap() = Some("pasted".to_string());
+        assert!(is_own_write(&slot, "pasted"));
+        // Copying the same text again later is a real copy
+        assert!(!is_own_write(&slot, "pasted"));
+
+        // Someone else changed the clipboard before the monitor saw ours
+        *slot.lock().unwrap() = Some("pasted".to_string());
+        assert!(!is_own_write(&slot, "copied elsewhere"));
+        assert!(!is_own_write(&slot, "pasted"));
+    }
+
+    #[test]
+    fn test_final_flush_saves_last_state() {
+        let throttle = Mutex::new(SaveThrottle::default());
+        let history = Mutex::new(History::new(10));
+        let interval = Duration::from_secs(2);
+        let t0 = Instant::now();
+        let contents = |history: &History| -> Vec<String> {
+            history
+                .entries()
+                .iter()
+                .map(|e| e.content.clone())
+                .collect()
+        };
+        let mut saved = Vec::new();
+        for (i, text) in ["first", "second", "last"].into_iter().enumerate() {
+            lock_recovering(&history).push(text.into());
+            let now = t0 + Duration::from_millis(i as u64 * 100);
+            if lock_recovering(&throttle).changed(now, interval) {
+                saved.push(contents(&lock_recovering(&history)));
+            }
+        }
+        assert_eq!(saved, vec![vec!["first"]]);
+
+        flush_pending(&throttle, &history, |h| saved.push(contents(h)));
+        assert_eq!(saved[1], vec!["last", "second", "first"]);
+        // Already saved; a second shutdown path has nothing to do
+        flush_pending(&throttle, &history, |h| saved.push(contents(h)));
+        assert_eq!(saved.len(), 2);
+    }
+
+    #[test]
+    fn test_save_throttle_zero_interval_saves_every_change() {
+        let mut throttle = SaveThrottle::default();
+        let t0 = Instant::now();
+        for i in 0..5 {
+            assert!(throttle.changed(t0 + Duration::from_millis(i), Duration::ZERO));
+        }
+    }
+
+    #[test]
+    fn test_next_check_waits_longer_only_when_notified_and_idle() {
+        let poll = Duration::from_millis(500);
+        assert_eq!(next_check(false, false, poll), poll);
+        assert_eq!(next_check(false, true, poll), poll);
+        assert_eq!(next_check(true, true, poll), poll);
+        assert_eq!(next_check(true, false, poll), NOTIFIED_RECHECK);
+        // A poll interval longer than the recheck is kept
+        let slow = Duration::from_secs(10);
+        assert_eq!(next_check(true, false, slow), slow);
+    }
+
+    #[test]
+    fn test_wait_for_change_coalesces_and_falls_back() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut changes = Some(receiver);
+        for _ in 0..3 {
+            sender.send(()).unwrap();
+        }
+        wait_for_change(&mut changes, Duration::from_secs(5));
+        assert!(changes.as_ref().unwrap().try_recv().is_err());
+
+        drop(sender);
+        wait_for_change(&mut changes, Duration::from_millis(1));
+        assert!(changes.is_none());
+    }
+
+    /// An operation failing with `error` the first `failures` times, then
+    /// returning how many calls it took.
+    fn flaky(
+        failures: u32,
+        error: fn() -> arboard::Error,
+    ) -> impl FnMut() -> Result<u32, arboard::Error> {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            if calls <= failures {
+                Err(error())
+            } else {
+                Ok(calls)
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_busy_backs_off_until_free() {
+        let mut sleeps = Vec::new();
+        let result = retry_busy(flaky(3, || arboard::Error::ClipboardOccupied), |d| {
+            sleeps.push(d)
+        });
+        assert_eq!(result.unwrap(), 4);
+        assert_eq!(sleeps, [BUSY_BACKOFF, BUSY_BACKOFF * 2, BUSY_BACKOFF * 4]);
+    }
+
+    #[test]
+    fn test_retry_busy_gives_up() {
+        let mut sleeps = 0;
+        let result = retry_busy(flaky(99, || arboard::Error::ClipboardOccupied), |_| {
+            sleeps += 1
+        });
+        assert!(matches!(result, Err(arboard::Error::ClipboardOccupied)));
+        assert_eq!(sleeps, BUSY_ATTEMPTS - 1);
+
+        // Only a busy clipboard is worth waiting for
+        let mut slept = false;
+        let result = retry_busy(flaky(1, || arboard::Error::ContentNotAvailable), |_| {
+            slept = true
+        });
+        assert!(matches!(result, Err(arboard::Error::ContentNotAvailable)));
+        assert!(!slept);
+    }
+
+    #[test]
+    fn test_reopen_delay_is_capped() {
+        assert_eq!(reopen_delay(1), Duration::from_secs(1));
+        assert_eq!(reopen_delay(3), Duration::from_secs(4));
+        assert_eq!(reopen_delay(6), MAX_REOPEN_DELAY);
+        assert_eq!(reopen_delay(u32::MAX), MAX_REOPEN_DELAY);
+    }
+
+    #[test]
+    fn test_ignored_own_copy() {
+        assert!(is_ignored_own_copy(true, || true));
+        assert!(!is_ignored_own_copy(true, || false));
+        assert!(!is_ignored_own_copy(false, || {
+            panic!("foreground window checked with the setting off")
+        }));
+    }
+
+    #[test]
+    fn test_excluded_capture_is_skipped() {
+        // The app's hint wins regardless of settings
+        assert!(is_skipped_capture(|| true, false, || false));
+        assert!(is_skipped_capture(|| true, true, || false));
+        assert!(!is_skipped_capture(|| false, false, || true));
+        assert!(is_skipped_capture(|| false, true, || true));
+        assert!(!is_skipped_capture(|| false, true, || false));
+    }
+
+    #[test]
+    fn test_format_only_update_sequence() {
+        // (html seen on a poll with unchanged text, counts as an update)
+        let polls = [
+            (Some("<b>x</b>"), false),
+            (Some("<i>x</i>"), true),
+            (Some("<i>x</i>"), false),
+            (None, false),
+        ];
+        let mut last_html = Some("<b>x</b>");
+        for (html, expected) in polls {
+            assert_eq!(is_format_only_update(last_html, html), expected);
+            if expected {
+                last_html = html;
+            }
+        }
+    }
+}
@@ -1,11 +1,446 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
 use serde::{Deserialize, Serialize};
 
+use crate::keymap::Action;
+use crate::storage;
+
+/// How `History::push` treats content that is already present in the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DedupMode {
+    /// Move an existing duplicate to the front with an updated timestamp.
+    #[default]
+    MoveToFront,
+    /// Append-only log: every push creates a new entry, even exact repeats.
+    None,
+}
+
+/// What `History::push` does with a copy larger than `Config::max_entry_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OversizePolicy {
+    /// Keep the first `max_entry_bytes` of it, marked as truncated (✂) so
+    /// picking it opens the detail view instead of pasting a partial copy.
+    #[default]
+    Truncate,
+    /// Don't record it.
+    Skip,
+    /// Record a placeholder with its size and a hash of the content.
+    StoreHashOnly,
+}
+
+/// Serialization format of the history file. Yaml and Toml need the crate
+/// built with the `yaml`/`toml` features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StorageFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl StorageFormat {
+    pub const ALL: [StorageFormat; 3] = [
+        StorageFormat::Json,
+        StorageFormat::Yaml,
+        StorageFormat::Toml,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Yaml => "yaml",
+            StorageFormat::Toml => "toml",
+        }
+    }
+}
+
+/// What the Hide action (Escape by default) does while the search box has text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EscapeBehavior {
+    /// Hide the window right away; the query is reset for the next show.
+    #[default]
+    HideImmediately,
+    /// First press clears the query and keeps the window open, the next one hides.
+    ClearThenHide,
+}
+
+/// What the Select action (Enter by default) does with the selected entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EnterAction {
+    #[default]
+    Paste,
+    /// Open the full content for reading and copying parts of it; the
+    /// PasteSelected action (Ctrl+Enter) pastes.
+    OpenDetail,
+}
+
+/// Where the window appears when shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WindowPlacement {
+    #[default]
+    Cursor,
+    /// Next to the tray icon, like the Win+V panel near the taskbar. Falls
+    /// back to the cursor where the icon's position is unknown (Linux).
+    NearTray,
+}
+
+/// Which result is selected when the window is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InitialSelection {
+    /// The newest entry, usually what's on the clipboard now.
+    #[default]
+    Newest,
+    /// The newest entry that isn't on the clipboard now, to re-paste what was
+    /// copied before it.
+    PreviousClipboard,
+}
+
+/// Where the search box sits relative to the results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SearchPosition {
+    #[default]
+    Top,
+    /// Search box at the bottom with the best results just above it.
+    Bottom,
+}
+
+/// How the search query is matched against entries. Ctrl+R cycles through
+/// the modes while the window is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SearchMode {
+    #[default]
+    Fuzzy,
+    /// Case-insensitive exact substring.
+    Substring,
+    /// A regular expression (see `pattern::Regex` for the syntax).
+    Regex,
+}
+
+impl SearchMode {
+    /// The next mode in the Ctrl+R cycle.
+    pub fn next(self) -> SearchMode {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Substring,
+            SearchMode::Substring => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Fuzzy,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchMode::Fuzzy => "Fuzzy",
+            SearchMode::Substring => "Exact",
+            SearchMode::Regex => "Regex",
+        }
+    }
+}
+
+/// Light or dark window visuals. Switchable from the tray's Theme submenu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Theme {
+    /// Follow the OS setting where it can be read, dark otherwise.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "System",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+}
+
+/// The global shortcut that shows and hides the window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// A modifier double-tap (`"Ctrl+Ctrl"`, `"Shift+Shift"`, `"Alt+Alt"`)
+    /// or a combo such as `"Ctrl+Shift+V"`.
+    pub toggle: String,
+    /// Longest gap between the two presses of a double-tap.
+    pub double_tap_ms: u64,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            toggle: "Ctrl+Ctrl".to_string(),
+            double_tap_ms: 300,
+        }
+    }
+}
+
+/// Order of the unfiltered list. Search results are always ranked by score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ListOrder {
+    #[default]
+    NewestFirst,
+    /// Oldest first, e.g. to retrace a sequence of copies in order.
+    OldestFirst,
+    /// Most often pasted first (`ClipboardEntry::use_count`); entries pasted
+    /// equally often stay newest first.
+    MostUsed,
+}
+
+impl ListOrder {
+    /// The next order in the Alt+S cycle.
+    pub fn next(self) -> ListOrder {
+        match self {
+            ListOrder::NewestFirst => ListOrder::OldestFirst,
+            ListOrder::OldestFirst => ListOrder::MostUsed,
+            ListOrder::MostUsed => ListOrder::NewestFirst,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ListOrder::NewestFirst => "Newest",
+            ListOrder::OldestFirst => "Oldest",
+            ListOrder::MostUsed => "Most used",
+        }
+    }
+}
+
+/// Which X11/Wayland selection a pasted entry is written to. Ignored outside Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PasteTarget {
+    /// The regular clipboard (Ctrl+V).
+    #[default]
+    Clipboard,
+    /// The primary selection (middle-click paste).
+    Primary,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub width: f32,
+    /// Upper bound; the window shrinks to fit fewer results.
+    pub height: f32,
+}
+
+impl WindowSize {
+    fn area(self) -> f32 {
+        self.width * self.height
+    }
+
+    /// Index of the preset Ctrl+= / Ctrl+- switches to from `self`: the
+    /// smallest preset larger than it when growing, the largest smaller one
+    /// when shrinking. `None` at either end.
+    pub fn step_preset(self, presets: &[WindowSize], grow: bool) -> Option<usize> {
+        let candidates = presets.iter().enumerate();
+        if grow {
+            candidates
+                .filter(|(_, p)| p.area() > self.area())
+                .min_by(|(_, a), (_, b)| a.area().total_cmp(&b.area()))
+                .map(|(i, _)| i)
+        } else {
+            candidates
+                .filter(|(_, p)| p.area() < self.area())
+                .max_by(|(_, a), (_, b)| a.area().total_cmp(&b.area()))
+                .map(|(i, _)| i)
+        }
+    }
+}
+
+/// Settings read from `config.toml`. Keys missing from the file keep their
+/// defaults.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub max_size: usize,
     pub poll_interval_ms: u64,
+    /// Only record a new clipboard value once it has been unchanged this long
+    /// (0 = record immediately).
+    pub capture_debounce_ms: u64,
+    /// Captures within this long of the last save are saved together once it
+    /// has passed (0 = save every capture).
+    pub save_debounce_ms: u64,
     pub window_width: f32,
     pub window_height: f32,
+    /// Sizes cycled through with Ctrl+= / Ctrl+-. `window_width`/`window_height`
+    /// is the size until one is picked.
+    pub window_presets: Vec<WindowSize>,
+    pub dedup_mode: DedupMode,
+    /// Per-action key combo overrides, e.g. `select = "Ctrl+J"`; an empty
+    /// combo (`quick_select_1 = ""`) unbinds the action.
+    pub keybindings: BTreeMap<Action, String>,
+    /// Show the window immediately and read history.json on a background thread.
+    pub lazy_load: bool,
+    /// Windows: move the popup to the active virtual desktop before showing it.
+    /// Off by default; it relies on a COM interface that some setups lack.
+    pub follow_virtual_desktop: bool,
+    pub storage_format: StorageFormat,
+    /// ArrowUp on the first result jumps to the last one and vice versa.
+    pub wrap_navigation: bool,
+    /// Clear the OS clipboard this many seconds after pasting an ephemeral entry.
+    pub clear_clipboard_after_secs: Option<u64>,
+    /// Apply `clear_clipboard_after_secs` to every paste, not just ephemeral entries.
+    pub clear_clipboard_on_all_pastes: bool,
+    /// Copies with fewer characters (ignoring surrounding whitespace) are never stored.
+    pub min_capture_length: usize,
+    /// Stored entries with fewer characters are hidden from the unfiltered list
+    /// but still show up when searched for.
+    pub min_display_length: usize,
+    /// Skip copies whose Shannon entropy (bits per character) is outside this
+    /// band, e.g. `max_entropy = 5.0` to keep random-looking blobs out.
+    pub min_entropy: Option<f64>,
+    pub max_entropy: Option<f64>,
+    /// Lay results out in a grid this many columns wide (1 = plain list).
+    pub columns: usize,
+    pub escape_behavior: EscapeBehavior,
+    /// Windows: seed the history from the system clipboard history (Win+V) on
+    /// the first launch.
+    pub import_system_history_once: bool,
+    pub paste_target: PasteTarget,
+    /// Show character, word and line counts of the selected entry.
+    pub show_text_stats: bool,
+    /// On startup, delete entries older than this many days (locked ones are kept).
+    pub compact_after_days: Option<u64>,
+    /// Only let compaction delete entries that were never pasted.
+    pub compact_only_unused: bool,
+    /// Take keyboard focus when the window is shown. When false the window
+    /// appears without activating, so typing continues in the current app.
+    pub grab_focus_on_show: bool,
+    /// Global shortcut that captures the current clipboard on demand,
+    /// e.g. `"Ctrl+Shift+C"`.
+    pub capture_hotkey: Option<String>,
+    /// Color entries that look like code in the preview pane and detail view,
+    /// with a simple per-language tokenizer.
+    pub syntax_highlight: bool,
+    /// Collapse runs of adjacent entries at least this similar (0.0–1.0) into one
+    /// expandable row. Display only; stored history is unchanged.
+    pub collapse_similar_threshold: Option<f32>,
+    /// Don't install global input hooks or call native window APIs; show and
+    /// hide only through the tray menu. For machines where those are blocked.
+    pub safe_mode: bool,
+    /// Also capture the HTML version of copies and restore it when pasting,
+    /// so rich text keeps its formatting. RTF is not captured.
+    pub preserve_formats: bool,
+    /// Shell command run with each newly captured text on stdin, e.g. to log URLs.
+    pub on_capture_cmd: Option<String>,
+    /// Shell command run with the picked entry on stdin; its stdout is pasted
+    /// instead. The original is pasted if it fails or times out.
+    pub on_paste_cmd: Option<String>,
+    /// Badge a newly captured top entry as "new" in the list for this many
+    /// seconds after the copy, fading out. `None` (0 in the file) disables
+    /// the badge.
+    #[serde(with = "zero_as_none")]
+    pub new_entry_badge_secs: Option<u64>,
+    /// Larger text and rows, and screen-reader labels carrying each entry's
+    /// timestamp and full content.
+    pub accessible: bool,
+    /// Fetch and show the page title of URL entries. Off by default since it
+    /// makes network requests.
+    pub fetch_url_titles: bool,
+    /// With `preserve_formats`, record a copy whose text matches the latest
+    /// entry but whose formatting differs, updating the stored HTML. Off: such
+    /// formatting-only updates are ignored.
+    pub keep_format_only_updates: bool,
+    pub search_position: SearchPosition,
+    /// Keep removed entries in a restorable trash (Ctrl+Shift+D) for this many
+    /// days. `None` (0 in the file, the default) deletes them outright, so
+    /// nothing removed stays on disk unless asked for.
+    #[serde(with = "zero_as_none")]
+    pub trash_retention_days: Option<u64>,
+    /// Copy the history file into a `backups` folder on every start, keeping
+    /// this many of the newest copies (0 = no backups).
+    pub max_backups: usize,
+    pub enter_action: EnterAction,
+    /// Remove ANSI escape sequences (terminal colors, cursor movement) from
+    /// copies before storing them. Off keeps the raw text.
+    pub strip_ansi: bool,
+    pub initial_selection: InitialSelection,
+    /// Collapse runs of spaces and tabs in copies to one space and drop
+    /// trailing spaces, e.g. for text copied out of PDFs.
+    pub collapse_whitespace: bool,
+    /// Join hard-wrapped lines of a paragraph back into one line when
+    /// capturing. Blank lines and list items keep their line breaks.
+    pub rejoin_wrapped_lines: bool,
+    pub window_placement: WindowPlacement,
+    /// Copies larger than this many bytes are handled by `oversize_policy`.
+    pub max_entry_bytes: Option<usize>,
+    pub oversize_policy: OversizePolicy,
+    /// Title of the popup window. The process id is appended so the native
+    /// window lookup on Windows can't pick up another window of the same name.
+    pub window_title: String,
+    /// Don't let copies made from this app change the history: pasting from
+    /// the window doesn't move the entry to the top, and (on Windows) copies
+    /// while the window is in the foreground aren't recorded. The paste
+    /// itself is never captured again either way.
+    pub ignore_own_copies: bool,
+    /// Save the history before the system sleeps and re-read the clipboard
+    /// after it wakes (Windows only).
+    pub sync_on_power_events: bool,
+    /// Mark each row with a dot colored by the entry's age: green for minutes,
+    /// yellow for hours, gray for days.
+    pub show_age_dots: bool,
+    /// The toggle hotkey (`hotkey.toggle`) toggles the window. Off: it only
+    /// shows (and refocuses) it, so accidental extra presses can't close it.
+    pub hotkey_toggles: bool,
+    /// Hold every new clipboard value for at least a second and drop it if
+    /// the clipboard reverts meanwhile, as some systems do around drags.
+    pub ignore_drag_clipboard: bool,
+    pub list_order: ListOrder,
+    /// Also record copied images. Each is stored as a PNG in the history
+    /// file, so screenshots make it much larger.
+    pub capture_images: bool,
+    /// How often the clipboard monitor and hotkey listener threads are
+    /// restarted if they exit, with growing delays (0 = never).
+    pub max_thread_restarts: u32,
+    pub hotkey: HotkeyConfig,
+    /// After an entry is picked, send Ctrl+V (Cmd+V on macOS) to the app
+    /// that had the focus, so it is pasted right away.
+    pub auto_paste: bool,
+    /// Skip copies that look like passwords or secrets (API tokens, private
+    /// keys, generated passwords). Copies the copying app marks as not for
+    /// clipboard history (Windows) are skipped either way.
+    pub ignore_sensitive: bool,
+    /// Show how long ago each entry was copied ("2m", "3h", "yesterday") at
+    /// the right of its row in the single-column list.
+    pub show_timestamps: bool,
+    /// Ignore trailing whitespace when looking for duplicates, so "foo" and
+    /// "foo\n" are one entry. The form copied first is the one kept.
+    pub trim_on_dedup: bool,
+    /// Show the full content of the selected entry in a pane below the list.
+    /// Ctrl+Shift+P opens and closes it while the window is up.
+    pub show_preview_pane: bool,
+    /// Search mode the window opens with.
+    pub search_mode: SearchMode,
+    /// Show the window at the mouse cursor. Off: it reopens where it was
+    /// last hidden (`window_position`).
+    pub follow_cursor: bool,
+    /// Where the window was last hidden, in points. Saved on hide when
+    /// `follow_cursor` is off.
+    pub window_position: Option<[f32; 2]>,
+    /// Permanently delete entries older than this many days, checked at
+    /// startup and every minute. Pinned and locked entries are kept.
+    pub expire_after_days: Option<u32>,
+    pub theme: Theme,
+    /// The window can't be resized (or shrink to fit its results) below this.
+    pub window_min_width: f32,
+    pub window_min_height: f32,
+    /// Drop the oldest unpinned entries once all entries together (text,
+    /// HTML and image data) take more than this many bytes.
+    pub max_total_bytes: Option<usize>,
+    /// Keep the history (and its backups) here instead of next to this
+    /// file. A relative path is taken from the executable's folder. To move
+    /// `config.toml` too, set `CLIPBOARD_HISTORY_DIR`.
+    pub data_dir: Option<PathBuf>,
+    /// Replace the newest entry instead of adding one when a copy within a
+    /// few seconds only extends it at either end by a little (e.g. a
+    /// selection grown word by word); see `history::is_incremental`.
+    pub collapse_incremental: bool,
 }
 
 impl Default for Config {
@@ -13,8 +448,345 @@ impl Default for Config {
         Self {
             max_size: 100,
             poll_interval_ms: 500,
+            capture_debounce_ms: 0,
+            save_debounce_ms: 2000,
             window_width: 400.0,
             window_height: 500.0,
+            window_presets: vec![
+                WindowSize {
+                    width: 320.0,
+                    height: 300.0,
+                },
+                WindowSize {
+                    width: 400.0,
+                    height: 500.0,
+                },
+                WindowSize {
+                    width: 640.0,
+                    height: 800.0,
+                },
+            ],
+            dedup_mode: DedupMode::default(),
+            keybindings: BTreeMap::new(),
+            lazy_load: false,
+            follow_virtual_desktop: false,
+            storage_format: StorageFormat::default(),
+            wrap_navigation: false,
+            clear_clipboard_after_secs: None,
+            clear_clipboard_on_all_pastes: false,
+            min_capture_length: 0,
+            min_display_length: 0,
+            min_entropy: None,
+            max_entropy: None,
+            columns: 1,
+            escape_behavior: EscapeBehavior::default(),
+            import_system_history_once: false,
+            paste_target: PasteTarget::default(),
+            show_text_stats: false,
+            compact_after_days: None,
+            compact_only_unused: true,
+            grab_focus_on_show: true,
+            capture_hotkey: None,
+            syntax_highlight: false,
+            collapse_similar_threshold: None,
+            safe_mode: false,
+            preserve_formats: false,
+            on_capture_cmd: None,
+            on_paste_cmd: None,
+            new_entry_badge_secs: Some(5),
+            accessible: false,
+            fetch_url_titles: false,
+            keep_format_only_updates: false,
+            search_position: SearchPosition::Top,
+            trash_retention_days: None,
+            max_backups: 5,
+            enter_action: EnterAction::Paste,
+            strip_ansi: false,
+            initial_selection: InitialSelection::Newest,
+            collapse_whitespace: false,
+            rejoin_wrapped_lines: false,
+            window_placement: WindowPlacement::Cursor,
+            max_entry_bytes: None,
+            oversize_policy: OversizePolicy::Truncate,
+            window_title: "Clipboard History".to_string(),
+            ignore_own_copies: false,
+            sync_on_power_events: true,
+            show_age_dots: false,
+            hotkey_toggles: true,
+            ignore_drag_clipboard: false,
+            list_order: ListOrder::NewestFirst,
+            capture_images: false,
+            max_thread_restarts: 5,
+            hotkey: HotkeyConfig::default(),
+            auto_paste: false,
+            ignore_sensitive: false,
+            show_timestamps: true,
+            trim_on_dedup: false,
+            show_preview_pane: false,
+            search_mode: SearchMode::Fuzzy,
+            follow_cursor: true,
+            window_position: None,
+            expire_after_days: None,
+            theme: Theme::System,
+            window_min_width: 240.0,
+            window_min_height: 120.0,
+            max_total_bytes: None,
+            data_dir: None,
+            collapse_incremental: false,
         }
     }
 }
+
+/// TOML has no null, so an `Option` that defaults to `Some` could never be
+/// turned off from the file. This stores `None` as 0 instead.
+mod zero_as_none {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.unwrap_or(0))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Ok(Some(u64::deserialize(deserializer)?).filter(|&n| n > 0))
+    }
+}
+
+/// Path to `config.toml`, in `storage::config_dir`.
+pub fn config_path() -> PathBuf {
+    storage::config_dir().join("config.toml")
+}
+
+/// Load the config file. Returns the defaults if it doesn't exist or can't
+/// be parsed.
+pub fn load() -> Config {
+    load_from(&config_path())
+}
+
+fn load_from(path: &Path) -> Config {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Config::default(),
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", path.display());
+            return Config::default();
+        }
+    };
+    toml::from_str(&data).unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid {}: {e}", path.display());
+        Config::default()
+    })
+}
+
+/// Write the top-level `keys` of `config` to the config file, leaving the
+/// rest of it (other settings, comments) as the user wrote it. A file that
+/// doesn't load is left alone, so a typo isn't replaced by the defaults.
+pub fn save_keys(config: &Config, keys: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    save_keys_to(&config_path(), config, keys)
+}
+
+fn save_keys_to(
+    path: &Path,
+    config: &Config,
+    keys: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let existing = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if let Err(e) = toml::from_str::<Config>(&existing) {
+        return Err(format!("not touching invalid {}: {e}", path.display()).into());
+    }
+    let mut document: toml_edit::Document = existing.parse()?;
+    let values: toml_edit::Document = toml::to_string(config)?.parse()?;
+    for &key in keys {
+        match values.get(key) {
+            Some(value) => document[key] = value.clone(),
+            // Unset options aren't written at all
+            None => {
+                document.remove(key);
+            }
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    storage::write_atomic(path, &document.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn size(width: f32, height: f32) -> WindowSize {
+        WindowSize { width, height }
+    }
+
+    #[test]
+    fn test_step_preset() {
+        let presets = Config::default().window_presets;
+        assert_eq!(size(400.0, 500.0).step_preset(&presets, true), Some(2));
+        assert_eq!(size(400.0, 500.0).step_preset(&presets, false), Some(0));
+        assert_eq!(size(640.0, 800.0).step_preset(&presets, true), None);
+        assert_eq!(size(320.0, 300.0).step_preset(&presets, false), None);
+    }
+
+    fn write_config(path: &Path, config: &Config) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, toml::to_string_pretty(config).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_save_keys_keeps_the_rest_of_the_file() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-config-keys-test");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        let path = tmp_dir.join("config.toml");
+        let mut config = Config {
+            window_position: Some([10.0, 20.0]),
+            ..Config::default()
+        };
+
+        // No file yet: only the saved key is written
+        save_keys_to(&path, &config, &["window_position"]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "window_position = [10.0, 20.0]\n"
+        );
+
+        let edited = "# My settings\nmax_size = 7 # small\n\n[keybindings]\nselect = \"Ctrl+J\"\n";
+        fs::write(&path, edited).unwrap();
+        config.theme = Theme::Dark;
+        config.window_position = None;
+        save_keys_to(&path, &config, &["theme", "window_position"]).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(
+            saved.starts_with("# My settings\nmax_size = 7 # small\n"),
+            "{saved}"
+        );
+        assert!(saved.contains("select = \"Ctrl+J\""), "{saved}");
+        assert!(!saved.contains("window_position"), "{saved}");
+        let loaded = load_from(&path);
+        assert_eq!(loaded.max_size, 7);
+        assert_eq!(loaded.theme, Theme::Dark);
+
+        // A broken file is left for the user to fix
+        fs::write(&path, "max_size = [oops\n").unwrap();
+        assert!(save_keys_to(&path, &config, &["theme"]).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "max_size = [oops\n");
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-config-test");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        let path = tmp_dir.join("config.toml");
+
+        let mut config = Config {
+            max_size: 250,
+            poll_interval_ms: 200,
+            window_width: 640.0,
+            dedup_mode: DedupMode::None,
+            trash_retention_days: Some(3),
+            ..Config::default()
+        };
+        config.keybindings.insert(Action::Select, "Ctrl+J".into());
+        write_config(&path, &config);
+
+        let loaded = load_from(&path);
+        assert_eq!(loaded.max_size, 250);
+        assert_eq!(loaded.poll_interval_ms, 200);
+        assert_eq!(loaded.window_width, 640.0);
+        assert_eq!(loaded.dedup_mode, DedupMode::None);
+        assert_eq!(loaded.trash_retention_days, Some(3));
+        assert_eq!(loaded.keybindings, config.keybindings);
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_window_dimensions_roundtrip() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-config-window-test");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        let path = tmp_dir.join("config.toml");
+
+        let config = Config {
+            window_width: 520.5,
+            window_height: 610.0,
+            window_min_width: 300.0,
+            window_min_height: 150.0,
+            window_presets: vec![size(300.0, 200.0), size(900.0, 700.0)],
+            window_position: Some([-1200.0, 40.0]),
+            ..Config::default()
+        };
+        write_config(&path, &config);
+
+        let loaded = load_from(&path);
+        assert_eq!(loaded.window_width, 520.5);
+        assert_eq!(loaded.window_height, 610.0);
+        assert_eq!(loaded.window_min_width, 300.0);
+        assert_eq!(loaded.window_min_height, 150.0);
+        assert_eq!(loaded.window_presets, config.window_presets);
+        assert_eq!(loaded.window_position, Some([-1200.0, 40.0]));
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_load_falls_back_to_defaults() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-config-fallback-test");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let path = tmp_dir.join("config.toml");
+        let defaults = Config::default();
+
+        // Missing file
+        assert_eq!(load_from(&path).max_size, defaults.max_size);
+
+        // Partial file: unset keys keep their defaults
+        fs::write(&path, "max_size = 7\n").unwrap();
+        let partial = load_from(&path);
+        assert_eq!(partial.max_size, 7);
+        assert_eq!(partial.poll_interval_ms, defaults.poll_interval_ms);
+
+        // Corrupted file
+        fs::write(&path, "max_size = [not toml").unwrap();
+        assert_eq!(load_from(&path).max_size, defaults.max_size);
+        fs::write(&path, "max_size = \"many\"").unwrap();
+        assert_eq!(load_from(&path).max_size, defaults.max_size);
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_theme_serde() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.theme, Theme::System);
+        let config: Config = toml::from_str("theme = \"Light\"").unwrap();
+        assert_eq!(config.theme, Theme::Light);
+        assert!(toml::from_str::<Config>("theme = \"Sepia\"").is_err());
+
+        for theme in Theme::ALL {
+            let text = toml::to_string(&Config {
+                theme,
+                ..Config::default()
+            })
+            .unwrap();
+            assert!(text.contains(&format!("theme = \"{}\"", theme.label())));
+            assert_eq!(toml::from_str::<Config>(&text).unwrap().theme, theme);
+        }
+    }
+
+    #[test]
+    fn test_step_preset_from_custom_size() {
+        // A configured size between presets steps to its neighbours
+        let presets = Config::default().window_presets;
+        assert_eq!(size(500.0, 600.0).step_preset(&presets, true), Some(2));
+        assert_eq!(size(500.0, 600.0).step_preset(&presets, false), Some(1));
+    }
+}
@@ -0,0 +1,85 @@
+use similar::{ChangeTag, TextDiff};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Same,
+    Added,
+    Removed,
+}
+
+/// One line of a line-by-line diff, without its line ending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub change: LineChange,
+    pub text: String,
+}
+
+/// Line-by-line diff from `old` to `new`.
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            change: match change.tag() {
+                ChangeTag::Equal => LineChange::Same,
+                ChangeTag::Insert => LineChange::Added,
+                ChangeTag::Delete => LineChange::Removed,
+            },
+            text: change.value().trim_end_matches(['\n', '\r']).to_string(),
+        })
+        .collect()
+}
+
+/// Character-level similarity of `a` and `b` in `0.0..=1.0`; 1.0 means equal.
+/// Only the first 1000 characters of each are compared.
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let head = |s: &str| s.char_indices().nth(1000).map_or(s.len(), |(i, _)| i);
+    TextDiff::from_chars(&a[..head(a)], &b[..head(b)]).ratio()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(change: LineChange, text: &str) -> DiffLine {
+        DiffLine {
+            change,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn test_identical() {
+        assert_eq!(
+            line_diff("a\nb", "a\nb"),
+            vec![line(LineChange::Same, "a"), line(LineChange::Same, "b")]
+        );
+    }
+
+    #[test]
+    fn test_changed_line() {
+        assert_eq!(
+            line_diff("let x = 1;\nfoo();\n", "let x = 2;\nfoo();\n"),
+            vec![
+                line(LineChange::Removed, "let x = 1;"),
+                line(LineChange::Added, "let x = 2;"),
+                line(LineChange::Same, "foo();"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crlf_is_stripped() {
+        assert_eq!(
+            line_diff("a\r\n", "a\r\nb\r\n"),
+            vec![line(LineChange::Same, "a"), line(LineChange::Added, "b")]
+        );
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("same", "same"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert!((similarity("abcdef", "abcxyz") - 0.5).abs() < f32::EPSILON);
+    }
+}
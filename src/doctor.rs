@@ -0,0 +1,147 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use arboard::Clipboard;
+
+use crate::config::Config;
+use crate::hotkey::{self, GlobalCombo, ToggleHotkey};
+use crate::keymap::Keymap;
+use crate::{storage, tray};
+
+/// How long the hotkey listener must stay up to count as started.
+const LISTENER_WAIT: Duration = Duration::from_millis(500);
+
+/// Run each subsystem's startup path once and print a pass/fail line per
+/// check. Returns whether everything passed.
+pub fn run(config: &Config) -> bool {
+    let results = [
+        report("clipboard", check_clipboard),
+        report("storage", check_storage),
+        report("config", || check_config(config)),
+        report("hotkey", || check_hotkey(config)),
+        report("tray", check_tray),
+    ];
+    results.iter().all(|passed| *passed)
+}
+
+/// Run one check and print its line.
+fn report(name: &str, check: impl FnOnce() -> Result<String, String>) -> bool {
+    // Some backends panic instead of returning an error (e.g. no display)
+    let result = panic::catch_unwind(AssertUnwindSafe(check))
+        .unwrap_or_else(|_| Err("panicked (see above)".into()));
+    match result {
+        Ok(detail) => {
+            println!("[pass] {name:<10} {detail}");
+            true
+        }
+        Err(detail) => {
+            println!("[FAIL] {name:<10} {detail}");
+            false
+        }
+    }
+}
+
+fn check_clipboard() -> Result<String, String> {
+    let mut clipboard = Clipboard::new().map_err(|e| format!("cannot open clipboard: {e}"))?;
+    match clipboard.get_text() {
+        Ok(text) => Ok(format!("read {} characters", text.chars().count())),
+        Err(arboard::Error::ContentNotAvailable) => Ok("readable (no text on it)".into()),
+        Err(e) => Err(format!("cannot read clipboard: {e}")),
+    }
+}
+
+fn check_storage() -> Result<String, String> {
+    let path = storage::check_writable()
+        .map_err(|e| format!("{} is not writable: {e}", storage::history_path().display()))?;
+    storage::check_readable().map_err(|e| format!("{} is corrupted: {e}", path.display()))?;
+    Ok(path.display().to_string())
+}
+
+fn check_config(config: &Config) -> Result<String, String> {
+    config_problems(config)
+        .map(|()| "valid".into())
+        .map_err(|problems| problems.join("; "))
+}
+
+/// Everything in `config` that startup would reject or silently fall back on.
+fn config_problems(config: &Config) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+    if let Err(e) = Keymap::from_overrides(&config.keybindings) {
+        problems.push(format!("keybindings: {e}"));
+    }
+    if let Err(e) = config.hotkey.toggle.parse::<ToggleHotkey>() {
+        problems.push(format!("hotkey.toggle: {e}"));
+    }
+    if let Some(combo) = &config.capture_hotkey {
+        if let Err(e) = combo.parse::<GlobalCombo>() {
+            problems.push(format!("capture_hotkey: {e}"));
+        }
+    }
+    if config.max_size == 0 {
+        problems.push("max_size is 0, nothing would be kept".into());
+    }
+    if config.columns == 0 {
+        problems.push("columns must be at least 1".into());
+    }
+    if let (Some(min), Some(max)) = (config.min_entropy, config.max_entropy) {
+        if min > max {
+            problems.push(format!("min_entropy {min} is above max_entropy {max}"));
+        }
+    }
+    if config.window_width < config.window_min_width
+        || config.window_height < config.window_min_height
+    {
+        problems.push(format!(
+            "window size {}x{} is below window_min_width/window_min_height {}x{}",
+            config.window_width,
+            config.window_height,
+            config.window_min_width,
+            config.window_min_height
+        ));
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+fn check_hotkey(config: &Config) -> Result<String, String> {
+    if config.safe_mode {
+        return Ok("skipped (global hooks are off in safe mode)".into());
+    }
+    hotkey::check_listener(LISTENER_WAIT)
+        .map(|()| "listener started".into())
+        .map_err(|e| format!("listener failed to start: {e}"))
+}
+
+fn check_tray() -> Result<String, String> {
+    tray::check()
+        .map(|()| "icon created".into())
+        .map_err(|e| format!("cannot create tray icon: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert_eq!(config_problems(&Config::default()), Ok(()));
+    }
+
+    #[test]
+    fn test_config_problems_are_all_reported() {
+        let mut config = Config {
+            capture_hotkey: Some("Ctrl+Nope".into()),
+            columns: 0,
+            window_min_width: 500.0,
+            ..Config::default()
+        };
+        config
+            .keybindings
+            .insert(crate::keymap::Action::Select, "Ctrl+".into());
+        let problems = config_problems(&config).unwrap_err();
+        assert_eq!(problems.len(), 4, "{problems:?}");
+    }
+}
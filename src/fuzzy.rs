@@ -1,62 +1,133 @@
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
 
+use crate::config::SearchMode;
 use crate::history::ClipboardEntry;
+use crate::pattern::{PatternError, Regex};
 
-/// Search entries by fuzzy matching against the query.
+/// Search entries by fuzzy matching against the query, with smart case
+/// unless `case_sensitive`.
 /// - Empty query: returns all entries in order (with score 0).
 /// - Non-empty query: returns only matching entries, sorted by score descending.
-pub fn search<'a>(query: &str, entries: &'a [ClipboardEntry]) -> Vec<(&'a ClipboardEntry, i64)> {
+pub fn search<'a>(
+    query: &str,
+    entries: &'a [ClipboardEntry],
+    case_sensitive: bool,
+) -> Vec<(&'a ClipboardEntry, i64)> {
+    fuzzy_rank(query, entries, |entry| &entry.content, case_sensitive)
+}
+
+/// Search entries with the given mode. Fuzzy is [`search`]; Substring and
+/// Regex keep the history order and give every match a score of 0. An empty
+/// query returns all entries in every mode. `case_sensitive` applies to
+/// Fuzzy and Substring; a regex always respects case.
+pub fn search_with<'a>(
+    query: &str,
+    entries: &'a [ClipboardEntry],
+    mode: SearchMode,
+    case_sensitive: bool,
+) -> Result<Vec<(&'a ClipboardEntry, i64)>, PatternError> {
+    if query.is_empty() || mode == SearchMode::Fuzzy {
+        return Ok(search(query, entries, case_sensitive));
+    }
+    rank(query, entries, |entry| &entry.content, mode, case_sensitive)
+}
+
+/// Whether a non-fuzzy query matches an entry's text.
+type Matcher = dyn Fn(&str) -> Result<bool, PatternError>;
+
+/// [`search_with`] over any items, reading each one's text with `content`.
+/// Lets the background search rank a snapshot without whole entries.
+pub fn rank<'a, T>(
+    query: &str,
+    items: &'a [T],
+    content: impl Fn(&T) -> &str,
+    mode: SearchMode,
+    case_sensitive: bool,
+) -> Result<Vec<(&'a T, i64)>, PatternError> {
+    let matches: Box<Matcher> = match mode {
+        _ if query.is_empty() => return Ok(fuzzy_rank(query, items, content, case_sensitive)),
+        SearchMode::Fuzzy => return Ok(fuzzy_rank(query, items, content, case_sensitive)),
+        SearchMode::Substring if case_sensitive => {
+            let query = query.to_string();
+            Box::new(move |content: &str| Ok(content.contains(&query)))
+        }
+        SearchMode::Substring => {
+            let query = query.to_lowercase();
+            Box::new(move |content: &str| Ok(content.to_lowercase().contains(&query)))
+        }
+        SearchMode::Regex => {
+            let regex = Regex::new(query)?;
+            Box::new(move |content: &str| regex.is_match(content))
+        }
+    };
+    let mut results = Vec::new();
+    for item in items {
+        if matches(content(item))? {
+            results.push((item, 0));
+        }
+    }
+    Ok(results)
+}
+
+fn fuzzy_rank<'a, T>(
+    query: &str,
+    items: &'a [T],
+    content: impl Fn(&T) -> &str,
+    case_sensitive: bool,
+) -> Vec<(&'a T, i64)> {
     if query.is_empty() {
-        return entries.iter().map(|e| (e, 0i64)).collect();
+        return items.iter().map(|item| (item, 0i64)).collect();
     }
 
-    let matcher = SkimMatcherV2::default();
-    let mut results: Vec<(&ClipboardEntry, i64)> = entries
+    // Smart case unless asked: an uppercase letter in the query respects case
+    let matcher = if case_sensitive {
+        SkimMatcherV2::default().respect_case()
+    } else {
+        SkimMatcherV2::default()
+    };
+    let mut results: Vec<(&T, i64)> = items
         .iter()
-        .filter_map(|entry| {
+        .filter_map(|item| {
             matcher
-                .fuzzy_match(&entry.content, query)
-                .map(|score| (entry, score))
+                .fuzzy_match(content(item), query)
+                .map(|score| (item, score))
         })
         .collect();
 
-    results.sort_by(|a, b| b.1.cmp(&a.1));
+    results.sort_by_key(|r| std::cmp::Reverse(r.1));
     results
 }
 
+/// Parse an `id:<n>` query, which jumps straight to the entry with that id
+/// instead of fuzzy matching.
+pub fn parse_id_query(query: &str) -> Option<u64> {
+    query.trim().strip_prefix("id:")?.trim().parse().ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
-
-    fn make_entry(id: u64, content: &str) -> ClipboardEntry {
-        ClipboardEntry {
-            id,
-            content: content.to_string(),
-            created_at: Utc::now(),
-        }
-    }
 
     #[test]
     fn test_empty_query_returns_all() {
         let entries = vec![
-            make_entry(1, "hello"),
-            make_entry(2, "world"),
-            make_entry(3, "foo"),
+            ClipboardEntry::for_test(1, "hello"),
+            ClipboardEntry::for_test(2, "world"),
+            ClipboardEntry::for_test(3, "foo"),
         ];
-        let results = search("", &entries);
+        let results = search("", &entries, false);
         assert_eq!(results.len(), 3);
     }
 
     #[test]
     fn test_fuzzy_match_filters() {
         let entries = vec![
-            make_entry(1, "hello world"),
-            make_entry(2, "goodbye world"),
-            make_entry(3, "foo bar"),
+            ClipboardEntry::for_test(1, "hello world"),
+            ClipboardEntry::for_test(2, "goodbye world"),
+            ClipboardEntry::for_test(3, "foo bar"),
         ];
-        let results = search("helo", &entries);
+        let results = search("helo", &entries, false);
         // "hello world" should match "helo" fuzzily
         assert!(!results.is_empty());
         assert!(results.iter().any(|(e, _)| e.content == "hello world"));
@@ -64,23 +135,104 @@ mod tests {
 
     #[test]
     fn test_no_match_returns_empty() {
-        let entries = vec![make_entry(1, "hello"), make_entry(2, "world")];
-        let results = search("zzzzz", &entries);
+        let entries = vec![
+            ClipboardEntry::for_test(1, "hello"),
+            ClipboardEntry::for_test(2, "world"),
+        ];
+        let results = search("zzzzz", &entries, false);
         assert!(results.is_empty());
     }
 
     #[test]
     fn test_results_sorted_by_score() {
         let entries = vec![
-            make_entry(1, "abc"),
-            make_entry(2, "abcdef"),
-            make_entry(3, "xyzabc"),
+            ClipboardEntry::for_test(1, "abc"),
+            ClipboardEntry::for_test(2, "abcdef"),
+            ClipboardEntry::for_test(3, "xyzabc"),
         ];
-        let results = search("abc", &entries);
+        let results = search("abc", &entries, false);
         // All should match; check they're sorted by score descending
         assert!(results.len() >= 2);
         for i in 0..results.len() - 1 {
             assert!(results[i].1 >= results[i + 1].1);
         }
     }
+
+    fn ids(results: &[(&ClipboardEntry, i64)]) -> Vec<u64> {
+        results.iter().map(|(entry, _)| entry.id).collect()
+    }
+
+    #[test]
+    fn test_substring_mode_is_exact_and_case_insensitive() {
+        let entries = vec![
+            ClipboardEntry::for_test(1, "https://Example.com/a"),
+            ClipboardEntry::for_test(2, "e-x-a-m-p-l-e"),
+            ClipboardEntry::for_test(3, "see example.com"),
+        ];
+        let results = search_with("example.COM", &entries, SearchMode::Substring, false).unwrap();
+        // In history order, without the fuzzy near-miss
+        assert_eq!(ids(&results), vec![1, 3]);
+        assert!(results.iter().all(|(_, score)| *score == 0));
+    }
+
+    #[test]
+    fn test_regex_mode() {
+        let entries = vec![
+            ClipboardEntry::for_test(1, "order 1234"),
+            ClipboardEntry::for_test(2, "no digits"),
+            ClipboardEntry::for_test(3, "42"),
+        ];
+        let results = search_with(r"^\d+$", &entries, SearchMode::Regex, false).unwrap();
+        assert_eq!(ids(&results), vec![3]);
+        let results = search_with(r"order \d+", &entries, SearchMode::Regex, false).unwrap();
+        assert_eq!(ids(&results), vec![1]);
+    }
+
+    #[test]
+    fn test_invalid_regex_is_an_error() {
+        let entries = vec![ClipboardEntry::for_test(1, "(")];
+        assert!(search_with("(", &entries, SearchMode::Regex, false).is_err());
+        // Fine as a substring
+        let results = search_with("(", &entries, SearchMode::Substring, false).unwrap();
+        assert_eq!(ids(&results), vec![1]);
+    }
+
+    #[test]
+    fn test_fuzzy_mode_and_empty_query() {
+        let entries = vec![
+            ClipboardEntry::for_test(1, "hello world"),
+            ClipboardEntry::for_test(2, "foo"),
+        ];
+        let results = search_with("helo", &entries, SearchMode::Fuzzy, false).unwrap();
+        assert_eq!(ids(&results), vec![1]);
+        for mode in [SearchMode::Fuzzy, SearchMode::Substring, SearchMode::Regex] {
+            assert_eq!(search_with("", &entries, mode, false).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        let entries = vec![
+            ClipboardEntry::for_test(1, "foobar"),
+            ClipboardEntry::for_test(2, "FooBar"),
+        ];
+        assert_eq!(ids(&search("foo", &entries, false)).len(), 2);
+        assert_eq!(ids(&search("Foo", &entries, false)), vec![2]);
+        assert_eq!(ids(&search("Foo", &entries, true)), vec![2]);
+        assert_eq!(ids(&search("foo", &entries, true)), vec![1]);
+        let exact = |query, case_sensitive| {
+            search_with(query, &entries, SearchMode::Substring, case_sensitive).unwrap()
+        };
+        assert_eq!(ids(&exact("Foo", false)), vec![1, 2]);
+        assert_eq!(ids(&exact("Foo", true)), vec![2]);
+    }
+
+    #[test]
+    fn test_parse_id_query() {
+        assert_eq!(parse_id_query("id:42"), Some(42));
+        assert_eq!(parse_id_query(" id: 7 "), Some(7));
+        assert_eq!(parse_id_query("id:"), None);
+        assert_eq!(parse_id_query("id:abc"), None);
+        assert_eq!(parse_id_query("42"), None);
+    }
 }
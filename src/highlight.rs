@@ -0,0 +1,274 @@
+use std::ops::Range;
+
+use eframe::egui::{self, text::LayoutJob, Color32, TextFormat};
+
+/// Best-effort language of a code entry; only picks the comment syntax and
+/// keyword set used for highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    Shell,
+    /// Looks like code but matches no specific language.
+    Generic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Comment,
+    Keyword,
+    Literal,
+    StringLit,
+    Punctuation,
+    Whitespace,
+    Other,
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "else", "enum", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where",
+    "while",
+];
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "class", "def", "elif", "else", "except", "for", "from", "if", "import", "in",
+    "is", "lambda", "not", "or", "pass", "raise", "return", "self", "try", "while", "with",
+    "yield",
+];
+const JS_KEYWORDS: &[&str] = &[
+    "async", "await", "break", "case", "class", "const", "else", "export", "for", "function", "if",
+    "import", "let", "new", "return", "switch", "this", "throw", "try", "var", "while",
+];
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "do", "done", "echo", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+    "in", "sudo", "then", "while",
+];
+const LITERALS: &[&str] = &[
+    "true",
+    "false",
+    "True",
+    "False",
+    "None",
+    "null",
+    "undefined",
+    "nil",
+];
+
+impl Language {
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => RUST_KEYWORDS,
+            Language::Python => PYTHON_KEYWORDS,
+            Language::JavaScript => JS_KEYWORDS,
+            Language::Shell => SHELL_KEYWORDS,
+            Language::Generic => &[],
+        }
+    }
+
+    fn line_comment(self) -> &'static str {
+        match self {
+            Language::Python | Language::Shell => "#",
+            _ => "//",
+        }
+    }
+}
+
+/// Classify `text` as code and guess its language. Returns `None` for prose,
+/// URLs and other non-code content. Only the first few KB are inspected.
+pub fn guess_language(text: &str) -> Option<Language> {
+    let sample: String = text.chars().take(2000).collect();
+    let has = |needles: &[&str]| needles.iter().any(|n| sample.contains(n));
+
+    if has(&["fn ", "let mut ", "impl ", "pub fn", "::", "#[derive"]) {
+        return Some(Language::Rust);
+    }
+    if has(&["def ", "import ", "elif ", "self."]) && sample.contains(':') {
+        return Some(Language::Python);
+    }
+    if has(&["function ", "const ", "=> ", "console.", "document."]) {
+        return Some(Language::JavaScript);
+    }
+    if sample.starts_with("#!") || has(&["$ ", " | grep", "&& ", "sudo ", "export "]) {
+        return Some(Language::Shell);
+    }
+
+    // Fall back to punctuation density: code is heavy on brackets and ;
+    let total = sample.chars().filter(|c| !c.is_whitespace()).count();
+    let symbols = sample
+        .chars()
+        .filter(|c| matches!(c, '{' | '}' | '(' | ')' | '[' | ']' | ';' | '=' | '<' | '>'))
+        .count();
+    if total >= 8 && symbols * 8 >= total && sample.contains(['{', ';']) {
+        Some(Language::Generic)
+    } else {
+        None
+    }
+}
+
+/// Split `text` into highlighted byte ranges. Every byte is covered exactly
+/// once, in order.
+pub fn tokenize(text: &str, lang: Language) -> Vec<(TokenKind, Range<usize>)> {
+    let comment = lang.line_comment();
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    let mut pos = 0;
+
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = if rest.starts_with(comment) {
+            (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if c == '"' || c == '\'' || c == '`' {
+            // Up to the matching quote, skipping escaped ones; unterminated
+            // strings run to the end of the line.
+            let mut escaped = false;
+            let end = rest[1..]
+                .char_indices()
+                .find(|&(_, ch)| {
+                    let done = (ch == c && !escaped) || ch == '\n';
+                    escaped = ch == '\\' && !escaped;
+                    done
+                })
+                .map_or(rest.len(), |(i, ch)| match ch {
+                    '\n' => 1 + i,
+                    _ => 1 + i + ch.len_utf8(),
+                });
+            (TokenKind::StringLit, end)
+        } else if c.is_whitespace() {
+            (TokenKind::Whitespace, prefix_len(rest, char::is_whitespace))
+        } else if c.is_ascii_digit() {
+            (
+                TokenKind::Literal,
+                prefix_len(rest, |ch| {
+                    ch.is_ascii_alphanumeric() || ch == '.' || ch == '_'
+                }),
+            )
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = prefix_len(rest, |ch| ch.is_alphanumeric() || ch == '_');
+            let word = &rest[..len];
+            let kind = if lang.keywords().contains(&word) {
+                TokenKind::Keyword
+            } else if LITERALS.contains(&word) {
+                TokenKind::Literal
+            } else {
+                TokenKind::Other
+            };
+            (kind, len)
+        } else {
+            (TokenKind::Punctuation, c.len_utf8())
+        };
+
+        tokens.push((kind, pos..pos + len));
+        rest = &rest[len..];
+        pos += len;
+    }
+    tokens
+}
+
+fn prefix_len(s: &str, pred: impl Fn(char) -> bool) -> usize {
+    s.find(|c: char| !pred(c)).unwrap_or(s.len())
+}
+
+/// Build a monospace layout of `code` highlighted as `lang`.
+pub fn layout_job(code: &str, lang: Language, style: &egui::Style) -> LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(style);
+    let dark = style.visuals.dark_mode;
+    let default_color = style.visuals.text_color();
+
+    let mut job = LayoutJob::default();
+    for (kind, range) in tokenize(code, lang) {
+        let color = match kind {
+            TokenKind::Comment => Color32::GRAY,
+            TokenKind::Keyword if dark => Color32::from_rgb(255, 100, 100),
+            TokenKind::Keyword => Color32::from_rgb(235, 0, 0),
+            TokenKind::Literal if dark => Color32::from_rgb(110, 180, 255),
+            TokenKind::Literal => Color32::from_rgb(40, 90, 200),
+            TokenKind::StringLit if dark => Color32::from_rgb(110, 200, 110),
+            TokenKind::StringLit => Color32::from_rgb(0, 130, 0),
+            TokenKind::Punctuation if dark => Color32::LIGHT_GRAY,
+            TokenKind::Punctuation => Color32::DARK_GRAY,
+            TokenKind::Whitespace | TokenKind::Other => default_color,
+        };
+        job.append(
+            &code[range],
+            0.0,
+            TextFormat::simple(font_id.clone(), color),
+        );
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str, lang: Language) -> Vec<(TokenKind, &str)> {
+        tokenize(text, lang)
+            .into_iter()
+            .filter(|(kind, _)| *kind != TokenKind::Whitespace)
+            .map(|(kind, range)| (kind, &text[range]))
+            .collect()
+    }
+
+    #[test]
+    fn test_guess_language() {
+        assert_eq!(
+            guess_language("fn main() { let x = 1; }"),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            guess_language("def f(x):\n    return x"),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            guess_language("const x = () => 1;"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(
+            guess_language("cat log.txt | grep error"),
+            Some(Language::Shell)
+        );
+        assert_eq!(
+            guess_language("if (a[i] > b) { c = 0; }"),
+            Some(Language::Generic)
+        );
+    }
+
+    #[test]
+    fn test_prose_is_not_code() {
+        assert_eq!(
+            guess_language("Meeting moved to Thursday, see you then."),
+            None
+        );
+        assert_eq!(guess_language("https://example.com/page"), None);
+        assert_eq!(guess_language(""), None);
+    }
+
+    #[test]
+    fn test_tokenize_rust() {
+        assert_eq!(
+            kinds("let s = \"a\\\"b\"; // done", Language::Rust),
+            vec![
+                (TokenKind::Keyword, "let"),
+                (TokenKind::Other, "s"),
+                (TokenKind::Punctuation, "="),
+                (TokenKind::StringLit, "\"a\\\"b\""),
+                (TokenKind::Punctuation, ";"),
+                (TokenKind::Comment, "// done"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_covers_all_bytes() {
+        let text = "x = 'unterminated\ny = 42 # ünï\n";
+        let tokens = tokenize(text, Language::Python);
+        let mut pos = 0;
+        for (_, range) in &tokens {
+            assert_eq!(range.start, pos);
+            pos = range.end;
+        }
+        assert_eq!(pos, text.len());
+        assert!(kinds(text, Language::Python).contains(&(TokenKind::StringLit, "'unterminated")));
+        assert!(kinds(text, Language::Python).contains(&(TokenKind::Comment, "# ünï")));
+    }
+}
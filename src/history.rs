@@ -1,11 +1,118 @@
-use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::config::{Config, DedupMode, OversizePolicy};
+use crate::image::{self, ImageContent};
+use crate::kind::{self, ContentKind};
+use crate::text::{collapse_whitespace, rejoin_wrapped_lines, shannon_entropy, strip_ansi};
+
+/// How many deleted entries `History::undo_remove` can bring back.
+const UNDO_LIMIT: usize = 10;
+
+/// Most characters a copy may add to the newest entry and still count as
+/// extending it (`Config::collapse_incremental`).
+const INCREMENTAL_MAX_DELTA: usize = 64;
+/// How recent the newest entry must be for a copy to extend it.
+const INCREMENTAL_WINDOW: TimeDelta = TimeDelta::seconds(10);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardEntry {
     pub id: u64,
     pub content: String,
     pub created_at: DateTime<Utc>,
+    /// Delete this entry right after it has been pasted once.
+    #[serde(default)]
+    pub ephemeral: bool,
+    /// Protected from deletion and clearing until unlocked.
+    #[serde(default)]
+    pub locked: bool,
+    /// Kept on top of the list and never trimmed by `max_size` or compaction.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Expand placeholders such as `{date}` when pasting (see `template.rs`).
+    #[serde(default)]
+    pub template: bool,
+    /// Cut off at `Config::max_entry_bytes` when captured, so `content` is
+    /// only the start of the copy.
+    #[serde(default)]
+    pub truncated: bool,
+    /// How many times this entry has been pasted from the window.
+    #[serde(default)]
+    pub use_count: u32,
+    /// HTML version of the copy, restored alongside `content` when pasting
+    /// (see `Config::preserve_formats`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    /// A copied image, pasted back instead of `content`, which then holds
+    /// its label (see `ImageContent::label`).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "image::deserialize_lenient"
+    )]
+    pub image: Option<ImageContent>,
+    /// Detected when captured. `None` only for entries saved before it
+    /// existed, until [`History::classify_missing_kinds`] runs on load.
+    #[serde(default)]
+    pub kind: Option<ContentKind>,
+}
+
+impl ClipboardEntry {
+    /// A plain text entry created now, for tests that build entries directly.
+    #[cfg(test)]
+    pub fn for_test(id: u64, content: impl Into<String>) -> Self {
+        Self {
+            id,
+            content: content.into(),
+            created_at: Utc::now(),
+            ephemeral: false,
+            locked: false,
+            pinned: false,
+            template: false,
+            truncated: false,
+            use_count: 0,
+            html: None,
+            image: None,
+            kind: Some(ContentKind::Text),
+        }
+    }
+
+    /// Bytes of text, HTML and image data held for this entry.
+    pub fn size_bytes(&self) -> usize {
+        self.content.len()
+            + self.html.as_ref().map_or(0, String::len)
+            + self.image.as_ref().map_or(0, |image| image.bytes().len())
+    }
+
+    /// Whether this looks like a copied emoji or special character rather
+    /// than text: a few non-ASCII characters with no letters, digits or spaces.
+    pub fn is_symbol(&self) -> bool {
+        let symbol = self.content.trim();
+        !symbol.is_empty()
+            && !symbol.is_ascii()
+            && symbol.chars().count() <= 8
+            && !symbol
+                .chars()
+                .any(|c| c.is_alphanumeric() || c.is_whitespace())
+    }
+}
+
+/// An entry removed from the history, kept in the trash until
+/// `Config::trash_retention_days` have passed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedEntry {
+    pub entry: ClipboardEntry,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// What a removal did: entries removed, and entries kept for being locked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Removal {
+    pub removed: usize,
+    pub locked: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -14,6 +121,113 @@ pub struct History {
     max_size: usize,
     #[serde(default)]
     next_id: u64,
+    /// Removed entries, most recently deleted first.
+    #[serde(default)]
+    trash: Vec<TrashedEntry>,
+    /// How long trashed entries are kept; `None` deletes outright.
+    #[serde(skip)]
+    trash_retention: Option<Duration>,
+    #[serde(skip)]
+    dedup_mode: DedupMode,
+    #[serde(skip)]
+    min_capture_length: usize,
+    #[serde(skip)]
+    min_entropy: Option<f64>,
+    #[serde(skip)]
+    max_entropy: Option<f64>,
+    /// Whether a copy of the latest text with only new formatting updates it.
+    #[serde(skip)]
+    keep_format_only_updates: bool,
+    /// Whether trailing whitespace is ignored when looking for duplicates.
+    #[serde(skip)]
+    trim_on_dedup: bool,
+    #[serde(skip)]
+    strip_ansi: bool,
+    #[serde(skip)]
+    collapse_whitespace: bool,
+    #[serde(skip)]
+    rejoin_wrapped_lines: bool,
+    #[serde(skip)]
+    max_entry_bytes: Option<usize>,
+    #[serde(skip)]
+    collapse_incremental: bool,
+    /// Byte budget for the unpinned entries, see `ClipboardEntry::size_bytes`.
+    #[serde(skip)]
+    max_total_bytes: Option<usize>,
+    #[serde(skip)]
+    oversize_policy: OversizePolicy,
+    /// Age after which entries are deleted by `prune_expired`.
+    #[serde(skip)]
+    expire_after: Option<TimeDelta>,
+    /// Recently deleted entries with their former positions, most recent
+    /// last. Only kept in memory.
+    #[serde(skip)]
+    undo: VecDeque<(usize, ClipboardEntry)>,
+    /// How many text entries there are per `dedup_hash`, so a push of new
+    /// content needn't scan the whole history for a duplicate. `None` until
+    /// first needed and after removals, which don't maintain it.
+    #[serde(skip)]
+    text_hashes: Option<HashMap<u64, usize>>,
+}
+
+/// Whether `content` has at least `min` characters, not counting surrounding
+/// whitespace. Used by both the capture and the display length filters.
+pub fn meets_min_length(content: &str, min: usize) -> bool {
+    min == 0 || content.trim().chars().count() >= min
+}
+
+/// Whether `a` and `b` count as the same text for deduplication, ignoring
+/// trailing whitespace with `trim_end`.
+fn same_text(a: &str, b: &str, trim_end: bool) -> bool {
+    if trim_end {
+        a.trim_end() == b.trim_end()
+    } else {
+        a == b
+    }
+}
+
+/// Whether `new` is `previous` grown at one end, as when a selection is
+/// extended and copied again: `previous` is non-empty, `new` starts or ends
+/// with it and adds between 1 and [`INCREMENTAL_MAX_DELTA`] characters.
+fn is_incremental(previous: &str, new: &str) -> bool {
+    if previous.is_empty() || new.len() <= previous.len() {
+        return false;
+    }
+    let Some(added) = new
+        .strip_prefix(previous)
+        .or_else(|| new.strip_suffix(previous))
+    else {
+        return false;
+    };
+    added.chars().count() <= INCREMENTAL_MAX_DELTA
+}
+
+/// Hash of `content` as deduplication sees it: equal for texts that
+/// [`same_text`] considers the same.
+fn dedup_hash(content: &str, trim_end: bool) -> u64 {
+    let content = if trim_end {
+        content.trim_end()
+    } else {
+        content
+    };
+    fnv1a(content.as_bytes())
+}
+
+/// Byte count for display, e.g. `"12.3 MB"`.
+pub fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// 64-bit FNV-1a hash; unlike `DefaultHasher` it is stable across Rust
+/// releases, so placeholders of the same content stay equal.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
 }
 
 impl History {
@@ -22,55 +236,670 @@ impl History {
             entries: Vec::new(),
             max_size,
             next_id: 1,
+            trash: Vec::new(),
+            trash_retention: None,
+            dedup_mode: DedupMode::default(),
+            min_capture_length: 0,
+            min_entropy: None,
+            max_entropy: None,
+            keep_format_only_updates: false,
+            trim_on_dedup: false,
+            strip_ansi: false,
+            collapse_whitespace: false,
+            rejoin_wrapped_lines: false,
+            max_entry_bytes: None,
+            collapse_incremental: false,
+            max_total_bytes: None,
+            oversize_policy: OversizePolicy::default(),
+            expire_after: None,
+            undo: VecDeque::new(),
+            text_hashes: None,
+        }
+    }
+
+    /// Apply the runtime settings from `config` that aren't persisted with the history.
+    pub fn apply_config(&mut self, config: &Config) {
+        self.dedup_mode = config.dedup_mode;
+        self.keep_format_only_updates = config.keep_format_only_updates;
+        self.trim_on_dedup = config.trim_on_dedup;
+        self.strip_ansi = config.strip_ansi;
+        self.collapse_whitespace = config.collapse_whitespace;
+        self.rejoin_wrapped_lines = config.rejoin_wrapped_lines;
+        self.max_entry_bytes = config.max_entry_bytes;
+        self.collapse_incremental = config.collapse_incremental;
+        self.max_total_bytes = config.max_total_bytes;
+        self.oversize_policy = config.oversize_policy;
+        self.min_capture_length = config.min_capture_length;
+        self.min_entropy = config.min_entropy;
+        self.max_entropy = config.max_entropy;
+        self.trash_retention = config
+            .trash_retention_days
+            .map(|days| Duration::from_secs(days.saturating_mul(86_400)));
+        self.expire_after = config
+            .expire_after_days
+            .map(|days| TimeDelta::days(days.into()));
+        // The hashes depend on trim_on_dedup
+        self.text_hashes = None;
+    }
+
+    /// Text entry counts by `dedup_hash`, built from the entries if needed.
+    fn text_hashes(&mut self) -> &mut HashMap<u64, usize> {
+        let trim_end = self.trim_on_dedup;
+        let entries = &self.entries;
+        self.text_hashes.get_or_insert_with(|| {
+            let mut hashes = HashMap::new();
+            for entry in entries.iter().filter(|e| e.image.is_none()) {
+                *hashes
+                    .entry(dedup_hash(&entry.content, trim_end))
+                    .or_insert(0) += 1;
+            }
+            hashes
+        })
+    }
+
+    /// Apply the configured capture cleanups (`strip_ansi`,
+    /// `collapse_whitespace`, `rejoin_wrapped_lines`). `None` if they leave
+    /// nothing but whitespace.
+    fn clean(&self, content: String) -> Option<String> {
+        if !self.strip_ansi && !self.collapse_whitespace && !self.rejoin_wrapped_lines {
+            return Some(content);
+        }
+        let mut content = content;
+        if self.strip_ansi {
+            content = strip_ansi(&content);
+        }
+        if self.collapse_whitespace {
+            content = collapse_whitespace(&content);
+        }
+        if self.rejoin_wrapped_lines {
+            content = rejoin_wrapped_lines(&content);
+        }
+        (!content.trim().is_empty()).then_some(content)
+    }
+
+    /// Apply `oversize_policy` to content over `max_entry_bytes`. `None` if
+    /// it is skipped, otherwise the content and whether it was truncated.
+    fn limit_size(&self, content: String) -> Option<(String, bool)> {
+        let max = match self.max_entry_bytes {
+            Some(max) if content.len() > max => max,
+            _ => return Some((content, false)),
+        };
+        match self.oversize_policy {
+            OversizePolicy::Truncate => {
+                let end = (0..=max)
+                    .rev()
+                    .find(|&i| content.is_char_boundary(i))
+                    .unwrap_or(0);
+                Some((content[..end].to_string(), true))
+            }
+            OversizePolicy::Skip => None,
+            // The placeholder is complete in itself, not a partial copy
+            OversizePolicy::StoreHashOnly => Some((
+                format!(
+                    "[Large copy not stored: {}, hash {:016x}]",
+                    format_size(content.len()),
+                    fnv1a(content.as_bytes())
+                ),
+                false,
+            )),
+        }
+    }
+
+    /// Whether `content` passes the configured capture filters (minimum
+    /// length, entropy band).
+    fn accepts(&self, content: &str) -> bool {
+        if !meets_min_length(content, self.min_capture_length) {
+            return false;
+        }
+        if self.min_entropy.is_none() && self.max_entropy.is_none() {
+            return true;
         }
+        let entropy = shannon_entropy(content);
+        self.min_entropy.is_none_or(|min| entropy >= min)
+            && self.max_entropy.is_none_or(|max| entropy <= max)
     }
 
     /// Add content to history.
+    /// - The configured cleanups (ANSI stripping, whitespace collapsing,
+    ///   rejoining wrapped lines) are applied first, then `oversize_policy`
+    ///   for content over `max_entry_bytes`.
+    /// - If rejected by the capture filters (`min_capture_length`, entropy band), skip.
     /// - If same as the most recent entry, skip.
     /// - If duplicate exists in history, move it to the front and update timestamp.
-    /// - If over max_size, remove the oldest entry.
+    ///   With `trim_on_dedup`, trailing whitespace doesn't count and the
+    ///   existing entry keeps its content.
+    /// - With `collapse_incremental`, if the newest entry is a plain text
+    ///   entry from the last [`INCREMENTAL_WINDOW`] that the content extends
+    ///   (see [`is_incremental`]), replace its content instead.
+    /// - If over max_size, remove the oldest unpinned entry.
+    ///
+    /// With `DedupMode::None` the two duplicate rules are skipped and every
+    /// push creates a new entry.
     pub fn push(&mut self, content: String) -> bool {
-        // Skip if same as most recent
-        if let Some(latest) = self.entries.first() {
-            if latest.content == content {
-                return false;
+        self.push_with_html(content, None)
+    }
+
+    /// Like [`History::push`], also keeping the HTML version of the copy. A
+    /// duplicate of an existing entry takes over the new HTML.
+    pub fn push_with_html(&mut self, content: String, html: Option<String>) -> bool {
+        self.push_text(content, html, self.collapse_incremental)
+    }
+
+    /// [`History::push_with_html`], collapsing into the newest entry only if
+    /// `collapse` is set.
+    fn push_text(&mut self, content: String, html: Option<String>, collapse: bool) -> bool {
+        let Some((content, truncated)) = self.clean(content).and_then(|c| self.limit_size(c))
+        else {
+            return false;
+        };
+        if !self.accepts(&content) {
+            return false;
+        }
+
+        if self.dedup_mode == DedupMode::MoveToFront {
+            let trim = self.trim_on_dedup;
+            // Skip if same as most recent
+            if let Some(latest) = self.entries.first_mut() {
+                if same_text(&latest.content, &content, trim) && latest.image.is_none() {
+                    // Same text with only new formatting isn't a new copy
+                    // unless configured otherwise
+                    if !self.keep_format_only_updates || html.is_none() || latest.html == html {
+                        return false;
+                    }
+                    latest.html = html;
+                    return true;
+                }
+            }
+
+            // Check for duplicate in history, unless the hash rules one out
+            let known = self.text_hashes().contains_key(&dedup_hash(&content, trim));
+            let duplicate =
+                |e: &ClipboardEntry| same_text(&e.content, &content, trim) && e.image.is_none();
+            let pos = if known {
+                self.entries.iter().position(duplicate)
+            } else {
+                None
+            };
+            if let Some(pos) = pos {
+                // Move existing entry to front with updated timestamp
+                let mut entry = self.entries.remove(pos);
+                entry.created_at = Utc::now();
+                if html.is_some() {
+                    entry.html = html;
+                }
+                self.entries.insert(0, entry);
+                return true;
             }
         }
 
-        // Check for duplicate in history
-        if let Some(pos) = self.entries.iter().position(|e| e.content == content) {
-            // Move existing entry to front with updated timestamp
-            let mut entry = self.entries.remove(pos);
-            entry.created_at = Utc::now();
-            self.entries.insert(0, entry);
-            return true;
+        if collapse {
+            let now = Utc::now();
+            let extended = self.entries.first_mut().filter(|latest| {
+                latest.image.is_none()
+                    && !(latest.pinned || latest.locked || latest.template || latest.ephemeral)
+                    && now - latest.created_at <= INCREMENTAL_WINDOW
+                    && is_incremental(&latest.content, &content)
+            });
+            if let Some(latest) = extended {
+                latest.kind = Some(kind::classify(&content));
+                latest.content = content;
+                latest.html = html;
+                latest.truncated = truncated;
+                latest.created_at = now;
+                self.text_hashes = None;
+                self.trim();
+                return true;
+            }
         }
 
-        // New entry
+        self.insert_new(content, html, None, truncated);
+        true
+    }
+
+    /// Add a copied image. Like text, a duplicate (same pixels) moves to the
+    /// front with `DedupMode::MoveToFront`, and nothing changes if it already
+    /// is the newest entry. The text cleanups and capture filters don't apply.
+    pub fn push_image(&mut self, image: ImageContent) -> bool {
+        if self.dedup_mode == DedupMode::MoveToFront {
+            let duplicate = |e: &ClipboardEntry| e.image.as_ref() == Some(&image);
+            if let Some(pos) = self.entries.iter().position(duplicate) {
+                if pos == 0 {
+                    return false;
+                }
+                let mut entry = self.entries.remove(pos);
+                entry.created_at = Utc::now();
+                self.entries.insert(0, entry);
+                return true;
+            }
+        }
+        self.insert_new(image.label(), None, Some(image), false);
+        true
+    }
+
+    fn insert_new(
+        &mut self,
+        content: String,
+        html: Option<String>,
+        image: Option<ImageContent>,
+        truncated: bool,
+    ) {
+        let kind = Some(match image {
+            Some(_) => ContentKind::Text,
+            None => kind::classify(&content),
+        });
         let entry = ClipboardEntry {
             id: self.next_id,
             content,
             created_at: Utc::now(),
+            ephemeral: false,
+            locked: false,
+            pinned: false,
+            template: false,
+            truncated,
+            use_count: 0,
+            html,
+            image,
+            kind,
         };
         self.next_id += 1;
+        if let Some(hashes) = self.text_hashes.as_mut().filter(|_| entry.image.is_none()) {
+            *hashes
+                .entry(dedup_hash(&entry.content, self.trim_on_dedup))
+                .or_insert(0) += 1;
+        }
         self.entries.insert(0, entry);
+        self.trim();
+    }
 
-        // Trim if over max size
-        if self.entries.len() > self.max_size {
-            self.entries.truncate(self.max_size);
+    /// Drop the oldest unpinned entries beyond `max_size` or
+    /// `max_total_bytes`. Pinned entries don't count towards the limits, and
+    /// the newest entry is kept even if it alone is over the byte budget.
+    fn trim(&mut self) {
+        let mut unpinned = 0;
+        let mut bytes = 0;
+        let mut dropped = Vec::new();
+        self.entries.retain(|e| {
+            if !e.pinned {
+                unpinned += 1;
+                bytes += e.size_bytes();
+            }
+            let within_budget =
+                unpinned == 1 || self.max_total_bytes.is_none_or(|max| bytes <= max);
+            let keep = e.pinned || (unpinned <= self.max_size && within_budget);
+            if !keep && e.image.is_none() {
+                dropped.push(dedup_hash(&e.content, self.trim_on_dedup));
+            }
+            keep
+        });
+        if let Some(hashes) = &mut self.text_hashes {
+            for hash in dropped {
+                if let Some(count) = hashes.get_mut(&hash) {
+                    *count -= 1;
+                    if *count == 0 {
+                        hashes.remove(&hash);
+                    }
+                }
+            }
         }
-
-        true
     }
 
     pub fn entries(&self) -> &[ClipboardEntry] {
         &self.entries
     }
 
-    #[allow(dead_code)]
+    /// Bytes held by all entries, see `ClipboardEntry::size_bytes`.
+    pub fn total_bytes(&self) -> usize {
+        self.entries.iter().map(ClipboardEntry::size_bytes).sum()
+    }
+
+    /// Delete an ephemeral entry after it has been pasted. The monitor skips
+    /// the paste itself (see `clipboard::set_text`), so it doesn't bring the
+    /// entry straight back. Locked entries are kept.
+    pub fn consume_ephemeral(&mut self, id: u64) -> bool {
+        let Some(pos) = self
+            .entries
+            .iter()
+            .position(|e| e.id == id && e.ephemeral && !e.locked)
+        else {
+            return false;
+        };
+        self.entries.remove(pos);
+        self.text_hashes = None;
+        true
+    }
+
+    /// Record that the entry was pasted. Returns whether it was found.
+    pub fn mark_used(&mut self, id: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.use_count = entry.use_count.saturating_add(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove stale entries: those created more than `older_than` ago and,
+    /// with `require_unused`, never pasted. Locked and pinned entries are
+    /// always kept.
+    /// Returns the number of entries removed.
+    pub fn compact(&mut self, older_than: Duration, require_unused: bool) -> usize {
+        // Too long ago to be a date: nothing is that old
+        let Some(cutoff) = TimeDelta::from_std(older_than)
+            .ok()
+            .and_then(|age| Utc::now().checked_sub_signed(age))
+        else {
+            return 0;
+        };
+        let (kept, removed) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|e| {
+                e.locked
+                    || e.pinned
+                    || e.created_at >= cutoff
+                    || (require_unused && e.use_count > 0)
+            });
+        self.entries = kept;
+        self.text_hashes = None;
+        self.discard(removed)
+    }
+
+    /// Delete entries created more than `Config::expire_after_days` before
+    /// `now`, pinned and locked ones excepted. They skip the trash, and
+    /// trashed entries that old are purged too, so nothing expired lingers.
+    /// Returns the number of entries deleted.
+    pub fn prune_expired(&mut self, now: DateTime<Utc>) -> usize {
+        let Some(cutoff) = self
+            .expire_after
+            .and_then(|age| now.checked_sub_signed(age))
+        else {
+            return 0;
+        };
+        let before = self.entries.len() + self.trash.len();
+        self.entries
+            .retain(|e| e.pinned || e.locked || e.created_at >= cutoff);
+        self.text_hashes = None;
+        self.trash.retain(|t| t.entry.created_at >= cutoff);
+        self.undo.retain(|(_, e)| e.created_at >= cutoff);
+        before - self.entries.len() - self.trash.len()
+    }
+
+    /// Move removed entries to the trash, or drop them if the trash is
+    /// disabled. Returns how many there were.
+    fn discard(&mut self, removed: Vec<ClipboardEntry>) -> usize {
+        let count = removed.len();
+        if self.trash_retention.is_some() {
+            let deleted_at = Utc::now();
+            self.trash.splice(
+                0..0,
+                removed
+                    .into_iter()
+                    .map(|entry| TrashedEntry { entry, deleted_at }),
+            );
+        }
+        count
+    }
+
+    /// Trashed entries, most recently deleted first.
+    pub fn trash(&self) -> &[TrashedEntry] {
+        &self.trash
+    }
+
+    /// Permanently delete trashed entries older than the retention period
+    /// (all of them if the trash is disabled). Returns how many were purged.
+    pub fn purge_trash(&mut self) -> usize {
+        let before = self.trash.len();
+        match self.trash_retention {
+            Some(retention) => {
+                // A retention too long to be a date keeps everything
+                let cutoff = TimeDelta::from_std(retention)
+                    .ok()
+                    .and_then(|age| Utc::now().checked_sub_signed(age));
+                if let Some(cutoff) = cutoff {
+                    self.trash.retain(|t| t.deleted_at >= cutoff);
+                }
+            }
+            None => self.trash.clear(),
+        }
+        before - self.trash.len()
+    }
+
+    /// Move a trashed entry back to the top of the history, keeping its id
+    /// and flags (see `reinsert` for a copy taken since). Returns whether it
+    /// was in the trash.
+    pub fn restore_from_trash(&mut self, id: u64) -> bool {
+        let Some(pos) = self.trash.iter().position(|t| t.entry.id == id) else {
+            return false;
+        };
+        let entry = self.trash.remove(pos).entry;
+        self.undo.retain(|(_, e)| e.id != id);
+        self.reinsert(0, entry);
+        true
+    }
+
+    /// Move the entry to the front with an updated timestamp, as a new copy
+    /// of it would. Returns whether it was found.
+    pub fn move_to_front(&mut self, id: u64) -> bool {
+        let Some(pos) = self.entries.iter().position(|e| e.id == id) else {
+            return false;
+        };
+        let mut entry = self.entries.remove(pos);
+        entry.created_at = Utc::now();
+        self.entries.insert(0, entry);
+        true
+    }
+
+    /// Flip the ephemeral flag on the entry with the given id.
+    /// Returns whether the entry was found.
+    pub fn toggle_ephemeral(&mut self, id: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.ephemeral = !entry.ephemeral;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Flip the lock on the entry with the given id.
+    /// Returns whether the entry was found.
+    pub fn toggle_lock(&mut self, id: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.locked = !entry.locked;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Flip the pin on the entry with the given id.
+    /// Returns whether the entry was found.
+    pub fn toggle_pin(&mut self, id: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.pinned = !entry.pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Flip the template flag on the entry with the given id.
+    /// Returns whether the entry was found.
+    pub fn toggle_template(&mut self, id: u64) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.template = !entry.template;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove every entry except locked ones. Returns how many locked
+    /// entries were kept. Ids aren't reused afterwards.
+    pub fn clear(&mut self) -> usize {
+        let removed = self.take_entries(|e| !e.locked);
+        self.discard(removed);
+        self.entries.len()
+    }
+
+    /// Remove the entries with the given ids, skipping locked ones. Returns
+    /// how many were removed and how many were kept for being locked.
+    pub fn remove_all(&mut self, ids: &[u64]) -> Removal {
+        // Deleting "all filtered" can pass thousands of ids
+        let ids: HashSet<u64> = ids.iter().copied().collect();
+        let locked = self
+            .entries
+            .iter()
+            .filter(|e| e.locked && ids.contains(&e.id))
+            .count();
+        let removed = self.take_entries(|e| ids.contains(&e.id) && !e.locked);
+        Removal {
+            removed: self.discard(removed),
+            locked,
+        }
+    }
+
+    /// Take the entries matching `remove` out of the list, remembering the
+    /// topmost `UNDO_LIMIT` of them for `undo_remove`.
+    fn take_entries(&mut self, remove: impl Fn(&ClipboardEntry) -> bool) -> Vec<ClipboardEntry> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.entries.len());
+        for (index, entry) in std::mem::take(&mut self.entries).into_iter().enumerate() {
+            if remove(&entry) {
+                removed.push((index, entry));
+            } else {
+                kept.push(entry);
+            }
+        }
+        self.entries = kept;
+        self.text_hashes = None;
+        // Pushed bottom-up so undoing goes top-down and every entry lands at
+        // its old position
+        for (index, entry) in removed.iter().take(UNDO_LIMIT).rev() {
+            self.undo.push_back((*index, entry.clone()));
+        }
+        while self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        removed.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Bring back the most recently deleted entry (of the last
+    /// `UNDO_LIMIT`) at about its old position, keeping its id and flags
+    /// (see `reinsert` for a copy taken since). Returns whether there was one.
+    pub fn undo_remove(&mut self) -> bool {
+        let Some((index, entry)) = self.undo.pop_back() else {
+            return false;
+        };
+        self.trash.retain(|t| t.entry.id != entry.id);
+        self.reinsert(index, entry);
+        true
+    }
+
+    /// Put a removed `entry` back at `index`. With `DedupMode::MoveToFront`
+    /// a live copy of the same content (copied again since the removal) is
+    /// merged into it instead of dropped: pins, locks and the template flag
+    /// carry over, and the newer id and timestamp are kept.
+    fn reinsert(&mut self, index: usize, mut entry: ClipboardEntry) {
+        let duplicate = self
+            .entries
+            .iter()
+            .position(|e| e.content == entry.content && e.image == entry.image)
+            .filter(|_| self.dedup_mode == DedupMode::MoveToFront);
+        if let Some(pos) = duplicate {
+            let live = self.entries.remove(pos);
+            entry.pinned |= live.pinned;
+            entry.locked |= live.locked;
+            entry.template |= live.template;
+            entry.ephemeral &= live.ephemeral;
+            entry.use_count = entry.use_count.max(live.use_count);
+            entry.id = entry.id.max(live.id);
+            entry.created_at = entry.created_at.max(live.created_at);
+            entry.html = entry.html.or(live.html);
+        }
+        self.text_hashes = None;
+        let index = index.min(self.entries.len());
+        self.entries.insert(index, entry);
+        self.trim();
+    }
+
+    /// Remove the entry with the given id unless it is locked.
+    pub fn remove_by_id(&mut self, id: u64) -> Removal {
+        self.remove_all(&[id])
+    }
+
+    /// Replace a multi-line entry with one entry per non-empty line.
+    /// The lines end up in the same top-to-bottom order as in the original,
+    /// with the first line newest; lines already in the history are moved up
+    /// according to the dedup mode, and lines the capture filters reject are
+    /// skipped. Lines never collapse into each other as incremental copies.
+    /// Returns the number of lines added or moved up; the original is only
+    /// removed if that is at least one. Nothing changes if the entry wasn't
+    /// found or has fewer than two lines. A locked original is always kept.
+    pub fn split_entry(&mut self, id: u64) -> usize {
+        let Some(pos) = self.entries.iter().position(|e| e.id == id) else {
+            return 0;
+        };
+        let lines: Vec<String> = self.entries[pos]
+            .content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        if lines.len() < 2 {
+            return 0;
+        }
+
+        let locked = self.entries[pos].locked;
+        // The original has at least two lines, so no line is a duplicate of it
+        let count = lines
+            .into_iter()
+            .rev()
+            .filter(|line| self.push_text(line.clone(), None, false))
+            .count();
+        if count > 0 && !locked {
+            if let Some(pos) = self.entries.iter().position(|e| e.id == id) {
+                self.entries.remove(pos);
+                self.text_hashes = None;
+            }
+        }
+        count
+    }
+
     pub fn get_by_id(&self, id: u64) -> Option<&ClipboardEntry> {
         self.entries.iter().find(|e| e.id == id)
     }
+
+    /// Move `next_id` past every id in use, including the trash's. Histories
+    /// saved before `next_id` was persisted load with 0, which would hand
+    /// out ids that collide with existing entries.
+    pub fn repair_next_id(&mut self) {
+        let max_id = self
+            .entries
+            .iter()
+            .chain(self.trash.iter().map(|t| &t.entry))
+            .map(|e| e.id)
+            .max()
+            .unwrap_or(0);
+        self.next_id = self.next_id.max(max_id + 1);
+    }
+
+    /// Detect the kind of entries saved before kinds were stored, so old
+    /// URLs and code show up under their filter chips.
+    pub fn classify_missing_kinds(&mut self) {
+        let entries = self.entries.iter_mut();
+        for entry in entries.chain(self.trash.iter_mut().map(|t| &mut t.entry)) {
+            if entry.kind.is_none() {
+                entry.kind = Some(match entry.image {
+                    Some(_) => ContentKind::Text,
+                    None => kind::classify(&entry.content),
+                });
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -108,6 +937,46 @@ mod tests {
         assert_eq!(history.entries()[2].content, "second");
     }
 
+    #[test]
+    fn test_push_keeps_html() {
+        let mut history = History::new(100);
+        history.push_with_html("bold".into(), Some("<b>bold</b>".into()));
+        history.push("plain".into());
+        assert_eq!(history.entries()[0].html, None);
+        assert_eq!(history.entries()[1].html.as_deref(), Some("<b>bold</b>"));
+
+        // A duplicate copied without HTML keeps the stored version
+        history.push("bold".into());
+        assert_eq!(history.entries()[0].html.as_deref(), Some("<b>bold</b>"));
+    }
+
+    #[test]
+    fn test_format_only_update_ignored_by_default() {
+        let mut history = History::new(100);
+        history.push_with_html("text".into(), Some("<b>text</b>".into()));
+        assert!(!history.push_with_html("text".into(), Some("<i>text</i>".into())));
+        assert!(!history.push_with_html("text".into(), None));
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.entries()[0].html.as_deref(), Some("<b>text</b>"));
+    }
+
+    #[test]
+    fn test_format_only_update_kept_when_configured() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            keep_format_only_updates: true,
+            ..Config::default()
+        });
+        history.push_with_html("text".into(), Some("<b>text</b>".into()));
+        // Re-copying the latest entry with new HTML updates it in place
+        assert!(history.push_with_html("text".into(), Some("<i>text</i>".into())));
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.entries()[0].html.as_deref(), Some("<i>text</i>"));
+        // Unchanged formatting, or none at all, is still not new
+        assert!(!history.push_with_html("text".into(), Some("<i>text</i>".into())));
+        assert!(!history.push_with_html("text".into(), None));
+    }
+
     #[test]
     fn test_max_size_enforced() {
         let mut history = History::new(3);
@@ -123,6 +992,97 @@ mod tests {
         assert_eq!(history.entries()[2].content, "b");
     }
 
+    #[test]
+    fn test_pinned_entries_survive_trimming() {
+        let mut history = History::new(3);
+        history.push("address".into());
+        history.push("git command".into());
+        let address = history.entries()[1].id;
+        let git = history.entries()[0].id;
+        assert!(history.toggle_pin(address));
+        assert!(history.toggle_pin(git));
+
+        for i in 0..50 {
+            history.push(format!("copy {i}"));
+        }
+        // Pinned entries don't count towards max_size
+        assert_eq!(
+            contents(&history),
+            vec!["copy 49", "copy 48", "copy 47", "git command", "address"]
+        );
+
+        assert!(history.toggle_pin(git));
+        history.push("copy 50".into());
+        assert_eq!(
+            contents(&history),
+            vec!["copy 50", "copy 49", "copy 48", "address"]
+        );
+        assert!(!history.toggle_pin(9999));
+    }
+
+    #[test]
+    fn test_byte_budget_evicts_oldest() {
+        let mut history = History::new(100);
+        history.max_total_bytes = Some(250);
+        for i in 0..4 {
+            history.push(format!("{i}").repeat(100));
+        }
+        // Far below max_size, but only two 100-byte entries fit in 250 bytes
+        let firsts: Vec<char> = history
+            .entries()
+            .iter()
+            .map(|e| e.content.chars().next().unwrap())
+            .collect();
+        assert_eq!(firsts, vec!['3', '2']);
+        assert_eq!(history.total_bytes(), 200);
+
+        // Small entries fill the rest of the budget
+        history.push("x".repeat(50));
+        assert_eq!(history.entries().len(), 3);
+        history.push("y".into());
+        assert_eq!(history.total_bytes(), 151);
+
+        // Pinned entries don't count; an entry over the budget alone is kept
+        let pinned = history.entries()[2].id;
+        assert!(history.toggle_pin(pinned));
+        history.push("z".repeat(400));
+        assert_eq!(contents(&history)[0], "z".repeat(400));
+        assert_eq!(history.entries().len(), 2);
+        assert!(history.entries()[1].pinned);
+    }
+
+    fn image(shade: u8) -> ImageContent {
+        ImageContent::new(2, 1, vec![shade; 8])
+    }
+
+    #[test]
+    fn test_push_image_dedupes_by_pixels() {
+        let mut history = History::new(100);
+        assert!(history.push_image(image(1)));
+        assert!(!history.push_image(image(1)));
+        // Same size, different pixels: a separate entry with the same label
+        assert!(history.push_image(image(2)));
+        assert_eq!(contents(&history), vec!["Image 2×1", "Image 2×1"]);
+
+        // Text matching the label isn't a duplicate of the image either way
+        assert!(history.push("Image 2×1".into()));
+        assert!(history.push_image(image(1)));
+        assert_eq!(history.entries().len(), 3);
+        assert_eq!(history.entries()[0].image, Some(image(1)));
+        assert_eq!(history.entries()[1].image, None);
+    }
+
+    #[test]
+    fn test_image_entries_roundtrip_json() {
+        let mut history = History::new(100);
+        history.push("text".into());
+        history.push_image(image(7));
+        let json = serde_json::to_string(&history).unwrap();
+        let loaded: History = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.entries()[0].image, Some(image(7)));
+        assert_eq!(loaded.entries()[1].image, None);
+    }
+
     #[test]
     fn test_get_by_id() {
         let mut history = History::new(100);
@@ -142,4 +1102,934 @@ mod tests {
         assert_eq!(ids.len(), 2);
         assert_ne!(ids[0], ids[1]);
     }
+
+    #[test]
+    fn test_toggle_ephemeral() {
+        let mut history = History::new(100);
+        history.push("secret".into());
+        let id = history.entries()[0].id;
+        assert!(!history.entries()[0].ephemeral);
+
+        assert!(history.toggle_ephemeral(id));
+        assert!(history.entries()[0].ephemeral);
+        assert!(history.toggle_ephemeral(id));
+        assert!(!history.entries()[0].ephemeral);
+        assert!(!history.toggle_ephemeral(9999));
+    }
+
+    #[test]
+    fn test_consume_ephemeral() {
+        let mut history = History::new(100);
+        history.push("secret".into());
+        history.push("other".into());
+        let id = history.entries()[1].id;
+
+        // Only ephemeral entries are consumed
+        assert!(!history.consume_ephemeral(id));
+        history.toggle_ephemeral(id);
+        assert!(history.consume_ephemeral(id));
+        assert_eq!(history.entries().len(), 1);
+
+        // A later copy is a new, ordinary entry
+        assert!(history.push("secret".into()));
+        assert!(!history.entries()[0].ephemeral);
+    }
+
+    #[test]
+    fn test_move_to_front() {
+        let mut history = History::new(100);
+        history.push("a".into());
+        history.push("b".into());
+        let id = history.entries()[1].id;
+        let before = history.entries()[1].created_at;
+        assert!(history.move_to_front(id));
+        assert_eq!(history.entries()[0].content, "a");
+        assert!(history.entries()[0].created_at >= before);
+        assert_eq!(history.entries().len(), 2);
+        assert!(!history.move_to_front(9999));
+    }
+
+    #[test]
+    fn test_ephemeral_defaults_false_for_old_json() {
+        let json = r#"{"id":1,"content":"x","created_at":"2024-01-01T00:00:00Z"}"#;
+        let entry: ClipboardEntry = serde_json::from_str(json).unwrap();
+        assert!(!entry.ephemeral);
+        assert_eq!(entry.kind, None);
+    }
+
+    #[test]
+    fn test_missing_kinds_classified_on_load() {
+        let json = r#"{"entries":[
+            {"id":1,"content":"https://example.com","created_at":"2024-01-01T00:00:00Z"},
+            {"id":2,"content":"hello","created_at":"2024-01-01T00:00:00Z","kind":"Code"}
+        ],"max_size":10}"#;
+        let mut history: History = serde_json::from_str(json).unwrap();
+        history.classify_missing_kinds();
+        let kinds: Vec<_> = history.entries().iter().map(|e| e.kind).collect();
+        // A stored kind is kept as is
+        assert_eq!(kinds, vec![Some(ContentKind::Url), Some(ContentKind::Code)]);
+    }
+
+    #[test]
+    fn test_kind_detected_on_push() {
+        let mut history = History::new(10);
+        history.push("https://example.com".into());
+        history.push("let x = 1;".into());
+        let kinds: Vec<_> = history.entries().iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![Some(ContentKind::Code), Some(ContentKind::Url)]);
+    }
+
+    #[test]
+    fn test_dedup_none_keeps_repeats() {
+        let mut history = History::new(100);
+        history.dedup_mode = DedupMode::None;
+        assert!(history.push("same".into()));
+        assert!(history.push("same".into()));
+        assert!(history.push("same".into()));
+
+        assert_eq!(history.entries().len(), 3);
+        assert!(history.entries().iter().all(|e| e.content == "same"));
+        let mut ids: Vec<u64> = history.entries().iter().map(|e| e.id).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn test_dedup_none_does_not_move_past_duplicate() {
+        let mut history = History::new(100);
+        history.dedup_mode = DedupMode::None;
+        history.push("first".into());
+        history.push("second".into());
+        history.push("first".into());
+
+        let contents: Vec<&str> = history
+            .entries()
+            .iter()
+            .map(|e| e.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["first", "second", "first"]);
+    }
+
+    #[test]
+    fn test_dedup_none_respects_max_size() {
+        let mut history = History::new(2);
+        history.dedup_mode = DedupMode::None;
+        for _ in 0..5 {
+            history.push("same".into());
+        }
+        assert_eq!(history.entries().len(), 2);
+    }
+
+    fn contents(history: &History) -> Vec<&str> {
+        history
+            .entries()
+            .iter()
+            .map(|e| e.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_entry_preserves_line_order() {
+        let mut history = History::new(100);
+        history.push("older".into());
+        history.push("one\r\ntwo\n\n  \nthree\n".into());
+        let id = history.entries()[0].id;
+
+        assert_eq!(history.split_entry(id), 3);
+        assert_eq!(contents(&history), vec!["one", "two", "three", "older"]);
+        assert!(history.get_by_id(id).is_none());
+    }
+
+    #[test]
+    fn test_split_entry_dedups_existing_lines() {
+        let mut history = History::new(100);
+        history.push("two".into());
+        history.push("other".into());
+        history.push("one\ntwo\none".into());
+        let id = history.entries()[0].id;
+
+        history.split_entry(id);
+        assert_eq!(contents(&history), vec!["one", "two", "other"]);
+    }
+
+    #[test]
+    fn test_split_entry_lines_dont_collapse() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            collapse_incremental: true,
+            ..Config::default()
+        });
+        // Pushed bottom-up, "foo bar" would extend "foo"
+        history.push("foo bar\nfoo".into());
+        let id = history.entries()[0].id;
+
+        assert_eq!(history.split_entry(id), 2);
+        assert_eq!(contents(&history), vec!["foo bar", "foo"]);
+    }
+
+    #[test]
+    fn test_split_entry_counts_only_accepted_lines() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            min_capture_length: 3,
+            ..Config::default()
+        });
+        history.push("a\nlong line\nb".into());
+        let id = history.entries()[0].id;
+        assert_eq!(history.split_entry(id), 1);
+        assert_eq!(contents(&history), vec!["long line"]);
+
+        // Nothing lands, so the original stays
+        history.push("x\ny\nz".into());
+        let id = history.entries()[0].id;
+        assert_eq!(history.split_entry(id), 0);
+        assert_eq!(contents(&history), vec!["x\ny\nz", "long line"]);
+    }
+
+    #[test]
+    fn test_split_single_line_is_noop() {
+        let mut history = History::new(100);
+        history.push("just one\n\n".into());
+        let id = history.entries()[0].id;
+
+        assert_eq!(history.split_entry(id), 0);
+        assert_eq!(history.split_entry(9999), 0);
+        assert_eq!(contents(&history), vec!["just one\n\n"]);
+    }
+
+    #[test]
+    fn test_min_capture_length_skips_short_content() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            min_capture_length: 2,
+            ..Config::default()
+        });
+        assert!(!history.push("x".into()));
+        assert!(!history.push(" y\n".into()));
+        assert!(history.push("ok".into()));
+        assert_eq!(contents(&history), vec!["ok"]);
+    }
+
+    #[test]
+    fn test_strip_ansi_on_capture() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            strip_ansi: true,
+            ..Config::default()
+        });
+        assert!(history.push("\x1b[32mok\x1b[0m".into()));
+        // The raw and cleaned copies are the same entry
+        assert!(!history.push("ok".into()));
+        // Nothing left after stripping
+        assert!(!history.push("\x1b[0m".into()));
+        assert_eq!(contents(&history), vec!["ok"]);
+    }
+
+    #[test]
+    fn test_whitespace_cleanup_on_capture() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            collapse_whitespace: true,
+            rejoin_wrapped_lines: true,
+            ..Config::default()
+        });
+        assert!(history.push("Copied  from\na   PDF ".into()));
+        assert!(!history.push(" \t\n".into()));
+        assert_eq!(contents(&history), vec!["Copied from a PDF"]);
+    }
+
+    #[test]
+    fn test_remove_all_skips_locked() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            trash_retention_days: Some(7),
+            ..Config::default()
+        });
+        for content in ["a", "b", "c", "d"] {
+            history.push(content.into());
+        }
+        let id = |content: &str| {
+            history
+                .entries()
+                .iter()
+                .find(|e| e.content == content)
+                .unwrap()
+                .id
+        };
+        let (a, b, c) = (id("a"), id("b"), id("c"));
+        history.toggle_lock(b);
+
+        assert_eq!(
+            history.remove_all(&[a, b, c, 9999]),
+            Removal {
+                removed: 2,
+                locked: 1
+            }
+        );
+        assert_eq!(contents(&history), vec!["d", "b"]);
+        assert_eq!(history.trash().len(), 2);
+        assert_eq!(history.remove_all(&[]), Removal::default());
+    }
+
+    #[test]
+    fn test_undo_remove_in_lifo_order() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            trash_retention_days: Some(7),
+            ..Config::default()
+        });
+        for content in ["e", "d", "c", "b", "a"] {
+            history.push(content.into());
+        }
+        let id = |history: &History, content: &str| {
+            history
+                .entries()
+                .iter()
+                .find(|e| e.content == content)
+                .unwrap()
+                .id
+        };
+        let (b, d) = (id(&history, "b"), id(&history, "d"));
+        assert_eq!(history.remove_by_id(d).removed, 1);
+        assert_eq!(history.remove_by_id(b).removed, 1);
+        assert_eq!(
+            history
+                .remove_all(&[id(&history, "a"), id(&history, "e")])
+                .removed,
+            2
+        );
+        assert_eq!(contents(&history), vec!["c"]);
+
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["a", "c"]);
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["a", "c", "e"]);
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["a", "b", "c", "e"]);
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(history.entries()[3].id, d);
+        assert!(!history.undo_remove());
+        assert!(history.trash().is_empty());
+    }
+
+    #[test]
+    fn test_undo_remove_merges_into_a_locked_duplicate() {
+        let mut history = History::new(100);
+        history.push("keep".into());
+        history.push("x".into());
+        let old = history.entries()[0].id;
+        history.remove_by_id(old);
+        // Copied again and locked before the undo
+        history.push("x".into());
+        let new = history.entries()[0].id;
+        assert!(history.toggle_lock(new));
+
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["x", "keep"]);
+        let entry = &history.entries()[0];
+        assert!(entry.locked);
+        assert_eq!(entry.id, new);
+    }
+
+    #[test]
+    fn test_undo_remove_is_bounded_and_not_persisted() {
+        let mut history = History::new(100);
+        for i in 0..15 {
+            history.push(format!("entry {i}"));
+        }
+        history.clear();
+        let json = serde_json::to_string(&history).unwrap();
+        let mut loaded: History = serde_json::from_str(&json).unwrap();
+        assert!(!loaded.undo_remove());
+
+        let mut undone = 0;
+        while history.undo_remove() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_LIMIT);
+        // The newest entries come back
+        assert_eq!(history.entries()[0].content, "entry 14");
+        assert_eq!(history.entries().len(), UNDO_LIMIT);
+    }
+
+    /// The maintained hash counts equal ones rebuilt from the entries.
+    fn assert_hashes_consistent(history: &mut History) {
+        if let Some(hashes) = history.text_hashes.take() {
+            assert_eq!(*history.text_hashes(), hashes);
+        }
+    }
+
+    #[test]
+    fn test_hash_dedup_matches_linear_model() {
+        for trim in [false, true] {
+            let mut history = History::new(8);
+            history.apply_config(&Config {
+                trim_on_dedup: trim,
+                ..Config::default()
+            });
+            // Move-to-front semantics by linear search, newest first
+            let mut model: Vec<String> = Vec::new();
+            let same = |a: &str, b: &str| same_text(a, b, trim);
+            let mut seed: u64 = 7;
+            let mut next = move |n: u64| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 33) % n
+            };
+            for _ in 0..2000 {
+                match next(10) {
+                    0..=6 => {
+                        let content = match next(3) {
+                            0 => format!("text {}", next(12)),
+                            1 => format!("text {}\n", next(12)),
+                            _ => format!("line {}\nline {}", next(4), next(4)),
+                        };
+                        let pushed = history.push(content.clone());
+                        let expected = match model.iter().position(|m| same(m, &content)) {
+                            Some(0) => false,
+                            Some(pos) => {
+                                let existing = model.remove(pos);
+                                model.insert(0, existing);
+                                true
+                            }
+                            None => {
+                                model.insert(0, content);
+                                model.truncate(8);
+                                true
+                            }
+                        };
+                        assert_eq!(pushed, expected);
+                    }
+                    7 | 8 if !model.is_empty() => {
+                        let pos = next(model.len() as u64) as usize;
+                        let id = history.entries()[pos].id;
+                        assert_eq!(history.remove_by_id(id).removed, 1);
+                        model.remove(pos);
+                    }
+                    _ => {
+                        if history.undo_remove() {
+                            // Bring the model in line; undo is tested on its own
+                            model = contents(&history).into_iter().map(String::from).collect();
+                        }
+                    }
+                }
+                assert_eq!(contents(&history), model);
+                assert_hashes_consistent(&mut history);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_index_survives_other_changes() {
+        let mut history = History::new(10);
+        for content in ["a", "b\nc", "d", "a", "e", "f", "g"] {
+            history.push(content.into());
+            assert_hashes_consistent(&mut history);
+        }
+        let multi = history.entries().iter().find(|e| e.content == "b\nc");
+        assert_eq!(history.split_entry(multi.unwrap().id), 2);
+        assert_hashes_consistent(&mut history);
+        assert!(!history.push("b".into()));
+        assert!(history.push("b\nc".into()));
+        history.clear();
+        assert!(history.push("a".into()));
+        assert!(!history.push("a".into()));
+        assert_hashes_consistent(&mut history);
+        assert!(history.undo_remove());
+        assert_eq!(contents(&history), vec!["b\nc", "a"]);
+        assert!(history.push("g".into()));
+        assert_eq!(contents(&history)[0], "g");
+        assert_eq!(contents(&history).iter().filter(|c| **c == "g").count(), 1);
+        assert_hashes_consistent(&mut history);
+    }
+
+    #[test]
+    fn test_remove_by_id() {
+        let mut history = History::new(100);
+        for content in ["a", "b", "c"] {
+            history.push(content.into());
+        }
+        let id = |history: &History, content: &str| {
+            history
+                .entries()
+                .iter()
+                .find(|e| e.content == content)
+                .unwrap()
+                .id
+        };
+        let b = id(&history, "b");
+        assert_eq!(history.remove_by_id(b).removed, 1);
+        assert_eq!(history.remove_by_id(b), Removal::default());
+        assert_eq!(contents(&history), vec!["c", "a"]);
+
+        let a = id(&history, "a");
+        history.toggle_lock(a);
+        assert_eq!(
+            history.remove_by_id(a),
+            Removal {
+                removed: 0,
+                locked: 1
+            }
+        );
+        assert_eq!(contents(&history), vec!["c", "a"]);
+    }
+
+    fn history_with_limit(policy: OversizePolicy) -> History {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            max_entry_bytes: Some(4),
+            oversize_policy: policy,
+            ..Config::default()
+        });
+        history
+    }
+
+    #[test]
+    fn test_oversize_truncate() {
+        let mut history = history_with_limit(OversizePolicy::Truncate);
+        assert!(history.push("ok".into()));
+        // Cut at a character boundary: "é" is two bytes
+        assert!(history.push("abcéé".into()));
+        assert_eq!(contents(&history), vec!["abc", "ok"]);
+    }
+
+    #[test]
+    fn test_oversize_limit_boundary() {
+        let mut history = history_with_limit(OversizePolicy::Truncate);
+        // Exactly max_entry_bytes is kept whole
+        assert!(history.push("abcd".into()));
+        assert!(!history.entries()[0].truncated);
+        // One byte over is cut back to the limit and marked
+        assert!(history.push("efghi".into()));
+        assert_eq!(history.entries()[0].content, "efgh");
+        assert!(history.entries()[0].truncated);
+
+        let mut history = history_with_limit(OversizePolicy::Skip);
+        assert!(history.push("abcd".into()));
+        assert!(!history.push("efghi".into()));
+        assert_eq!(contents(&history), vec!["abcd"]);
+    }
+
+    #[test]
+    fn test_oversize_skip() {
+        let mut history = history_with_limit(OversizePolicy::Skip);
+        assert!(!history.push("too long".into()));
+        assert!(history.push("fits".into()));
+        assert_eq!(contents(&history), vec!["fits"]);
+    }
+
+    #[test]
+    fn test_oversize_store_hash_only() {
+        let mut history = history_with_limit(OversizePolicy::StoreHashOnly);
+        assert!(history.push("too long".into()));
+        let placeholder = history.entries()[0].content.clone();
+        assert!(placeholder.starts_with("[Large copy not stored: 8 B, hash "));
+        // The same content again is a duplicate of its placeholder
+        assert!(!history.push("too long".into()));
+        assert!(history.push("also too long".into()));
+        assert_ne!(history.entries()[0].content, placeholder);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1_048_576), "3.0 MB");
+    }
+
+    #[test]
+    fn test_meets_min_length() {
+        assert!(meets_min_length("", 0));
+        assert!(!meets_min_length("  a  ", 2));
+        assert!(meets_min_length("äö", 2));
+    }
+
+    #[test]
+    fn test_is_symbol() {
+        let entry = |content: &str| ClipboardEntry {
+            id: 1,
+            content: content.into(),
+            created_at: Utc::now(),
+            ephemeral: false,
+            locked: false,
+            pinned: false,
+            template: false,
+            truncated: false,
+            use_count: 0,
+            html: None,
+            image: None,
+            kind: Some(ContentKind::Text),
+        };
+        assert!(entry("🎉").is_symbol());
+        assert!(entry(" → \n").is_symbol());
+        assert!(entry("👨‍👩‍👧").is_symbol());
+        assert!(entry("©®").is_symbol());
+        assert!(!entry(".").is_symbol());
+        assert!(!entry("é").is_symbol());
+        assert!(!entry("🎉 party").is_symbol());
+        assert!(!entry("🎉 🎉").is_symbol());
+        assert!(!entry("").is_symbol());
+    }
+
+    #[test]
+    fn test_toggle_lock() {
+        let mut history = History::new(100);
+        history.push("keep me".into());
+        let id = history.entries()[0].id;
+
+        assert!(history.toggle_lock(id));
+        assert!(history.entries()[0].locked);
+        assert!(history.toggle_lock(id));
+        assert!(!history.entries()[0].locked);
+        assert!(!history.toggle_lock(9999));
+    }
+
+    #[test]
+    fn test_clear_skips_locked() {
+        let mut history = History::new(100);
+        history.push("a".into());
+        history.push("b".into());
+        history.push("c".into());
+        let id = history.entries()[1].id;
+        history.toggle_lock(id);
+
+        assert_eq!(history.clear(), 1);
+        assert_eq!(contents(&history), vec!["b"]);
+    }
+
+    #[test]
+    fn test_trim_on_dedup_collapses_trailing_whitespace() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            trim_on_dedup: true,
+            ..Config::default()
+        });
+        history.push("foo".into());
+        assert!(!history.push("foo\n".into()));
+        history.push("bar".into());
+        assert!(history.push("foo  \r\n".into()));
+        // The first form copied is kept, moved to the front
+        assert_eq!(contents(&history), vec!["foo", "bar"]);
+        // Leading whitespace still makes a different entry
+        history.push("  foo".into());
+        assert_eq!(contents(&history), vec!["  foo", "foo", "bar"]);
+    }
+
+    #[test]
+    fn test_trailing_whitespace_is_distinct_by_default() {
+        let mut history = History::new(100);
+        history.push("foo".into());
+        assert!(history.push("foo\n".into()));
+        assert_eq!(contents(&history), vec!["foo\n", "foo"]);
+    }
+
+    #[test]
+    fn test_clear_keeps_ids_increasing() {
+        let mut history = History::new(100);
+        history.push("a".into());
+        history.push("b".into());
+        let last_id = history.entries()[0].id;
+
+        assert_eq!(history.clear(), 0);
+        assert!(history.entries().is_empty());
+        history.push("c".into());
+        assert!(history.entries()[0].id > last_id);
+    }
+
+    #[test]
+    fn test_locked_ephemeral_is_not_consumed() {
+        let mut history = History::new(100);
+        history.push("secret".into());
+        let id = history.entries()[0].id;
+        history.toggle_ephemeral(id);
+        history.toggle_lock(id);
+
+        assert!(!history.consume_ephemeral(id));
+        assert_eq!(history.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_incremental_rule() {
+        for (previous, new) in [
+            ("hello", "hello world"),
+            ("world", "hello world"),
+            ("fn main", "fn main() {}"),
+            ("日本", "日本語"),
+            ("a", &format!("a{}", "b".repeat(INCREMENTAL_MAX_DELTA))),
+        ] {
+            assert!(is_incremental(previous, new), "{previous:?} -> {new:?}");
+        }
+        for (previous, new) in [
+            // Unrelated, shorter, equal or grown in the middle
+            ("hello", "goodbye"),
+            ("hello world", "hello"),
+            ("hello", "hello"),
+            ("hello", "hel-lo"),
+            ("", "anything"),
+            // Longer in bytes but not in characters
+            ("abc", "éé"),
+            ("abc", "日本"),
+            // Too much added at once
+            ("a", &format!("a{}", "b".repeat(INCREMENTAL_MAX_DELTA + 1))),
+        ] {
+            assert!(!is_incremental(previous, new), "{previous:?} -> {new:?}");
+        }
+    }
+
+    #[test]
+    fn test_collapse_incremental_push() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            collapse_incremental: true,
+            ..Config::default()
+        });
+        history.push("older".into());
+        history.push("The quick".into());
+        let id = history.entries()[0].id;
+        assert!(history.push("The quick brown".into()));
+        assert!(history.push("The quick brown fox".into()));
+        assert_eq!(history.entries().len(), 2);
+        assert_eq!(history.entries()[0].content, "The quick brown fox");
+        assert_eq!(history.entries()[0].id, id);
+        // The replaced text no longer counts as a duplicate
+        assert!(history.push("The quick".into()));
+        assert_eq!(history.entries().len(), 3);
+
+        // Unrelated content and large additions are new entries
+        history.push("jumps".into());
+        history.push(format!("jumps{}", " over".repeat(20)));
+        assert_eq!(history.entries().len(), 5);
+
+        // So is an extension of an old or pinned entry
+        history.push("lazy".into());
+        history.entries[0].created_at -= INCREMENTAL_WINDOW * 2;
+        history.push("lazy dog".into());
+        assert_eq!(history.entries().len(), 7);
+        history.entries[0].pinned = true;
+        history.push("lazy dog!".into());
+        assert_eq!(history.entries().len(), 8);
+    }
+
+    #[test]
+    fn test_incremental_copies_kept_by_default() {
+        let mut history = History::new(100);
+        history.push("The quick".into());
+        history.push("The quick brown".into());
+        assert_eq!(history.entries().len(), 2);
+    }
+
+    #[test]
+    fn test_mark_used() {
+        let mut history = History::new(100);
+        history.push("a".into());
+        let id = history.entries()[0].id;
+        assert!(history.mark_used(id));
+        assert!(history.mark_used(id));
+        assert_eq!(history.entries()[0].use_count, 2);
+        assert!(!history.mark_used(9999));
+    }
+
+    /// History with entries "new", "old-used", "old-unused" and "old-locked",
+    /// the old ones created 30 days ago.
+    fn aged_history() -> History {
+        let mut history = History::new(100);
+        for content in ["old-locked", "old-unused", "old-used", "new"] {
+            history.push(content.into());
+        }
+        let month_ago = Utc::now() - TimeDelta::days(30);
+        for entry in &mut history.entries {
+            if entry.content.starts_with("old") {
+                entry.created_at = month_ago;
+            }
+            match entry.content.as_str() {
+                "old-used" => entry.use_count = 3,
+                "old-locked" => entry.locked = true,
+                _ => {}
+            }
+        }
+        history
+    }
+
+    const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    #[test]
+    fn test_compact_unused_only() {
+        let mut history = aged_history();
+        assert_eq!(history.compact(WEEK, true), 1);
+        assert_eq!(contents(&history), vec!["new", "old-used", "old-locked"]);
+    }
+
+    #[test]
+    fn test_compact_regardless_of_use() {
+        let mut history = aged_history();
+        assert_eq!(history.compact(WEEK, false), 2);
+        assert_eq!(contents(&history), vec!["new", "old-locked"]);
+    }
+
+    #[test]
+    fn test_compact_keeps_entries_younger_than_cutoff() {
+        let mut history = aged_history();
+        assert_eq!(history.compact(WEEK * 8, false), 0);
+        assert_eq!(history.entries().len(), 4);
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let now = Utc::now();
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            expire_after_days: Some(7),
+            ..Config::default()
+        });
+        for content in ["stale-pinned", "stale-locked", "stale", "edge", "fresh"] {
+            history.push(content.into());
+        }
+        for entry in &mut history.entries {
+            entry.created_at = match entry.content.as_str() {
+                "fresh" => now - TimeDelta::hours(1),
+                "edge" => now - TimeDelta::days(7),
+                _ => now - TimeDelta::days(8),
+            };
+            entry.pinned = entry.content == "stale-pinned";
+            entry.locked = entry.content == "stale-locked";
+        }
+
+        assert_eq!(history.prune_expired(now), 1);
+        assert_eq!(
+            contents(&history),
+            vec!["fresh", "edge", "stale-locked", "stale-pinned"]
+        );
+        assert!(history.trash().is_empty());
+        // A day later the edge entry has expired as well
+        assert_eq!(history.prune_expired(now + TimeDelta::days(1)), 1);
+        assert_eq!(history.prune_expired(now + TimeDelta::days(1)), 0);
+    }
+
+    #[test]
+    fn test_prune_expired_disabled_and_trash() {
+        let now = Utc::now();
+        let mut history = history_with_trash();
+        assert_eq!(history.prune_expired(now), 0);
+        assert_eq!(history.compact(WEEK, false), 2);
+        assert_eq!(history.trash().len(), 2);
+
+        history.apply_config(&Config {
+            trash_retention_days: Some(7),
+            expire_after_days: Some(7),
+            ..Config::default()
+        });
+        assert_eq!(history.prune_expired(now), 2);
+        assert!(history.trash().is_empty());
+        assert_eq!(contents(&history), vec!["new", "old-locked"]);
+    }
+
+    #[test]
+    fn test_entropy_band_filters_capture() {
+        let mut history = History::new(100);
+        history.apply_config(&Config {
+            min_entropy: Some(1.0),
+            max_entropy: Some(5.0),
+            ..Config::default()
+        });
+        assert!(!history.push("aaaaaaaa".into()));
+        assert!(!history.push("q8Zf+2Kx/9LmWv3RbT0pYc7HnJd5GsE1aUo4iNk6".into()));
+        assert!(history.push("hello world".into()));
+        assert_eq!(contents(&history), vec!["hello world"]);
+    }
+
+    fn history_with_trash() -> History {
+        let mut history = aged_history();
+        history.apply_config(&Config {
+            trash_retention_days: Some(7),
+            ..Config::default()
+        });
+        history
+    }
+
+    #[test]
+    fn test_removed_entries_go_to_trash() {
+        let mut history = history_with_trash();
+        history.compact(WEEK, true);
+        history.clear();
+        let trashed: Vec<&str> = history
+            .trash()
+            .iter()
+            .map(|t| t.entry.content.as_str())
+            .collect();
+        assert_eq!(trashed, vec!["new", "old-used", "old-unused"]);
+    }
+
+    #[test]
+    fn test_no_trash_when_disabled() {
+        let mut history = aged_history();
+        assert_eq!(history.compact(WEEK, true), 1);
+        assert!(history.trash().is_empty());
+    }
+
+    #[test]
+    fn test_restore_from_trash() {
+        let mut history = history_with_trash();
+        history.compact(WEEK, true);
+        let id = history.trash()[0].entry.id;
+        assert!(history.restore_from_trash(id));
+        assert!(!history.restore_from_trash(id));
+        assert_eq!(history.entries()[0].id, id);
+        assert_eq!(history.entries()[0].content, "old-unused");
+        assert!(history.trash().is_empty());
+    }
+
+    #[test]
+    fn test_restore_from_trash_merges_into_a_pinned_duplicate() {
+        let mut history = history_with_trash();
+        history.push("x".into());
+        let old = history.entries()[0].id;
+        history.remove_by_id(old);
+        history.push("x".into());
+        let new = history.entries()[0].id;
+        assert!(history.toggle_pin(new));
+
+        assert!(history.restore_from_trash(old));
+        let copies: Vec<_> = history
+            .entries()
+            .iter()
+            .filter(|e| e.content == "x")
+            .collect();
+        assert_eq!(copies.len(), 1);
+        assert!(copies[0].pinned);
+        assert_eq!(copies[0].id, new);
+    }
+
+    #[test]
+    fn test_purge_trash_after_retention() {
+        let mut history = history_with_trash();
+        history.clear();
+        history.trash[0].deleted_at = Utc::now() - TimeDelta::days(8);
+        history.trash[1].deleted_at = Utc::now() - TimeDelta::days(6);
+        assert_eq!(history.purge_trash(), 1);
+        assert_eq!(history.trash().len(), 2);
+
+        // Disabling the trash empties it
+        history.apply_config(&Config {
+            trash_retention_days: None,
+            ..Config::default()
+        });
+        assert_eq!(history.purge_trash(), 2);
+    }
+
+    #[test]
+    fn test_huge_retention_keeps_everything() {
+        let mut history = history_with_trash();
+        assert_eq!(history.compact(Duration::from_secs(u64::MAX), false), 0);
+        history.clear();
+        let trashed = history.trash().len();
+        history.apply_config(&Config {
+            trash_retention_days: Some(u64::MAX),
+            ..Config::default()
+        });
+        history.trash[0].deleted_at = Utc::now() - TimeDelta::days(10_000);
+        assert_eq!(history.purge_trash(), 0);
+        assert_eq!(history.trash().len(), trashed);
+    }
 }
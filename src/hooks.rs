@@ -0,0 +1,150 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a capture or paste hook may run before it is killed.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum HookError {
+    Io(io::Error),
+    /// Killed after running for the given timeout.
+    TimedOut(Duration),
+    Failed(ExitStatus),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::Io(e) => write!(f, "{e}"),
+            HookError::TimedOut(timeout) => write!(f, "timed out after {timeout:?}"),
+            HookError::Failed(status) => write!(f, "exited with {status}"),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+impl From<io::Error> for HookError {
+    fn from(e: io::Error) -> Self {
+        HookError::Io(e)
+    }
+}
+
+/// Run `cmd` through the platform shell with `input` on stdin and return its
+/// stdout. The script is killed if it runs longer than `timeout`.
+///
+/// A single trailing newline is dropped from the output unless `input` ended
+/// with one, since most scripts `echo` their result.
+pub fn run(cmd: &str, input: &str, timeout: Duration) -> Result<String, HookError> {
+    let mut child = shell(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // Feed stdin and drain stdout on their own threads, so a script that
+    // ignores its input or writes a lot of output can't deadlock with us.
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut out = Vec::new();
+        stdout.read_to_end(&mut out).map(|_| out)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(HookError::TimedOut(timeout));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        return Err(HookError::Failed(status));
+    }
+
+    let out = reader
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("stdout reader panicked")))?;
+    let mut out = String::from_utf8_lossy(&out).into_owned();
+    if !input.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+        if out.ends_with('\r') {
+            out.pop();
+        }
+    }
+    Ok(out)
+}
+
+/// Run `cmd` on a background thread, logging failures. For hooks whose
+/// output isn't used, such as `Config::on_capture_cmd`.
+pub fn spawn(cmd: String, input: String) {
+    thread::spawn(move || {
+        if let Err(e) = run(&cmd, &input, HOOK_TIMEOUT) {
+            eprintln!("Hook \"{cmd}\" failed: {e}");
+        }
+    });
+}
+
+fn shell(cmd: &str) -> Command {
+    #[cfg(windows)]
+    let mut command = {
+        use std::os::windows::process::CommandExt;
+
+        // cmd.exe doesn't parse its command line MSVC-style, so quoting `cmd`
+        // as one argument would mangle quotes inside it
+        let mut command = Command::new("cmd");
+        command.arg("/C").raw_arg(cmd);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    };
+    crate::platform::hide_console(&mut command);
+    command
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_transforms_stdin() {
+        assert_eq!(run("tr a-z A-Z", "hello", TIMEOUT).unwrap(), "HELLO");
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        assert_eq!(run("cat; echo", "x", TIMEOUT).unwrap(), "x");
+        assert_eq!(run("cat", "x\n", TIMEOUT).unwrap(), "x\n");
+    }
+
+    #[test]
+    fn test_failure_and_timeout() {
+        assert!(matches!(
+            run("exit 3", "", TIMEOUT),
+            Err(HookError::Failed(_))
+        ));
+        let timeout = Duration::from_millis(100);
+        let err = run("sleep 5", "", timeout).unwrap_err();
+        assert!(matches!(err, HookError::TimedOut(t) if t == timeout));
+        assert_eq!(err.to_string(), "timed out after 100ms");
+    }
+}
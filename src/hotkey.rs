@@ -1,76 +1,528 @@
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use rdev::{listen, Event, EventType, Key};
+use rdev::{listen, simulate, Event, EventType, Key};
+
+use crate::config::Config;
+use crate::history::History;
+use crate::keymap::KeyCombo;
+
+/// A global shortcut such as `Ctrl+Shift+C`, matched against raw key events.
+/// Unlike `keymap::KeyCombo` it works while the window is hidden.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalCombo {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    key: Key,
+}
+
+/// Modifier keys currently held down.
+#[derive(Debug, Default, Clone, Copy)]
+struct Held {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl GlobalCombo {
+    fn matches(&self, key: Key, held: Held) -> bool {
+        self.key == key
+            && self.ctrl == held.ctrl
+            && self.shift == held.shift
+            && self.alt == held.alt
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseComboError(String);
+
+impl fmt::Display for ParseComboError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid global shortcut \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseComboError {}
+
+const LETTERS: [Key; 26] = [
+    Key::KeyA,
+    Key::KeyB,
+    Key::KeyC,
+    Key::KeyD,
+    Key::KeyE,
+    Key::KeyF,
+    Key::KeyG,
+    Key::KeyH,
+    Key::KeyI,
+    Key::KeyJ,
+    Key::KeyK,
+    Key::KeyL,
+    Key::KeyM,
+    Key::KeyN,
+    Key::KeyO,
+    Key::KeyP,
+    Key::KeyQ,
+    Key::KeyR,
+    Key::KeyS,
+    Key::KeyT,
+    Key::KeyU,
+    Key::KeyV,
+    Key::KeyW,
+    Key::KeyX,
+    Key::KeyY,
+    Key::KeyZ,
+];
+const DIGITS: [Key; 10] = [
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+impl FromStr for GlobalCombo {
+    type Err = ParseComboError;
+
+    /// Parse a [`KeyCombo`] whose key is a letter, digit, `F1`–`F12`,
+    /// `Space` or `Insert`, the keys the global hook knows. At least one
+    /// modifier is required so a plain keypress in another app can't
+    /// trigger it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseComboError(s.to_string());
+        let KeyCombo { modifiers, key } = s.parse().map_err(|_| err())?;
+        if modifiers.is_none() {
+            return Err(err());
+        }
+
+        let upper = key.name().to_ascii_uppercase();
+        let mut chars = upper.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c @ 'A'..='Z'), None) => LETTERS[(c as u8 - b'A') as usize],
+            (Some(c @ '0'..='9'), None) => DIGITS[(c as u8 - b'0') as usize],
+            _ => match upper.as_str() {
+                "SPACE" => Key::Space,
+                "INSERT" => Key::Insert,
+                f => f
+                    .strip_prefix('F')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .and_then(|n| FUNCTION_KEYS.get(n.wrapping_sub(1)).copied())
+                    .ok_or_else(err)?,
+            },
+        };
+        Ok(GlobalCombo {
+            ctrl: modifiers.command,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+            key,
+        })
+    }
+}
+
+/// A modifier key that can be double-tapped to toggle the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapKey {
+    Ctrl,
+    Shift,
+    Alt,
+}
+
+impl TapKey {
+    fn matches(self, key: Key) -> bool {
+        match self {
+            TapKey::Ctrl => matches!(key, Key::ControlLeft | Key::ControlRight),
+            TapKey::Shift => matches!(key, Key::ShiftLeft | Key::ShiftRight),
+            TapKey::Alt => matches!(key, Key::Alt | Key::AltGr),
+        }
+    }
+}
+
+/// The global shortcut that shows/hides the window: a modifier double-tap
+/// such as `"Ctrl+Ctrl"`, or a combo such as `"Ctrl+Shift+V"`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToggleHotkey {
+    DoubleTap(TapKey),
+    Combo(GlobalCombo),
+}
+
+impl Default for ToggleHotkey {
+    fn default() -> Self {
+        ToggleHotkey::DoubleTap(TapKey::Ctrl)
+    }
+}
+
+impl FromStr for ToggleHotkey {
+    type Err = ParseComboError;
+
+    /// Parse `"Ctrl+Ctrl"`, `"Shift+Shift"` or `"Alt+Alt"` as a double-tap,
+    /// anything else as a [`GlobalCombo`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tap_key = match s.split('+').map(str::trim).collect::<Vec<_>>()[..] {
+            [a, b] if a.eq_ignore_ascii_case(b) => match a.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Some(TapKey::Ctrl),
+                "shift" => Some(TapKey::Shift),
+                "alt" => Some(TapKey::Alt),
+                _ => None,
+            },
+            _ => None,
+        };
+        match tap_key {
+            Some(key) => Ok(ToggleHotkey::DoubleTap(key)),
+            None => s.parse().map(ToggleHotkey::Combo),
+        }
+    }
+}
+
+/// How the toggle hotkey behaves, from `Config::hotkey` and
+/// `Config::hotkey_toggles`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToggleSettings {
+    pub hotkey: ToggleHotkey,
+    /// Longest gap between the two presses of a double-tap.
+    pub double_tap_window: Duration,
+    /// Whether the hotkey hides a shown window (see `visibility_after_toggle`).
+    pub toggles: bool,
+}
+
+/// Detects double-taps of one key from raw key events.
+#[derive(Debug)]
+struct DoubleTap {
+    window: Duration,
+    /// Time of the previous genuine tap, if it could still start a double-tap.
+    last_press: Option<Instant>,
+    /// True while the key is physically held. Used to ignore OS key-repeat
+    /// events (KeyPress fires repeatedly while held, which would otherwise
+    /// trigger a false double-tap after ~530 ms).
+    is_down: bool,
+}
+
+impl DoubleTap {
+    fn new(window: Duration) -> Self {
+        DoubleTap {
+            window,
+            last_press: None,
+            is_down: false,
+        }
+    }
+
+    /// Feed a press seen at `now`. Returns true if it completes a
+    /// double-tap; the next press then starts a new one, so a triple-tap
+    /// counts once and a quadruple-tap twice.
+    fn press(&mut self, now: Instant) -> bool {
+        if self.is_down {
+            return false;
+        }
+        self.is_down = true;
+        match self.last_press {
+            Some(prev) if now.duration_since(prev) < self.window => {
+                self.last_press = None;
+                true
+            }
+            _ => {
+                self.last_press = Some(now);
+                false
+            }
+        }
+    }
+
+    fn release(&mut self) {
+        self.is_down = false;
+    }
+
+    /// Forget a pending first tap, e.g. when the key was part of another shortcut.
+    fn reset(&mut self) {
+        self.last_press = None;
+    }
+}
+
+/// Turns raw key events into activations of the toggle hotkey, ignoring
+/// key repeat for both kinds.
+#[derive(Debug)]
+struct ToggleDetector {
+    hotkey: ToggleHotkey,
+    taps: DoubleTap,
+    /// The combo's key is held since it fired, so repeats don't fire it again.
+    combo_down: bool,
+}
+
+impl ToggleDetector {
+    fn new(hotkey: ToggleHotkey, double_tap_window: Duration) -> Self {
+        ToggleDetector {
+            hotkey,
+            taps: DoubleTap::new(double_tap_window),
+            combo_down: false,
+        }
+    }
+
+    /// Feed a key press with the modifiers `held` at `now`. Returns whether
+    /// it activates the hotkey.
+    fn press(&mut self, key: Key, held: Held, now: Instant) -> bool {
+        match self.hotkey {
+            ToggleHotkey::DoubleTap(tap_key) => tap_key.matches(key) && self.taps.press(now),
+            ToggleHotkey::Combo(combo) => {
+                let fire = combo.matches(key, held) && !self.combo_down;
+                self.combo_down |= fire;
+                fire
+            }
+        }
+    }
+
+    fn release(&mut self, key: Key) {
+        match self.hotkey {
+            ToggleHotkey::DoubleTap(tap_key) if tap_key.matches(key) => self.taps.release(),
+            ToggleHotkey::Combo(combo) if combo.key == key => self.combo_down = false,
+            _ => {}
+        }
+    }
+
+    /// Another shortcut used the keys, so a pending first tap doesn't count.
+    fn reset(&mut self) {
+        self.taps.reset();
+    }
+}
+
+/// What the global listener reacts to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bindings {
+    pub toggle: ToggleSettings,
+    /// Pushes the current clipboard into the history (`Config::capture_hotkey`).
+    pub capture: Option<GlobalCombo>,
+}
+
+impl Bindings {
+    /// Read the bindings from `config`. An invalid toggle hotkey falls back
+    /// to the default double-tap, an invalid capture hotkey to none.
+    pub fn from_config(config: &Config) -> Self {
+        let hotkey = config.hotkey.toggle.parse().unwrap_or_else(|e| {
+            eprintln!("{e}; using the default Ctrl+Ctrl double-tap to show the window");
+            ToggleHotkey::default()
+        });
+        let capture = config.capture_hotkey.as_deref().and_then(|combo| {
+            combo
+                .parse()
+                .map_err(|e| eprintln!("Capture hotkey disabled: {e}"))
+                .ok()
+        });
+        Bindings {
+            toggle: ToggleSettings {
+                hotkey,
+                double_tap_window: Duration::from_millis(config.hotkey.double_tap_ms),
+                toggles: config.hotkey_toggles,
+            },
+            capture,
+        }
+    }
+}
+
+/// The bindings of a running listener. `rdev::listen` can't be stopped, so
+/// rather than restarting its thread the listener looks them up on every
+/// key event and a change applies from the next one.
+#[derive(Debug, Clone)]
+pub struct HotkeyHandle(Arc<Mutex<Bindings>>);
+
+impl HotkeyHandle {
+    pub fn new(bindings: Bindings) -> Self {
+        HotkeyHandle(Arc::new(Mutex::new(bindings)))
+    }
+
+    /// Switch the listener to `bindings`, e.g. after the config changed.
+    pub fn reconfigure(&self, bindings: Bindings) {
+        *self.0.lock().unwrap() = bindings;
+    }
+
+    fn bindings(&self) -> Bindings {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// A global shortcut the listener fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fired {
+    Toggle,
+    Capture,
+}
+
+/// The listener's key handling, apart from rdev: tracks the held modifiers
+/// and feeds presses to a detector for the current bindings.
+#[derive(Debug)]
+struct KeyHandler {
+    handle: HotkeyHandle,
+    /// The bindings `detector` was made for.
+    applied: Bindings,
+    detector: ToggleDetector,
+    held: Held,
+}
+
+impl KeyHandler {
+    fn new(handle: HotkeyHandle) -> Self {
+        let applied = handle.bindings();
+        KeyHandler {
+            handle,
+            applied,
+            detector: ToggleDetector::new(applied.toggle.hotkey, applied.toggle.double_tap_window),
+            held: Held::default(),
+        }
+    }
+
+    /// Feed a raw event seen at `now`. Bindings changed through the handle
+    /// are picked up first, dropping a half-finished double-tap of the old
+    /// ones.
+    fn event(&mut self, event: &EventType, now: Instant) -> Option<Fired> {
+        let bindings = self.handle.bindings();
+        if bindings != self.applied {
+            self.detector =
+                ToggleDetector::new(bindings.toggle.hotkey, bindings.toggle.double_tap_window);
+            self.applied = bindings;
+        }
+        track_modifiers(event, &mut self.held);
+        match *event {
+            EventType::KeyPress(key)
+                if bindings.capture.is_some_and(|c| c.matches(key, self.held)) =>
+            {
+                // The combo's modifier press mustn't count towards a double-tap
+                self.detector.reset();
+                Some(Fired::Capture)
+            }
+            EventType::KeyPress(key) => self
+                .detector
+                .press(key, self.held, now)
+                .then_some(Fired::Toggle),
+            EventType::KeyRelease(key) => {
+                self.detector.release(key);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Window visibility after the toggle hotkey: toggled, or with `toggles`
+/// off always shown so extra presses can't close it.
+fn visibility_after_toggle(visible: bool, toggles: bool) -> bool {
+    !(visible && toggles)
+}
+
+/// Set while `paste_into_previous_app` is sending keys, so the listener
+/// doesn't take its Ctrl press for a tap.
+static SIMULATING: AtomicBool = AtomicBool::new(false);
+
+/// How long to wait for the previous app to get the focus back after the
+/// window hides.
+const FOCUS_RETURN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Key events of the platform's paste shortcut: Cmd+V on macOS, Ctrl+V elsewhere.
+fn paste_keys() -> [EventType; 4] {
+    let modifier = if cfg!(target_os = "macos") {
+        Key::MetaLeft
+    } else {
+        Key::ControlLeft
+    };
+    [
+        EventType::KeyPress(modifier),
+        EventType::KeyPress(Key::KeyV),
+        EventType::KeyRelease(Key::KeyV),
+        EventType::KeyRelease(modifier),
+    ]
+}
+
+/// Send the paste shortcut to whichever app has the focus once our window
+/// is gone, from a background thread. Call after hiding the window: the
+/// keystrokes wait (on Windows) until it is no longer in the foreground,
+/// and until `pending` (a paste hook still writing the clipboard) finishes.
+pub fn paste_into_previous_app(pending: Option<thread::JoinHandle<()>>) {
+    thread::spawn(move || {
+        if let Some(pending) = pending {
+            if pending.join().is_err() {
+                eprintln!("Paste hook thread panicked, skipping auto-paste");
+                return;
+            }
+        }
+        let deadline = Instant::now() + FOCUS_RETURN_TIMEOUT;
+        while crate::platform::own_window_is_foreground() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        // Let the target app finish activating before it gets keys
+        thread::sleep(Duration::from_millis(50));
+        SIMULATING.store(true, Ordering::SeqCst);
+        for event in paste_keys() {
+            if let Err(e) = simulate(&event) {
+                eprintln!("Failed to simulate {:?} for auto-paste: {:?}", event, e);
+            }
+            // Some platforms drop events sent back to back
+            thread::sleep(Duration::from_millis(20));
+        }
+        SIMULATING.store(false, Ordering::SeqCst);
+    });
+}
 
 /// Start the global hotkey listener in a background thread.
-/// Detects Ctrl+Ctrl double-tap (two Ctrl presses within 300ms).
-/// Also tracks global mouse cursor position into `cursor_pos`.
+/// Detects the toggle hotkey (by default a Ctrl+Ctrl double-tap), which
+/// toggles the window or, with `toggles` off, only ever shows it.
+/// Also tracks global mouse cursor position into `cursor_pos`, and pushes the
+/// current clipboard into `history` when the capture combo is pressed. Both
+/// shortcuts are read from `hotkeys` on each event, see [`HotkeyHandle`].
 pub fn start_listener(
     visible: Arc<Mutex<bool>>,
     ctx: eframe::egui::Context,
     cursor_pos: Arc<Mutex<(f64, f64)>>,
+    history: Arc<Mutex<History>>,
+    hotkeys: HotkeyHandle,
 ) -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        // last_ctrl_press: timestamp of the previous genuine Ctrl tap.
-        let last_ctrl_press: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
-        // ctrl_is_down: true while any Ctrl key is physically held.
-        // Used to ignore OS key-repeat events (KeyPress fires repeatedly while
-        // held, which would otherwise trigger a false double-tap after ~530 ms).
-        let ctrl_is_down: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
-
-        let last_ctrl = Arc::clone(&last_ctrl_press);
-        let is_down = Arc::clone(&ctrl_is_down);
-        let vis = Arc::clone(&visible);
-        let cur = Arc::clone(&cursor_pos);
+        let mut keys = KeyHandler::new(hotkeys);
 
         let callback = move |event: Event| {
-            match event.event_type {
-                EventType::MouseMove { x, y } => {
-                    *cur.lock().unwrap() = (x, y);
-                }
-                EventType::KeyPress(Key::ControlLeft)
-                | EventType::KeyPress(Key::ControlRight) => {
-                    // Ignore key-repeat events produced by holding the key.
-                    let mut down = is_down.lock().unwrap();
-                    if *down {
-                        return;
-                    }
-                    *down = true;
-                    drop(down);
-
-                    let mut last = last_ctrl.lock().unwrap();
-                    let now = Instant::now();
-
-                    if let Some(prev) = *last {
-                        let elapsed = now.duration_since(prev);
-                        if elapsed.as_millis() < 300 {
-                            // Double-tap detected — toggle visibility
-                            let mut v = vis.lock().unwrap();
-                            *v = !*v;
-                            let is_now_visible = *v;
-                            drop(v);
-
-                            if is_now_visible {
-                                crate::platform::show_window_native();
-                            } else {
-                                crate::platform::hide_window_native();
-                            }
-
-                            ctx.request_repaint();
-                            *last = None; // Reset to avoid triple-tap
-                            return;
-                        }
-                    }
+            if SIMULATING.load(Ordering::SeqCst) {
+                return;
+            }
+            track_cursor(&event.event_type, &cursor_pos);
 
-                    *last = Some(now);
+            match keys.event(&event.event_type, Instant::now()) {
+                Some(Fired::Capture) => {
+                    // Not on the hook thread, where a slow clipboard read
+                    // would hold up every key and mouse event
+                    let history = Arc::clone(&history);
+                    let ctx = ctx.clone();
+                    thread::spawn(move || crate::clipboard::capture_now(&history, &ctx));
                 }
-                EventType::KeyRelease(Key::ControlLeft)
-                | EventType::KeyRelease(Key::ControlRight) => {
-                    *is_down.lock().unwrap() = false;
+                Some(Fired::Toggle) => {
+                    let mut v = visible.lock().unwrap();
+                    *v = visibility_after_toggle(*v, keys.applied.toggle.toggles);
+                    let is_now_visible = *v;
+                    drop(v);
+
+                    // Showing an already shown window brings it back to the front
+                    if is_now_visible {
+                        crate::platform::show_window_native();
+                    } else {
+                        crate::platform::hide_window_native();
+                    }
+                    ctx.request_repaint();
                 }
-                _ => {}
+                None => {}
             }
         };
 
@@ -79,3 +531,328 @@ pub fn start_listener(
         }
     })
 }
+
+/// Start a global key listener that ignores every event and report whether
+/// it is still running after `wait`. `rdev::listen` only returns on failure,
+/// so surviving the wait means the hook was installed.
+pub fn check_listener(wait: Duration) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        if let Err(e) = listen(|_| {}) {
+            let _ = tx.send(format!("{e:?}"));
+        }
+    });
+    match rx.recv_timeout(wait) {
+        Ok(e) => Err(e),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Record the global mouse position from a `MouseMove` event; the window is
+/// shown next to it.
+fn track_cursor(event: &EventType, cursor_pos: &Mutex<(f64, f64)>) {
+    if let EventType::MouseMove { x, y } = *event {
+        *cursor_pos.lock().unwrap() = (x, y);
+    }
+}
+
+fn track_modifiers(event: &EventType, held: &mut Held) {
+    let (key, down) = match *event {
+        EventType::KeyPress(key) => (key, true),
+        EventType::KeyRelease(key) => (key, false),
+        _ => return,
+    };
+    match key {
+        Key::ControlLeft | Key::ControlRight => held.ctrl = down,
+        Key::ShiftLeft | Key::ShiftRight => held.shift = down,
+        Key::Alt | Key::AltGr => held.alt = down,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_parse_global_combo() {
+        let combo: GlobalCombo = "Ctrl+Shift+c".parse().unwrap();
+        assert_eq!(
+            combo,
+            GlobalCombo {
+                ctrl: true,
+                shift: true,
+                alt: false,
+                key: Key::KeyC
+            }
+        );
+        assert_eq!("Alt+F12".parse::<GlobalCombo>().unwrap().key, Key::F12);
+        assert_eq!("Ctrl+7".parse::<GlobalCombo>().unwrap().key, Key::Num7);
+    }
+
+    #[test]
+    fn test_parse_global_combo_errors() {
+        assert!("C".parse::<GlobalCombo>().is_err());
+        assert!("Ctrl+Ctrl+C".parse::<GlobalCombo>().is_err());
+        assert!("Ctrl+F13".parse::<GlobalCombo>().is_err());
+        assert!("Ctrl+F0".parse::<GlobalCombo>().is_err());
+        assert!("Hyper+C".parse::<GlobalCombo>().is_err());
+    }
+
+    #[test]
+    fn test_combo_needs_exact_modifiers() {
+        let combo: GlobalCombo = "Ctrl+Shift+C".parse().unwrap();
+        let ctrl_shift = Held {
+            ctrl: true,
+            shift: true,
+            alt: false,
+        };
+        let ctrl_only = Held {
+            ctrl: true,
+            ..Held::default()
+        };
+        assert!(combo.matches(Key::KeyC, ctrl_shift));
+        assert!(!combo.matches(Key::KeyC, ctrl_only));
+        assert!(!combo.matches(Key::KeyV, ctrl_shift));
+    }
+
+    #[test]
+    fn test_track_cursor() {
+        let cursor_pos = Mutex::new((0.0, 0.0));
+        let events = [
+            EventType::MouseMove { x: 10.0, y: 20.0 },
+            EventType::KeyPress(Key::ControlLeft),
+            EventType::MouseMove { x: 640.5, y: 480.0 },
+            EventType::KeyRelease(Key::ControlLeft),
+        ];
+        let mut seen = Vec::new();
+        for event in &events {
+            track_cursor(event, &cursor_pos);
+            seen.push(*cursor_pos.lock().unwrap());
+        }
+        assert_eq!(
+            seen,
+            vec![(10.0, 20.0), (10.0, 20.0), (640.5, 480.0), (640.5, 480.0)]
+        );
+    }
+
+    /// Feed (press, release) pairs `gap_ms` apart; returns which presses
+    /// completed a double-tap.
+    fn taps(gaps_ms: &[u64]) -> Vec<bool> {
+        let mut taps = DoubleTap::new(Duration::from_millis(300));
+        let mut now = Instant::now();
+        gaps_ms
+            .iter()
+            .map(|&gap| {
+                now += Duration::from_millis(gap);
+                let tapped = taps.press(now);
+                taps.release();
+                tapped
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_double_tap_multi_tap_sequences() {
+        assert_eq!(taps(&[0, 100]), vec![false, true]);
+        assert_eq!(taps(&[0, 400]), vec![false, false]);
+        // Triple-tap counts once, quadruple-tap twice
+        assert_eq!(taps(&[0, 100, 100]), vec![false, true, false]);
+        assert_eq!(taps(&[0, 100, 100, 100]), vec![false, true, false, true]);
+    }
+
+    #[test]
+    fn test_double_tap_ignores_key_repeat() {
+        let mut taps = DoubleTap::new(Duration::from_millis(300));
+        let now = Instant::now();
+        assert!(!taps.press(now));
+        // Repeats while held
+        assert!(!taps.press(now + Duration::from_millis(50)));
+        assert!(!taps.press(now + Duration::from_millis(100)));
+        taps.release();
+        assert!(taps.press(now + Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn test_visibility_after_repeated_double_taps() {
+        let run = |toggles| {
+            let mut visible = false;
+            (0..3)
+                .map(|_| {
+                    visible = visibility_after_toggle(visible, toggles);
+                    visible
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(true), vec![true, false, true]);
+        assert_eq!(run(false), vec![true, true, true]);
+    }
+
+    #[test]
+    fn test_paste_keys_release_in_reverse_order() {
+        let keys = paste_keys();
+        let modifier = match keys[0] {
+            EventType::KeyPress(key) => key,
+            other => panic!("paste starts with {other:?}"),
+        };
+        assert_eq!(keys[1], EventType::KeyPress(Key::KeyV));
+        assert_eq!(keys[2], EventType::KeyRelease(Key::KeyV));
+        assert_eq!(keys[3], EventType::KeyRelease(modifier));
+    }
+
+    #[test]
+    fn test_parse_toggle_hotkey() {
+        let parse = |s: &str| s.parse::<ToggleHotkey>();
+        assert_eq!(
+            parse("Ctrl+Ctrl"),
+            Ok(ToggleHotkey::DoubleTap(TapKey::Ctrl))
+        );
+        assert_eq!(
+            parse("shift + Shift"),
+            Ok(ToggleHotkey::DoubleTap(TapKey::Shift))
+        );
+        assert_eq!(parse("Alt+Alt"), Ok(ToggleHotkey::DoubleTap(TapKey::Alt)));
+        assert_eq!(
+            parse("Ctrl+Shift+V"),
+            Ok(ToggleHotkey::Combo(GlobalCombo {
+                ctrl: true,
+                shift: true,
+                alt: false,
+                key: Key::KeyV
+            }))
+        );
+        assert_eq!(
+            parse(&Config::default().hotkey.toggle),
+            Ok(ToggleHotkey::default())
+        );
+    }
+
+    #[test]
+    fn test_parse_toggle_hotkey_errors() {
+        for invalid in [
+            "",
+            "V",
+            "Ctrl+Alt",
+            "Ctrl+Ctrl+Ctrl",
+            "Win+Win",
+            "Ctrl+Nope",
+        ] {
+            assert!(invalid.parse::<ToggleHotkey>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_combo_toggle_ignores_key_repeat() {
+        let combo = "Ctrl+Shift+V".parse().unwrap();
+        let mut detector = ToggleDetector::new(combo, Duration::from_millis(300));
+        let held = Held {
+            ctrl: true,
+            shift: true,
+            alt: false,
+        };
+        let now = Instant::now();
+        assert!(detector.press(Key::KeyV, held, now));
+        // Repeats while V is held
+        assert!(!detector.press(Key::KeyV, held, now));
+        assert!(!detector.press(Key::KeyV, held, now));
+        detector.release(Key::KeyV);
+        assert!(detector.press(Key::KeyV, held, now));
+        // Wrong modifiers, or the modifiers alone
+        detector.release(Key::KeyV);
+        assert!(!detector.press(Key::KeyV, Held::default(), now));
+        assert!(!detector.press(Key::ControlLeft, held, now));
+    }
+
+    #[test]
+    fn test_double_tap_toggle_uses_configured_window() {
+        let mut detector = ToggleDetector::new(
+            ToggleHotkey::DoubleTap(TapKey::Shift),
+            Duration::from_millis(500),
+        );
+        let now = Instant::now();
+        assert!(!detector.press(Key::ShiftLeft, Held::default(), now));
+        detector.release(Key::ShiftLeft);
+        // Other keys neither count nor reset the tap
+        assert!(!detector.press(Key::ControlLeft, Held::default(), now));
+        assert!(detector.press(
+            Key::ShiftRight,
+            Held::default(),
+            now + Duration::from_millis(400)
+        ));
+    }
+
+    fn bindings(toggle: &str, capture: Option<&str>) -> Bindings {
+        Bindings {
+            toggle: ToggleSettings {
+                hotkey: toggle.parse().unwrap(),
+                double_tap_window: Duration::from_millis(300),
+                toggles: true,
+            },
+            capture: capture.map(|c| c.parse().unwrap()),
+        }
+    }
+
+    /// Press and release `keys` in order at `at`, returning what fired.
+    fn chord(handler: &mut KeyHandler, keys: &[Key], at: Instant) -> Vec<Fired> {
+        let mut fired: Vec<Fired> = keys
+            .iter()
+            .filter_map(|&key| handler.event(&EventType::KeyPress(key), at))
+            .collect();
+        for &key in keys.iter().rev() {
+            fired.extend(handler.event(&EventType::KeyRelease(key), at));
+        }
+        fired
+    }
+
+    #[test]
+    fn test_bindings_from_config() {
+        let mut config = Config::default();
+        config.hotkey.toggle = "Ctrl+Alt+V".into();
+        config.hotkey.double_tap_ms = 500;
+        config.capture_hotkey = Some("Ctrl+Shift+C".into());
+        let mut expected = bindings("Ctrl+Alt+V", Some("Ctrl+Shift+C"));
+        expected.toggle.double_tap_window = Duration::from_millis(500);
+        assert_eq!(Bindings::from_config(&config), expected);
+
+        // Invalid hotkeys fall back instead of failing
+        config.hotkey.toggle = "Ctrl+Nope".into();
+        config.capture_hotkey = Some("Ctrl+".into());
+        let fallback = Bindings::from_config(&config);
+        assert_eq!(fallback.toggle.hotkey, ToggleHotkey::default());
+        assert_eq!(fallback.capture, None);
+    }
+
+    #[test]
+    fn test_key_handler_follows_reconfigure() {
+        let handle = HotkeyHandle::new(bindings("Ctrl+Ctrl", Some("Ctrl+Shift+C")));
+        let mut handler = KeyHandler::new(handle.clone());
+        let now = Instant::now();
+        let ms = |n| now + Duration::from_millis(n);
+
+        assert!(chord(&mut handler, &[Key::ControlLeft], ms(0)).is_empty());
+        assert_eq!(
+            chord(&mut handler, &[Key::ControlLeft], ms(100)),
+            [Fired::Toggle]
+        );
+        let capture = [Key::ControlLeft, Key::ShiftLeft, Key::KeyC];
+        assert_eq!(chord(&mut handler, &capture, ms(200)), [Fired::Capture]);
+
+        // A first tap of the old binding doesn't carry over
+        assert!(chord(&mut handler, &[Key::ControlLeft], ms(1000)).is_empty());
+        handle.reconfigure(bindings("Ctrl+Alt+V", None));
+        assert!(chord(&mut handler, &[Key::ControlLeft], ms(1100)).is_empty());
+        assert!(chord(&mut handler, &capture, ms(1200)).is_empty());
+        let toggle = [Key::ControlLeft, Key::Alt, Key::KeyV];
+        assert_eq!(chord(&mut handler, &toggle, ms(1300)), [Fired::Toggle]);
+
+        // Back to a double-tap, now of Shift
+        handle.reconfigure(bindings("Shift+Shift", None));
+        assert!(chord(&mut handler, &toggle, ms(2000)).is_empty());
+        assert!(chord(&mut handler, &[Key::ShiftLeft], ms(2100)).is_empty());
+        assert_eq!(
+            chord(&mut handler, &[Key::ShiftRight], ms(2200)),
+            [Fired::Toggle]
+        );
+    }
+}
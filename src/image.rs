@@ -0,0 +1,242 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::history::fnv1a;
+
+/// Longest side of a list thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: usize = 48;
+
+/// A copied image as RGBA8 pixels. Stored on disk as base64 PNG, since the
+/// raw pixels of a screenshot would be megabytes of JSON numbers. The pixels
+/// are hashed once, so comparing images is cheap, and encoded once, on the
+/// first save, so later saves reuse the PNG.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "StoredImage")]
+pub struct ImageContent {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major RGBA, `width * height * 4` bytes.
+    bytes: Vec<u8>,
+    hash: u64,
+    /// The base64 PNG, shared by clones.
+    encoded: Arc<OnceLock<String>>,
+}
+
+impl PartialEq for ImageContent {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && (self.width, self.height) == (other.width, other.height)
+            && self.bytes == other.bytes
+    }
+}
+
+impl Eq for ImageContent {}
+
+impl Serialize for ImageContent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredImage {
+            png: self.encoded().to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+// Derived Debug would print every pixel
+impl fmt::Debug for ImageContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ImageContent({}×{})", self.width, self.height)
+    }
+}
+
+impl ImageContent {
+    pub fn new(width: usize, height: usize, bytes: Vec<u8>) -> ImageContent {
+        ImageContent {
+            width,
+            height,
+            hash: fnv1a(&bytes),
+            bytes,
+            encoded: Arc::default(),
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// [`fnv1a`] of the pixels.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The text an image entry shows and is searched by, e.g. "Image 800×600".
+    pub fn label(&self) -> String {
+        format!("Image {}×{}", self.width, self.height)
+    }
+
+    /// A copy scaled down (nearest neighbour) so its longer side is at most
+    /// `max_side`. Images already that small are returned unchanged.
+    pub fn thumbnail(&self, max_side: usize) -> ImageContent {
+        let longest = self.width.max(self.height);
+        if longest <= max_side {
+            return self.clone();
+        }
+        let width = (self.width * max_side / longest).max(1);
+        let height = (self.height * max_side / longest).max(1);
+        let mut bytes = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let src_y = y * self.height / height;
+            for x in 0..width {
+                let src = (src_y * self.width + x * self.width / width) * 4;
+                bytes.extend_from_slice(&self.bytes[src..src + 4]);
+            }
+        }
+        ImageContent::new(width, height, bytes)
+    }
+
+    /// The base64 PNG for the history file, encoded on first use.
+    fn encoded(&self) -> &str {
+        self.encoded.get_or_init(|| {
+            let png = self.to_png().unwrap_or_else(|e| {
+                // Only fails if the pixels don't match the size; store nothing
+                eprintln!("Failed to encode {self:?} as PNG: {e}");
+                Vec::new()
+            });
+            BASE64.encode(png)
+        })
+    }
+
+    fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.bytes)?;
+        Ok(png)
+    }
+
+    fn from_png(data: &[u8]) -> Result<ImageContent, String> {
+        let mut reader = png::Decoder::new(data)
+            .read_info()
+            .map_err(|e| e.to_string())?;
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut bytes).map_err(|e| e.to_string())?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(format!(
+                "expected 8-bit RGBA, got {:?} {:?}",
+                info.color_type, info.bit_depth
+            ));
+        }
+        bytes.truncate(info.buffer_size());
+        Ok(ImageContent::new(
+            info.width as usize,
+            info.height as usize,
+            bytes,
+        ))
+    }
+}
+
+/// Deserialize an entry's image, dropping one that can't be decoded instead
+/// of failing to load the whole history.
+pub fn deserialize_lenient<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ImageContent>, D::Error> {
+    let stored = Option::<StoredImage>::deserialize(deserializer)?;
+    Ok(stored.and_then(|stored| {
+        ImageContent::try_from(stored)
+            .map_err(|e| eprintln!("Dropping an image that can't be decoded: {e}"))
+            .ok()
+    }))
+}
+
+/// On-disk form of [`ImageContent`].
+#[derive(Serialize, Deserialize)]
+struct StoredImage {
+    png: String,
+}
+
+impl TryFrom<StoredImage> for ImageContent {
+    type Error = String;
+
+    fn try_from(stored: StoredImage) -> Result<Self, Self::Error> {
+        let png = BASE64.decode(&stored.png).map_err(|e| e.to_string())?;
+        let image = ImageContent::from_png(&png)?;
+        // Saving it again needs no new encoding
+        let _ = image.encoded.set(stored.png);
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width`×`height` image whose pixel at (x, y) is `[x, y, 0, 255]`.
+    fn gradient(width: usize, height: usize) -> ImageContent {
+        let mut bytes = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                bytes.extend_from_slice(&[x as u8, y as u8, 0, 255]);
+            }
+        }
+        ImageContent::new(width, height, bytes)
+    }
+
+    #[test]
+    fn test_serde_roundtrip_through_png() {
+        let image = gradient(7, 3);
+        let json = serde_json::to_string(&image).unwrap();
+        assert!(json.contains("\"png\""));
+        let loaded: ImageContent = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, image);
+        assert_eq!(loaded.hash(), image.hash());
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
+
+    #[test]
+    fn test_encoding_is_cached_and_shared_by_clones() {
+        let image = gradient(4, 4);
+        let copy = image.clone();
+        let png = image.encoded().as_ptr();
+        assert_eq!(copy.encoded().as_ptr(), png);
+        assert_ne!(image, gradient(4, 5));
+        assert_ne!(image, ImageContent::new(4, 4, vec![0; 64]));
+    }
+
+    #[test]
+    fn test_invalid_png_is_an_error() {
+        assert!(serde_json::from_str::<ImageContent>(r#"{"png":"bm90IGEgcG5n"}"#).is_err());
+        assert!(serde_json::from_str::<ImageContent>(r#"{"png":"!!"}"#).is_err());
+    }
+
+    #[test]
+    fn test_lenient_deserialize_drops_bad_image() {
+        #[derive(Deserialize)]
+        struct Entry {
+            #[serde(default, deserialize_with = "deserialize_lenient")]
+            image: Option<ImageContent>,
+        }
+        let good = serde_json::to_string(&gradient(2, 2)).unwrap();
+        let entry: Entry = serde_json::from_str(&format!(r#"{{"image":{good}}}"#)).unwrap();
+        assert_eq!(entry.image, Some(gradient(2, 2)));
+        let entry: Entry = serde_json::from_str(r#"{"image":{"png":"!!"}}"#).unwrap();
+        assert_eq!(entry.image, None);
+        let entry: Entry = serde_json::from_str("{}").unwrap();
+        assert_eq!(entry.image, None);
+    }
+
+    #[test]
+    fn test_thumbnail_keeps_aspect_ratio() {
+        let thumb = gradient(200, 100).thumbnail(50);
+        assert_eq!((thumb.width, thumb.height), (50, 25));
+        assert_eq!(thumb.bytes.len(), 50 * 25 * 4);
+        // Nearest neighbour: thumbnail pixel (1, 1) is source pixel (4, 4)
+        assert_eq!(&thumb.bytes[(50 + 1) * 4..][..4], &[4, 4, 0, 255]);
+
+        let small = gradient(10, 20);
+        assert_eq!(small.thumbnail(50), small);
+    }
+}
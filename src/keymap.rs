@@ -0,0 +1,585 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use eframe::egui::{self, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// Something the user can trigger from the keyboard while the window is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    NavUp,
+    NavDown,
+    NavLeft,
+    NavRight,
+    NavFirst,
+    NavLast,
+    Select,
+    PasteSelected,
+    Hide,
+    ClearSearch,
+    ToggleEphemeral,
+    SplitEntry,
+    TogglePalette,
+    ToggleLock,
+    GrowWindow,
+    ShrinkWindow,
+    ToggleTemplate,
+    ToggleMark,
+    ShowDiff,
+    ToggleGroup,
+    ToggleTrash,
+    DeleteFiltered,
+    DeleteEntry,
+    ExportEntry,
+    TogglePin,
+    TogglePreview,
+    CycleSearchMode,
+    UndoDelete,
+    /// Paste the selected entry as plain text, without its HTML.
+    PastePlain,
+    /// Paste the selected entry and keep the window open for the next pick.
+    PasteKeepOpen,
+    /// Match the search query's case exactly (fuzzy and exact modes).
+    ToggleCaseSensitive,
+    /// Paste the selected entry transformed, leaving the stored entry as is.
+    PasteUppercase,
+    PasteLowercase,
+    PasteTrimmed,
+    /// Cycle the order of the unfiltered list: newest, oldest, most used.
+    CycleListOrder,
+    /// Paste the 1st … 9th result at once.
+    #[serde(rename = "quick_select_1")]
+    QuickSelect1,
+    #[serde(rename = "quick_select_2")]
+    QuickSelect2,
+    #[serde(rename = "quick_select_3")]
+    QuickSelect3,
+    #[serde(rename = "quick_select_4")]
+    QuickSelect4,
+    #[serde(rename = "quick_select_5")]
+    QuickSelect5,
+    #[serde(rename = "quick_select_6")]
+    QuickSelect6,
+    #[serde(rename = "quick_select_7")]
+    QuickSelect7,
+    #[serde(rename = "quick_select_8")]
+    QuickSelect8,
+    #[serde(rename = "quick_select_9")]
+    QuickSelect9,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::NavUp,
+        Action::NavDown,
+        Action::NavLeft,
+        Action::NavRight,
+        Action::NavFirst,
+        Action::NavLast,
+        Action::Select,
+        Action::PasteSelected,
+        Action::Hide,
+        Action::ClearSearch,
+        Action::ToggleEphemeral,
+        Action::SplitEntry,
+        Action::TogglePalette,
+        Action::ToggleLock,
+        Action::GrowWindow,
+        Action::ShrinkWindow,
+        Action::ToggleTemplate,
+        Action::ToggleMark,
+        Action::ShowDiff,
+        Action::ToggleGroup,
+        Action::ToggleTrash,
+        Action::DeleteFiltered,
+        Action::DeleteEntry,
+        Action::ExportEntry,
+        Action::TogglePin,
+        Action::TogglePreview,
+        Action::CycleSearchMode,
+        Action::UndoDelete,
+        Action::PastePlain,
+        Action::PasteKeepOpen,
+        Action::ToggleCaseSensitive,
+        Action::PasteUppercase,
+        Action::PasteLowercase,
+        Action::PasteTrimmed,
+        Action::CycleListOrder,
+        Action::QuickSelect1,
+        Action::QuickSelect2,
+        Action::QuickSelect3,
+        Action::QuickSelect4,
+        Action::QuickSelect5,
+        Action::QuickSelect6,
+        Action::QuickSelect7,
+        Action::QuickSelect8,
+        Action::QuickSelect9,
+    ];
+
+    /// The result a quick-select action picks: 0 for `QuickSelect1`.
+    pub fn quick_select_index(self) -> Option<usize> {
+        let index = match self {
+            Action::QuickSelect1 => 0,
+            Action::QuickSelect2 => 1,
+            Action::QuickSelect3 => 2,
+            Action::QuickSelect4 => 3,
+            Action::QuickSelect5 => 4,
+            Action::QuickSelect6 => 5,
+            Action::QuickSelect7 => 6,
+            Action::QuickSelect8 => 7,
+            Action::QuickSelect9 => 8,
+            _ => return None,
+        };
+        Some(index)
+    }
+
+    /// The combo bound to this action when the config doesn't override it.
+    fn default_combo(self) -> &'static str {
+        match self {
+            Action::NavUp => "ArrowUp",
+            Action::NavDown => "ArrowDown",
+            Action::NavLeft => "ArrowLeft",
+            Action::NavRight => "ArrowRight",
+            Action::NavFirst => "Home",
+            Action::NavLast => "End",
+            Action::Select => "Enter",
+            Action::PasteSelected => "Ctrl+Enter",
+            Action::Hide => "Escape",
+            Action::ClearSearch => "Ctrl+U",
+            Action::ToggleEphemeral => "Ctrl+E",
+            Action::SplitEntry => "Ctrl+L",
+            Action::TogglePalette => "Ctrl+G",
+            Action::ToggleLock => "Ctrl+K",
+            Action::GrowWindow => "Ctrl+=",
+            Action::ShrinkWindow => "Ctrl+-",
+            Action::ToggleTemplate => "Ctrl+T",
+            Action::ToggleMark => "Ctrl+Space",
+            Action::ShowDiff => "Ctrl+D",
+            Action::ToggleGroup => "Ctrl+O",
+            Action::ToggleTrash => "Ctrl+Shift+D",
+            Action::DeleteFiltered => "Ctrl+Shift+Backspace",
+            Action::DeleteEntry => "Delete",
+            Action::ExportEntry => "Ctrl+S",
+            Action::TogglePin => "Ctrl+P",
+            Action::TogglePreview => "Ctrl+Shift+P",
+            Action::CycleSearchMode => "Ctrl+R",
+            Action::UndoDelete => "Ctrl+Z",
+            Action::PastePlain => "Shift+Enter",
+            Action::PasteKeepOpen => "Alt+Enter",
+            Action::ToggleCaseSensitive => "Alt+C",
+            Action::PasteUppercase => "Ctrl+Shift+U",
+            Action::PasteLowercase => "Ctrl+Shift+L",
+            Action::PasteTrimmed => "Ctrl+Shift+T",
+            Action::CycleListOrder => "Alt+S",
+            // Alt keeps plain digits free for typing into the search box
+            Action::QuickSelect1 => "Alt+1",
+            Action::QuickSelect2 => "Alt+2",
+            Action::QuickSelect3 => "Alt+3",
+            Action::QuickSelect4 => "Alt+4",
+            Action::QuickSelect5 => "Alt+5",
+            Action::QuickSelect6 => "Alt+6",
+            Action::QuickSelect7 => "Alt+7",
+            Action::QuickSelect8 => "Alt+8",
+            Action::QuickSelect9 => "Alt+9",
+        }
+    }
+}
+
+/// A key plus the exact set of modifiers that must be held with it.
+/// "Ctrl" means Cmd on macOS and Ctrl everywhere else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl KeyCombo {
+    /// Whether the search field would use this combo itself (moving the text
+    /// cursor, deleting, typing, undoing an edit). Such combos are only
+    /// treated as shortcuts while the search field has nothing to edit.
+    pub fn is_text_editing(&self) -> bool {
+        let is_undo = match self.key {
+            Key::Z => {
+                self.modifiers == Modifiers::COMMAND
+                    || self.modifiers == Modifiers::COMMAND | Modifiers::SHIFT
+            }
+            Key::Y => self.modifiers == Modifiers::COMMAND,
+            _ => false,
+        };
+        if is_undo {
+            return true;
+        }
+        if !self.modifiers.is_none() && self.modifiers != Modifiers::SHIFT {
+            return false;
+        }
+        let is_arrow = matches!(
+            self.key,
+            Key::ArrowUp | Key::ArrowDown | Key::ArrowLeft | Key::ArrowRight
+        );
+        // Letters, digits and punctuation all have single-character symbols
+        let types_char = !is_arrow && self.key.symbol_or_name().chars().count() == 1;
+        types_char
+            || matches!(
+                self.key,
+                Key::Home
+                    | Key::End
+                    | Key::ArrowLeft
+                    | Key::ArrowRight
+                    | Key::Backspace
+                    | Key::Delete
+                    | Key::Space
+            )
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = KeymapError;
+
+    /// Parse combos like `"Enter"`, `"Ctrl+U"` or `"Ctrl+Shift+Backspace"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let (key_name, modifier_names) = match parts.split_last() {
+            Some((key, mods)) if !key.is_empty() => (*key, mods),
+            _ => return Err(KeymapError::InvalidCombo(s.to_string())),
+        };
+
+        let mut modifiers = Modifiers::NONE;
+        for name in modifier_names {
+            let modifier = match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" | "cmd" | "command" => Modifiers::COMMAND,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                _ => return Err(KeymapError::InvalidCombo(s.to_string())),
+            };
+            if modifiers.contains(modifier) {
+                return Err(KeymapError::InvalidCombo(s.to_string()));
+            }
+            modifiers |= modifier;
+        }
+
+        let key = Key::from_name(key_name)
+            .ok_or_else(|| KeymapError::UnknownKey(key_name.to_string()))?;
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeymapError {
+    InvalidCombo(String),
+    UnknownKey(String),
+    Conflict {
+        combo: String,
+        first: Action,
+        second: Action,
+    },
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::InvalidCombo(s) => write!(f, "invalid key combo \"{s}\""),
+            KeymapError::UnknownKey(s) => write!(f, "unknown key \"{s}\""),
+            KeymapError::Conflict {
+                combo,
+                first,
+                second,
+            } => {
+                write!(f, "{combo} is bound to both {first:?} and {second:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Maps key combos to actions. Built from the defaults plus any per-action
+/// overrides from `Config::keybindings`.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeyCombo, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_overrides(&BTreeMap::new()).expect("default keybindings are valid")
+    }
+}
+
+impl Keymap {
+    /// Build a keymap from the defaults, replacing the combo of every action
+    /// listed in `overrides`; an empty combo unbinds the action. Fails if a
+    /// combo is malformed or two actions end up on the same combo.
+    pub fn from_overrides(overrides: &BTreeMap<Action, String>) -> Result<Self, KeymapError> {
+        let mut bindings: Vec<(KeyCombo, Action)> = Vec::with_capacity(Action::ALL.len());
+        for &action in Action::ALL {
+            let combo_str = overrides
+                .get(&action)
+                .map(String::as_str)
+                .unwrap_or_else(|| action.default_combo());
+            if combo_str.is_empty() {
+                continue;
+            }
+            let combo: KeyCombo = combo_str.parse()?;
+
+            if let Some((_, existing)) = bindings.iter().find(|(c, _)| *c == combo) {
+                return Err(KeymapError::Conflict {
+                    combo: combo.to_string(),
+                    first: *existing,
+                    second: action,
+                });
+            }
+            bindings.push((combo, action));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// The combo currently bound to `action`.
+    pub fn combo_for(&self, action: Action) -> Option<KeyCombo> {
+        self.bindings
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(c, _)| *c)
+    }
+
+    /// Consume this frame's key presses that match a binding and return the
+    /// triggered actions in the order they were pressed.
+    ///
+    /// When `text_input_active` is true, combos the search field uses for
+    /// editing (see [`KeyCombo::is_text_editing`]) are left for it. Keys
+    /// bound to an `inactive` action (one the current layout has no use
+    /// for) are left alone too.
+    pub fn resolve(
+        &self,
+        ctx: &egui::Context,
+        text_input_active: bool,
+        inactive: &[Action],
+    ) -> Vec<Action> {
+        let mut actions = Vec::new();
+        ctx.input_mut(|i| {
+            i.events.retain(|event| {
+                let egui::Event::Key {
+                    key,
+                    modifiers,
+                    pressed: true,
+                    ..
+                } = event
+                else {
+                    return true;
+                };
+                match self.lookup(*key, *modifiers, text_input_active) {
+                    Some(action) if !inactive.contains(&action) => {
+                        actions.push(action);
+                        false
+                    }
+                    _ => true,
+                }
+            });
+        });
+        actions
+    }
+
+    fn lookup(&self, key: Key, modifiers: Modifiers, text_input_active: bool) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(combo, _)| {
+                combo.key == key
+                    && modifiers.matches_exact(combo.modifiers)
+                    && !(text_input_active && combo.is_text_editing())
+            })
+            .map(|(_, action)| *action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_leaves_keys_of_inactive_actions() {
+        let keymap = Keymap::default();
+        let ctx = egui::Context::default();
+        let press = |key| egui::Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::NONE,
+        };
+        ctx.begin_pass(egui::RawInput {
+            events: vec![press(Key::ArrowLeft), press(Key::ArrowDown)],
+            ..Default::default()
+        });
+        let actions = keymap.resolve(&ctx, false, &[Action::NavLeft, Action::NavRight]);
+        assert_eq!(actions, vec![Action::NavDown]);
+        // ArrowLeft stays in the input for the widgets
+        assert_eq!(ctx.input(|i| i.events.len()), 1);
+        let _ = ctx.end_pass();
+    }
+
+    #[test]
+    fn test_parse_plain_key() {
+        let combo: KeyCombo = "Enter".parse().unwrap();
+        assert_eq!(combo.key, Key::Enter);
+        assert!(combo.modifiers.is_none());
+    }
+
+    #[test]
+    fn test_parse_modifiers() {
+        let combo: KeyCombo = "Ctrl+Shift+Backspace".parse().unwrap();
+        assert_eq!(combo.key, Key::Backspace);
+        assert_eq!(combo.modifiers, Modifiers::COMMAND | Modifiers::SHIFT);
+        assert_eq!(combo.to_string(), "Ctrl+Shift+Backspace");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "Ctrl+".parse::<KeyCombo>(),
+            Err(KeymapError::InvalidCombo("Ctrl+".into()))
+        );
+        assert_eq!(
+            "Hyper+A".parse::<KeyCombo>(),
+            Err(KeymapError::InvalidCombo("Hyper+A".into()))
+        );
+        assert_eq!(
+            "Ctrl+Ctrl+A".parse::<KeyCombo>(),
+            Err(KeymapError::InvalidCombo("Ctrl+Ctrl+A".into()))
+        );
+        assert_eq!(
+            "Ctrl+Banana".parse::<KeyCombo>(),
+            Err(KeymapError::UnknownKey("Banana".into()))
+        );
+    }
+
+    #[test]
+    fn test_default_keymap_has_every_action() {
+        let keymap = Keymap::default();
+        for &action in Action::ALL {
+            assert!(keymap.combo_for(action).is_some(), "{action:?} unbound");
+        }
+    }
+
+    #[test]
+    fn test_override_replaces_default() {
+        let overrides = BTreeMap::from([(Action::Select, "Ctrl+J".to_string())]);
+        let keymap = Keymap::from_overrides(&overrides).unwrap();
+        assert_eq!(
+            keymap.lookup(Key::J, Modifiers::COMMAND, false),
+            Some(Action::Select)
+        );
+        assert_eq!(keymap.lookup(Key::Enter, Modifiers::NONE, false), None);
+    }
+
+    #[test]
+    fn test_quick_select_bindings() {
+        let keymap = Keymap::default();
+        let pick = |key| {
+            keymap
+                .lookup(key, Modifiers::ALT, false)
+                .and_then(Action::quick_select_index)
+        };
+        assert_eq!(pick(Key::Num1), Some(0));
+        assert_eq!(pick(Key::Num3), Some(2));
+        assert_eq!(pick(Key::Num9), Some(8));
+        assert_eq!(pick(Key::Num0), None);
+        // Plain digits are typed into the search box
+        assert_eq!(keymap.lookup(Key::Num3, Modifiers::NONE, false), None);
+        assert_eq!(
+            keymap.lookup(Key::Num3, Modifiers::ALT | Modifiers::SHIFT, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_empty_override_unbinds() {
+        let overrides = BTreeMap::from([
+            (Action::QuickSelect1, String::new()),
+            (Action::QuickSelect2, "Ctrl+1".to_string()),
+        ]);
+        let keymap = Keymap::from_overrides(&overrides).unwrap();
+        assert_eq!(keymap.combo_for(Action::QuickSelect1), None);
+        assert_eq!(keymap.lookup(Key::Num1, Modifiers::ALT, false), None);
+        assert_eq!(
+            keymap.lookup(Key::Num1, Modifiers::COMMAND, false),
+            Some(Action::QuickSelect2)
+        );
+    }
+
+    #[test]
+    fn test_conflict_detected() {
+        let overrides = BTreeMap::from([(Action::ClearSearch, "Escape".to_string())]);
+        let err = Keymap::from_overrides(&overrides).unwrap_err();
+        assert_eq!(
+            err,
+            KeymapError::Conflict {
+                combo: "Escape".into(),
+                first: Action::Hide,
+                second: Action::ClearSearch,
+            }
+        );
+    }
+
+    #[test]
+    fn test_modifiers_must_match_exactly() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.lookup(Key::Enter, Modifiers::NONE, false),
+            Some(Action::Select)
+        );
+        assert_eq!(
+            keymap.lookup(Key::Enter, Modifiers::SHIFT, false),
+            Some(Action::PastePlain)
+        );
+        assert_eq!(
+            keymap.lookup(Key::Enter, Modifiers::ALT, false),
+            Some(Action::PasteKeepOpen)
+        );
+        assert_eq!(
+            keymap.lookup(Key::Enter, Modifiers::CTRL | Modifiers::ALT, false),
+            None
+        );
+        assert_eq!(keymap.lookup(Key::U, Modifiers::NONE, false), None);
+    }
+
+    #[test]
+    fn test_editing_keys_left_to_search_field() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.lookup(Key::Home, Modifiers::NONE, true), None);
+        assert_eq!(
+            keymap.lookup(Key::Home, Modifiers::NONE, false),
+            Some(Action::NavFirst)
+        );
+        // Navigation and modifier shortcuts still work while typing
+        assert_eq!(
+            keymap.lookup(Key::ArrowDown, Modifiers::NONE, true),
+            Some(Action::NavDown)
+        );
+        assert_eq!(
+            keymap.lookup(Key::U, Modifiers::COMMAND, true),
+            Some(Action::ClearSearch)
+        );
+        // Ctrl+Z undoes typing in the query, and deletes once it's empty
+        assert_eq!(keymap.lookup(Key::Z, Modifiers::COMMAND, true), None);
+        assert_eq!(
+            keymap.lookup(Key::Z, Modifiers::COMMAND, false),
+            Some(Action::UndoDelete)
+        );
+    }
+}
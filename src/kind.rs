@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+
+/// What an entry looks like, detected when it is captured. The search bar's
+/// filter chips narrow the results to one kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentKind {
+    #[default]
+    Text,
+    Url,
+    Email,
+    Code,
+}
+
+impl ContentKind {
+    /// Order of the filter chips.
+    pub const ALL: [ContentKind; 4] = [
+        ContentKind::Url,
+        ContentKind::Code,
+        ContentKind::Email,
+        ContentKind::Text,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContentKind::Text => "Text",
+            ContentKind::Url => "URL",
+            ContentKind::Email => "Email",
+            ContentKind::Code => "Code",
+        }
+    }
+
+    /// Short chip text for the search bar.
+    pub fn icon(self) -> &'static str {
+        match self {
+            ContentKind::Text => "¶",
+            ContentKind::Url => "🔗",
+            ContentKind::Email => "✉",
+            ContentKind::Code => "{ }",
+        }
+    }
+}
+
+/// Guess the kind of `content`. Cheap enough to run on every capture.
+pub fn classify(content: &str) -> ContentKind {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return ContentKind::Text;
+    }
+    if !trimmed.contains(char::is_whitespace) {
+        if is_url(trimmed) {
+            return ContentKind::Url;
+        }
+        if is_email(trimmed.strip_prefix("mailto:").unwrap_or(trimmed)) {
+            return ContentKind::Email;
+        }
+    }
+    if code_signals(content) >= 2 {
+        ContentKind::Code
+    } else {
+        ContentKind::Text
+    }
+}
+
+/// A `scheme://` prefix, or a bare `www.` host.
+fn is_url(token: &str) -> bool {
+    if let Some((scheme, rest)) = token.split_once("://") {
+        let mut chars = scheme.chars();
+        return chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            && !rest.is_empty();
+    }
+    token
+        .get(..4)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("www."))
+        && token[4..].contains('.')
+}
+
+/// `local@domain.tld`, with one `@` and a dot inside the domain.
+fn is_email(token: &str) -> bool {
+    let Some((local, domain)) = token.split_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || "._%+-".contains(c));
+    let domain_ok = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+        && domain
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-');
+    local_ok && domain_ok
+}
+
+/// How many independent hints of source code `content` has: braces, lines
+/// ending in `;`, indented lines, keywords starting a line and operators.
+fn code_signals(content: &str) -> usize {
+    const KEYWORDS: [&str; 16] = [
+        "fn ",
+        "def ",
+        "function ",
+        "class ",
+        "import ",
+        "from ",
+        "#include",
+        "let ",
+        "const ",
+        "var ",
+        "return ",
+        "if (",
+        "for (",
+        "while (",
+        "pub ",
+        "use ",
+    ];
+    const OPERATORS: [&str; 6] = ["=>", "->", "==", "!=", "&&", "::"];
+
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    let braces = content.contains('{') && content.contains('}');
+    let semicolons = lines.iter().any(|l| l.trim_end().ends_with(';'));
+    let indented = lines.len() > 1
+        && lines
+            .iter()
+            .skip(1)
+            .any(|l| l.starts_with('\t') || l.starts_with("  "));
+    let keywords = lines.iter().any(|l| {
+        let l = l.trim_start();
+        KEYWORDS.iter().any(|k| l.starts_with(k))
+    });
+    let operators = OPERATORS.iter().any(|op| content.contains(op));
+    [braces, semicolons, indented, keywords, operators]
+        .into_iter()
+        .filter(|&signal| signal)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls() {
+        for url in [
+            "https://example.com/path?q=1",
+            "http://localhost:8080",
+            "  ftp://files.example.org/pub\n",
+            "file:///home/me/notes.txt",
+            "www.example.com",
+        ] {
+            assert_eq!(classify(url), ContentKind::Url, "{url:?}");
+        }
+        for text in [
+            "see https://example.com for details",
+            "://nothing",
+            "www.",
+            "1a://x",
+            "wwwé.x",
+        ] {
+            assert_ne!(classify(text), ContentKind::Url, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_emails() {
+        for email in [
+            "jane.doe@example.com",
+            "mailto:support+tag@mail.example.co.uk",
+            " ops-team@host-1.example.org ",
+        ] {
+            assert_eq!(classify(email), ContentKind::Email, "{email:?}");
+        }
+        for text in [
+            "@handle",
+            "user@localhost",
+            "a@b@example.com",
+            "user@.example.com",
+            "user@example.com.",
+            "mail me at jane@example.com",
+        ] {
+            assert_ne!(classify(text), ContentKind::Email, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_code() {
+        for code in [
+            "fn main() {\n    println!(\"hi\");\n}",
+            "def greet(name):\n    return f\"hi {name}\"",
+            "let x = compute(a, b);",
+            "const total = items.reduce((a, b) => a + b, 0);",
+            "#include <stdio.h>\nint main(void) {\n\treturn 0;\n}",
+            "if (x != null) {\n  x.close();\n}",
+        ] {
+            assert_eq!(classify(code), ContentKind::Code, "{code:?}");
+        }
+    }
+
+    #[test]
+    fn test_plain_text() {
+        for text in [
+            "",
+            "   \n",
+            "Hello world",
+            "Meeting moved to 3pm; bring the slides",
+            "Dear team,\n  thanks for the update.",
+            "Let me know what you think.",
+            "Use the {name} placeholder",
+            "😀",
+        ] {
+            assert_eq!(classify(text), ContentKind::Text, "{text:?}");
+        }
+    }
+}
@@ -4,22 +4,50 @@
 mod app;
 mod clipboard;
 mod config;
+mod diff;
+mod doctor;
 mod fuzzy;
+mod highlight;
 mod history;
+mod hooks;
 mod hotkey;
+mod image;
+mod keymap;
+mod kind;
+mod pattern;
 mod platform;
+mod search;
 mod storage;
+mod template;
+mod text;
+mod transform;
 mod tray;
+mod url_title;
+mod watchdog;
 
 use std::sync::{Arc, Mutex};
 
 use eframe::egui;
 
 fn main() -> eframe::Result<()> {
-    let config = config::Config::default();
+    let config = config::load();
+    clipboard::configure(&config);
+    platform::configure(&config);
+    storage::configure(&config);
 
-    // Load history from disk
-    let history = storage::load(config.max_size);
+    if std::env::args().any(|arg| arg == "--doctor") {
+        platform::attach_parent_console();
+        std::process::exit(if doctor::run(&config) { 0 } else { 1 });
+    }
+
+    // Load history from disk (deferred to a background thread with lazy_load)
+    let history = if config.lazy_load {
+        let mut history = history::History::new(config.max_size);
+        history.apply_config(&config);
+        history
+    } else {
+        storage::load_configured(&config)
+    };
     let history = Arc::new(Mutex::new(history));
 
     // Shared visibility flag (start visible so user sees the window on first launch)
@@ -27,20 +55,28 @@ fn main() -> eframe::Result<()> {
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
+            .with_title(platform::window_title())
             .with_inner_size([config.window_width, config.window_height])
+            .with_min_inner_size([config.window_min_width, config.window_min_height])
             .with_decorations(false)
             .with_always_on_top(),
+        // The window is placed at the cursor on every show, so don't let
+        // eframe restore the last position.
+        persist_window: false,
         ..Default::default()
     };
 
+    // The app name also keys eframe's persisted state, so it stays fixed; the
+    // window title comes from the viewport above
     eframe::run_native(
         "Clipboard History",
         options,
-        Box::new(move |_cc| {
+        Box::new(move |cc| {
             Ok(Box::new(app::ClipboardHistoryApp::new(
                 Arc::clone(&history),
                 Arc::clone(&visible),
                 config.clone(),
+                cc.storage,
             )))
         }),
     )
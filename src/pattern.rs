@@ -0,0 +1,524 @@
+use std::fmt;
+
+/// A small regular expression engine for the Regex search mode.
+///
+/// Supports literals, `.`, character classes (`[a-z]`, `[^0-9]`), the
+/// escapes `\d \w \s` (and `\D \W \S`), the quantifiers `*`, `+` and `?`,
+/// anchors `^` and `$`, groups `(...)` / `(?:...)` and alternation `|`.
+/// Matching is case-sensitive and finds the pattern anywhere in the text.
+///
+/// Patterns compile to an NFA that is run over the text in lockstep (a Pike
+/// VM), so matching never recurses and takes time linear in the text, with
+/// a step budget as a backstop for huge entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+/// Steps one [`Regex::is_match`] may take before giving up on a text.
+const MAX_STEPS: usize = 50_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternError(String);
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pattern: {}", self.0)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Char(char),
+    /// `.`: any character but a newline.
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    /// Alternatives, each a sequence.
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    /// `\d`, `\w`, `\s` or their negations, by letter.
+    Escape(char),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(lo, hi) => (lo..=hi).contains(&c),
+            ClassItem::Escape(letter) => {
+                let hit = match letter.to_ascii_lowercase() {
+                    'd' => c.is_ascii_digit(),
+                    'w' => c.is_alphanumeric() || c == '_',
+                    _ => c.is_whitespace(),
+                };
+                hit == letter.is_ascii_lowercase()
+            }
+        }
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, PatternError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let root = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(PatternError("unmatched )".into()));
+        }
+        let mut program = Vec::new();
+        compile_alternatives(&root, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// Whether the pattern matches anywhere in `text`, or an error if that
+    /// takes more than [`MAX_STEPS`] to decide.
+    pub fn is_match(&self, text: &str) -> Result<bool, PatternError> {
+        self.is_match_within(text, MAX_STEPS)
+    }
+
+    fn is_match_within(&self, text: &str, budget: usize) -> Result<bool, PatternError> {
+        let mut vm = Vm {
+            program: &self.program,
+            text_len: text.len(),
+            steps: 0,
+            budget,
+            stack: Vec::new(),
+        };
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut at = 0;
+        let mut chars = text.chars();
+        loop {
+            // A fresh thread at every offset finds matches anywhere
+            if vm.add(&mut current, 0, at)? {
+                return Ok(true);
+            }
+            let Some(c) = chars.next() else {
+                return Ok(false);
+            };
+            at += c.len_utf8();
+            next.clear();
+            for &pc in &current.pcs {
+                vm.step()?;
+                if self.program[pc].accepts(c) && vm.add(&mut next, pc + 1, at)? {
+                    return Ok(true);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Sequences separated by `|`, up to a `)` or the end.
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, PatternError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, PatternError> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                _ => {
+                    nodes.push(atom);
+                    continue;
+                }
+            };
+            self.pos += 1;
+            if matches!(atom, Node::Start | Node::End) {
+                return Err(PatternError("nothing to repeat".into()));
+            }
+            nodes.push(Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+            });
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let c = self.next().expect("called with input left");
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '*' | '+' | '?' => return Err(PatternError(format!("nothing to repeat before {c}"))),
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let group = self.alternatives()?;
+                if self.next() != Some(')') {
+                    return Err(PatternError("unclosed (".into()));
+                }
+                Node::Group(group)
+            }
+            '[' => self.class()?,
+            '\\' => match self.escape()? {
+                ClassItem::Range(c, _) => Node::Char(c),
+                item => Node::Class {
+                    items: vec![item],
+                    negated: false,
+                },
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    /// The character after a `\`: a class escape, or an escaped literal.
+    fn escape(&mut self) -> Result<ClassItem, PatternError> {
+        match self.next() {
+            Some(c @ ('d' | 'D' | 'w' | 'W' | 's' | 'S')) => Ok(ClassItem::Escape(c)),
+            Some('n') => Ok(ClassItem::Range('\n', '\n')),
+            Some('t') => Ok(ClassItem::Range('\t', '\t')),
+            Some(c) => Ok(ClassItem::Range(c, c)),
+            None => Err(PatternError("trailing \\".into())),
+        }
+    }
+
+    /// A `[...]` class, after the `[`.
+    fn class(&mut self) -> Result<Node, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        loop {
+            let item = match self.next() {
+                None => return Err(PatternError("unclosed [".into())),
+                // A leading ] is a literal
+                Some(']') if !items.is_empty() => break,
+                Some('\\') => self.escape()?,
+                Some(c) => ClassItem::Range(c, c),
+            };
+            let is_range = self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']');
+            match item {
+                ClassItem::Range(lo, _) if is_range => {
+                    self.pos += 1;
+                    let hi = match self.next() {
+                        Some('\\') => match self.escape()? {
+                            ClassItem::Range(hi, _) => hi,
+                            ClassItem::Escape(_) => {
+                                return Err(PatternError("class escape as range end".into()))
+                            }
+                        },
+                        Some(hi) => hi,
+                        None => return Err(PatternError("unclosed [".into())),
+                    };
+                    if hi < lo {
+                        return Err(PatternError(format!("range {lo}-{hi} out of order")));
+                    }
+                    items.push(ClassItem::Range(lo, hi));
+                }
+                item => items.push(item),
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+}
+
+/// One step of a compiled pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    /// Continue at both targets.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+impl Inst {
+    /// Whether this character-consuming step accepts `c`.
+    fn accepts(&self, c: char) -> bool {
+        match self {
+            Inst::Char(expected) => c == *expected,
+            Inst::Any => c != '\n',
+            Inst::Class { items, negated } => items.iter().any(|item| item.matches(c)) != *negated,
+            _ => false,
+        }
+    }
+}
+
+fn compile_alternatives(alternatives: &[Vec<Node>], program: &mut Vec<Inst>) {
+    let (last, rest) = alternatives.split_last().expect("at least one alternative");
+    let mut jumps = Vec::new();
+    for sequence in rest {
+        let split = program.len();
+        program.push(Inst::Split(split + 1, 0));
+        compile_sequence(sequence, program);
+        jumps.push(program.len());
+        program.push(Inst::Jump(0));
+        program[split] = Inst::Split(split + 1, program.len());
+    }
+    compile_sequence(last, program);
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+}
+
+fn compile_sequence(nodes: &[Node], program: &mut Vec<Inst>) {
+    for node in nodes {
+        compile_node(node, program);
+    }
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class { items, negated } => program.push(Inst::Class {
+            items: items.clone(),
+            negated: *negated,
+        }),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(alternatives) => compile_alternatives(alternatives, program),
+        Node::Repeat { node, min, max } => {
+            for copy in 0..*min {
+                let start = program.len();
+                compile_node(node, program);
+                // `x+`: loop back over the last required copy
+                if copy + 1 == *min && max.is_none() {
+                    program.push(Inst::Split(start, program.len() + 1));
+                }
+            }
+            match max {
+                None if *min == 0 => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile_node(node, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                None => {}
+                Some(max) => {
+                    for _ in *min..*max {
+                        let split = program.len();
+                        program.push(Inst::Split(split + 1, 0));
+                        compile_node(node, program);
+                        program[split] = Inst::Split(split + 1, program.len());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The live program positions at one point in the text.
+struct Threads {
+    /// Positions waiting to consume the next character.
+    pcs: Vec<usize>,
+    /// The `generation` in which each position was last visited.
+    seen: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(size: usize) -> Threads {
+        Threads {
+            pcs: Vec::with_capacity(size),
+            seen: vec![0; size],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pcs.clear();
+        self.generation += 1;
+    }
+}
+
+/// Runs a compiled program over one text, counting steps against a budget.
+struct Vm<'a> {
+    program: &'a [Inst],
+    text_len: usize,
+    steps: usize,
+    budget: usize,
+    stack: Vec<usize>,
+}
+
+impl Vm<'_> {
+    fn step(&mut self) -> Result<(), PatternError> {
+        self.steps += 1;
+        if self.steps > self.budget {
+            return Err(PatternError(format!(
+                "gave up after {} steps on a {} byte entry",
+                self.budget, self.text_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Add `pc` and everything reachable from it without consuming a
+    /// character at byte offset `at`. Returns whether that reaches a match.
+    fn add(&mut self, threads: &mut Threads, pc: usize, at: usize) -> Result<bool, PatternError> {
+        self.stack.push(pc);
+        while let Some(pc) = self.stack.pop() {
+            if threads.seen[pc] == threads.generation {
+                continue;
+            }
+            self.step()?;
+            threads.seen[pc] = threads.generation;
+            match self.program[pc] {
+                Inst::Match => {
+                    self.stack.clear();
+                    return Ok(true);
+                }
+                Inst::Jump(target) => self.stack.push(target),
+                Inst::Split(first, second) => {
+                    self.stack.push(second);
+                    self.stack.push(first);
+                }
+                Inst::Start if at == 0 => self.stack.push(pc + 1),
+                Inst::End if at == self.text_len => self.stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                _ => threads.pcs.push(pc),
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text).unwrap()
+    }
+
+    #[test]
+    fn test_literals_and_anchors() {
+        assert!(matches("world", "hello world"));
+        assert!(!matches("World", "hello world"));
+        assert!(matches("^hello", "hello world"));
+        assert!(!matches("^world", "hello world"));
+        assert!(matches("world$", "hello world"));
+        assert!(matches("", "anything"));
+        assert!(matches(r"a\.b", "a.b"));
+        assert!(!matches(r"a\.b", "axb"));
+    }
+
+    #[test]
+    fn test_quantifiers_and_classes() {
+        assert!(matches(r"^\d+$", "12345"));
+        assert!(!matches(r"^\d+$", "12a45"));
+        assert!(matches("colou?r", "color"));
+        assert!(matches("colou?r", "colour"));
+        assert!(matches("^ab*c$", "ac"));
+        assert!(matches("^ab*c$", "abbbc"));
+        assert!(matches("^[a-f0-9]+$", "deadbeef42"));
+        assert!(!matches("^[^0-9]+$", "abc1"));
+        assert!(matches(r"^\w+@\w+\.com$", "me@example.com"));
+        assert!(matches(r"a\sb", "a b"));
+        assert!(matches("^a.c$", "abc"));
+        assert!(!matches("^a.c$", "a\nc"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("^[a-]+$", "a-a"));
+    }
+
+    #[test]
+    fn test_groups_and_alternation() {
+        assert!(matches("^(cat|dog)s?$", "dogs"));
+        assert!(!matches("^(cat|dog)s?$", "cow"));
+        assert!(matches(
+            "https?://(?:www\\.)?example",
+            "http://www.example.org"
+        ));
+        assert!(matches("^(ab)+$", "ababab"));
+        assert!(!matches("^(ab)+$", "aba"));
+        assert!(matches("^(a*)+$", ""));
+        assert!(matches("^(a|b)*c", "abbac"));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        assert!(matches("^a*ab$", "aaab"));
+        assert!(matches("^.*foo$", "xfoofoo"));
+        assert!(matches("^(a|ab)c$", "abc"));
+    }
+
+    #[test]
+    fn test_long_inputs_and_nested_quantifiers() {
+        let long = format!("h{}", "x".repeat(200_000));
+        assert!(matches("h.*", &long));
+        assert!(matches("^h.*x$", &long));
+        assert!(!matches("^h.*y$", &long));
+
+        let xs = "x".repeat(5_000);
+        assert!(!matches("(x*)*y", &xs));
+        assert!(matches("(x*)*y", &format!("{xs}y")));
+        assert!(!matches("^(x+)+$", &format!("{xs}z")));
+        assert!(matches("^((a|b)*c)+$", "abcbac"));
+        assert!(matches("^(é|ü)+$", "éüé"));
+    }
+
+    #[test]
+    fn test_step_budget() {
+        let regex = Regex::new("(x*)*y").unwrap();
+        let text = "x".repeat(1_000);
+        assert!(regex.is_match_within(&text, 100).is_err());
+        assert_eq!(regex.is_match_within(&text, MAX_STEPS), Ok(false));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for invalid in ["(", "(ab", "ab)", "[ab", "*a", "a**", "^*", "\\", "[z-a]"] {
+            assert!(Regex::new(invalid).is_err(), "{invalid:?}");
+        }
+    }
+}
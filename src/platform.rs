@@ -1,3 +1,42 @@
+use std::sync::{mpsc, OnceLock};
+
+use crate::config::Config;
+
+/// Config values the native window helpers need. They run on the hotkey and
+/// tray threads, so the settings are captured once at startup instead of
+/// being threaded through every call.
+#[derive(Debug, Default)]
+#[cfg_attr(not(windows), allow(dead_code))]
+struct Settings {
+    follow_virtual_desktop: bool,
+    grab_focus_on_show: bool,
+    safe_mode: bool,
+    window_title: String,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Capture the platform-relevant parts of `config`. Call once from `main`.
+pub fn configure(config: &Config) {
+    let _ = SETTINGS.set(Settings {
+        follow_virtual_desktop: config.follow_virtual_desktop,
+        grab_focus_on_show: config.grab_focus_on_show,
+        safe_mode: config.safe_mode,
+        window_title: format!("{} ({})", config.window_title, std::process::id()),
+    });
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+/// The window title: `Config::window_title` plus the process id. Used for
+/// the viewport and to find the window again by title.
+pub fn window_title() -> &'static str {
+    &settings().window_title
+}
+
 /// On Windows, `ViewportCommand::Visible(true)` combined with
 /// `ctx.request_repaint()` is not sufficient to un-hide a window that was
 /// hidden via `ViewportCommand::Visible(false)`.  Win32 does not deliver
@@ -8,22 +47,36 @@
 /// `SetForegroundWindow` directly so that Windows delivers a `WM_PAINT`
 /// message, waking the event loop and allowing `update()` to run normally.
 ///
+/// With `Config::follow_virtual_desktop` the window is first moved to the
+/// virtual desktop the user is currently on.
+///
+/// With `Config::grab_focus_on_show` off, the window is shown with
+/// `SW_SHOWNOACTIVATE` and not brought to the foreground, so the current
+/// application keeps the keyboard.
+///
 /// On non-Windows platforms the egui repaint mechanism is sufficient, so this
-/// is a no-op.
+/// is a no-op. It is also a no-op with `Config::safe_mode`.
 pub fn show_window_native() {
     #[cfg(windows)]
     {
+        if settings().safe_mode {
+            return;
+        }
         use windows_sys::Win32::UI::WindowsAndMessaging::{
-            FindWindowW, SetForegroundWindow, ShowWindow, SW_SHOW,
+            SetForegroundWindow, ShowWindow, SW_SHOW, SW_SHOWNOACTIVATE,
         };
 
-        // Locate the main window by its title (set in eframe::run_native).
-        let title: Vec<u16> = "Clipboard History\0".encode_utf16().collect();
-        let hwnd = unsafe { FindWindowW(std::ptr::null(), title.as_ptr()) };
-        if hwnd != std::ptr::null_mut() {
+        if let Some(hwnd) = find_main_window() {
+            if settings().follow_virtual_desktop {
+                virtual_desktop::move_to_current(hwnd);
+            }
             unsafe {
-                ShowWindow(hwnd, SW_SHOW);
-                SetForegroundWindow(hwnd);
+                if settings().grab_focus_on_show {
+                    ShowWindow(hwnd, SW_SHOW);
+                    SetForegroundWindow(hwnd);
+                } else {
+                    ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+                }
             }
         }
     }
@@ -35,20 +88,579 @@ pub fn show_window_native() {
 /// window disappears instantly, preventing egui's black clear-color from
 /// flashing on screen during the hide transition.
 ///
-/// No-op on non-Windows platforms.
+/// No-op on non-Windows platforms and with `Config::safe_mode`.
 pub fn hide_window_native() {
     #[cfg(windows)]
     {
-        use windows_sys::Win32::UI::WindowsAndMessaging::{
-            FindWindowW, ShowWindow, SW_HIDE,
-        };
+        if settings().safe_mode {
+            return;
+        }
+        use windows_sys::Win32::UI::WindowsAndMessaging::{ShowWindow, SW_HIDE};
 
-        let title: Vec<u16> = "Clipboard History\0".encode_utf16().collect();
-        let hwnd = unsafe { FindWindowW(std::ptr::null(), title.as_ptr()) };
-        if hwnd != std::ptr::null_mut() {
+        if let Some(hwnd) = find_main_window() {
             unsafe {
                 ShowWindow(hwnd, SW_HIDE);
             }
         }
     }
 }
+
+/// A system sleep transition reported to `on_power_event` callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub enum PowerEvent {
+    Suspend,
+    Resume,
+}
+
+type PowerCallback = Box<dyn Fn(PowerEvent) + Send + Sync>;
+
+static POWER_CALLBACK: OnceLock<PowerCallback> = OnceLock::new();
+
+/// Call `callback` (on a system thread) when the machine is about to sleep
+/// and after it wakes up. Only the first registration takes effect.
+///
+/// Windows only; elsewhere the callback is never called (Linux would need
+/// D-Bus for logind's `PrepareForSleep` signal).
+pub fn on_power_event(callback: impl Fn(PowerEvent) + Send + Sync + 'static) {
+    let first = POWER_CALLBACK.set(Box::new(callback)).is_ok();
+    #[cfg(windows)]
+    if first {
+        power::register();
+    }
+    #[cfg(not(windows))]
+    let _ = first;
+}
+
+/// Whether our window is the foreground window, so a copy made now came
+/// from it. Always false on other platforms.
+pub fn own_window_is_foreground() -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+        let foreground = unsafe { GetForegroundWindow() };
+        find_main_window().is_some_and(|hwnd| hwnd == foreground)
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Whether the point (in physical pixels) is on a connected monitor. `None`
+/// where that isn't known (other platforms).
+pub fn point_on_monitor(x: f32, y: f32) -> Option<bool> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::POINT;
+        use windows_sys::Win32::Graphics::Gdi::{MonitorFromPoint, MONITOR_DEFAULTTONULL};
+
+        let point = POINT {
+            x: x as i32,
+            y: y as i32,
+        };
+        Some(!unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONULL) }.is_null())
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (x, y);
+        None
+    }
+}
+
+/// Keep a spawned command from opening a console window on Windows, where
+/// the app itself has none. No-op elsewhere.
+pub fn hide_console(command: &mut std::process::Command) {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    #[cfg(not(windows))]
+    let _ = command;
+}
+
+/// Attach to the console of the shell that launched us, so command-line modes
+/// like `--doctor` can print. The GUI executable has no console of its own on
+/// Windows; no-op elsewhere.
+pub fn attach_parent_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Read the text entries of the Windows clipboard history (Win+V), oldest
+/// first. Returns an empty list when clipboard history is disabled, the API is
+/// unavailable, and on other platforms.
+pub fn read_system_clipboard_history() -> Vec<String> {
+    #[cfg(windows)]
+    {
+        // On a thread of its own: this runs at startup on the thread that
+        // later owns the event loop, and joining the MTA there would make
+        // winit's OleInitialize (for drag and drop) fail when it creates the
+        // window.
+        let read = std::thread::spawn(system_history::read).join();
+        match read {
+            Ok(Ok(texts)) => texts,
+            Ok(Err(e)) => {
+                eprintln!("Failed to read system clipboard history: {e}");
+                Vec::new()
+            }
+            Err(_) => {
+                eprintln!("Reading system clipboard history panicked");
+                Vec::new()
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(windows)]
+mod system_history {
+    use windows::ApplicationModel::DataTransfer::{
+        Clipboard, ClipboardHistoryItemsResultStatus, StandardDataFormats,
+    };
+    use windows_sys::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+
+    /// Only call on a thread of its own; it joins and leaves the MTA.
+    pub fn read() -> windows::core::Result<Vec<String>> {
+        let initialized =
+            unsafe { CoInitializeEx(std::ptr::null(), COINIT_MULTITHREADED as u32) } >= 0;
+        let texts = read_texts();
+        if initialized {
+            unsafe { CoUninitialize() };
+        }
+        texts
+    }
+
+    fn read_texts() -> windows::core::Result<Vec<String>> {
+        let result = Clipboard::GetHistoryItemsAsync()?.get()?;
+        if result.Status()? != ClipboardHistoryItemsResultStatus::Success {
+            return Ok(Vec::new());
+        }
+
+        let text_format = StandardDataFormats::Text()?;
+        let mut texts = Vec::new();
+        for item in result.Items()? {
+            let content = item.Content()?;
+            if content.Contains(&text_format)? {
+                texts.push(content.GetTextAsync()?.get()?.to_string());
+            }
+        }
+        // The API lists the newest item first
+        texts.reverse();
+        Ok(texts)
+    }
+}
+
+/// Whether the app that put the current clipboard content there asked for it
+/// to be kept out of clipboard history, as password managers do with the
+/// `ExcludeClipboardContentFromMonitorProcessing` and `Clipboard Viewer
+/// Ignore` formats or a `CanIncludeInClipboardHistory` value of 0. Always
+/// false on other platforms.
+pub fn clipboard_excluded_from_history() -> bool {
+    #[cfg(windows)]
+    {
+        exclusion::marked()
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+#[cfg(windows)]
+mod exclusion {
+    use windows_sys::Win32::System::DataExchange::{
+        CloseClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+        RegisterClipboardFormatW,
+    };
+    use windows_sys::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+
+    fn format(name: &str) -> u32 {
+        let name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        unsafe { RegisterClipboardFormatW(name.as_ptr()) }
+    }
+
+    fn available(format: u32) -> bool {
+        format != 0 && unsafe { IsClipboardFormatAvailable(format) } != 0
+    }
+
+    pub fn marked() -> bool {
+        if available(format("ExcludeClipboardContentFromMonitorProcessing"))
+            || available(format("Clipboard Viewer Ignore"))
+        {
+            return true;
+        }
+        let can_include = format("CanIncludeInClipboardHistory");
+        available(can_include) && read_dword(can_include) == Some(0)
+    }
+
+    /// The DWORD stored under `format`, or `None` if the clipboard is busy or
+    /// holds less than four bytes there (the content is then captured as usual).
+    fn read_dword(format: u32) -> Option<u32> {
+        unsafe {
+            if OpenClipboard(std::ptr::null_mut()) == 0 {
+                return None;
+            }
+            let handle = GetClipboardData(format);
+            let mut value = None;
+            // Any app can put any size of data under the format
+            if !handle.is_null() && GlobalSize(handle) >= std::mem::size_of::<u32>() {
+                let data = GlobalLock(handle) as *const u32;
+                if !data.is_null() {
+                    value = Some(data.read_unaligned());
+                    GlobalUnlock(handle);
+                }
+            }
+            CloseClipboard();
+            value
+        }
+    }
+}
+
+/// Call `tick` every `interval` on the calling (UI) thread, also while the
+/// window is hidden and egui runs no frames (see [`show_window_native`]).
+/// Windows only: a thread timer, which winit's message loop dispatches.
+/// Elsewhere `tick` is never called and the caller relies on `update()`.
+pub fn every_ui_tick(interval: std::time::Duration, tick: impl FnMut() + 'static) {
+    #[cfg(windows)]
+    ui_timer::start(interval, Box::new(tick));
+    #[cfg(not(windows))]
+    let _ = (interval, tick);
+}
+
+#[cfg(windows)]
+mod ui_timer {
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    use windows_sys::Win32::Foundation::HWND;
+    use windows_sys::Win32::UI::WindowsAndMessaging::SetTimer;
+
+    /// A timer id and its callback.
+    type Tick = (usize, Box<dyn FnMut()>);
+
+    thread_local! {
+        /// The timers started on this thread, which only fire here.
+        static TICKS: RefCell<Vec<Tick>> = RefCell::new(Vec::new());
+    }
+
+    unsafe extern "system" fn timer_proc(_: HWND, _: u32, id: usize, _: u32) {
+        TICKS.with(|ticks| {
+            // Skipped if a tick is already running further up the stack
+            if let Ok(mut ticks) = ticks.try_borrow_mut() {
+                for (_, tick) in ticks.iter_mut().filter(|(timer, _)| *timer == id) {
+                    tick();
+                }
+            }
+        });
+    }
+
+    pub fn start(interval: Duration, tick: Box<dyn FnMut()>) {
+        let millis = interval.as_millis().clamp(1, u32::MAX.into()) as u32;
+        let id = unsafe { SetTimer(std::ptr::null_mut(), 0, millis, Some(timer_proc)) };
+        if id == 0 {
+            eprintln!("Failed to start a UI timer");
+            return;
+        }
+        TICKS.with(|ticks| ticks.borrow_mut().push((id, tick)));
+    }
+}
+
+/// A number that changes whenever the clipboard does, so the monitor can
+/// skip re-reading an unchanged clipboard. Windows only
+/// (`GetClipboardSequenceNumber`); `None` elsewhere.
+pub fn clipboard_sequence() -> Option<u32> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::DataExchange::GetClipboardSequenceNumber;
+        Some(unsafe { GetClipboardSequenceNumber() })
+    }
+    #[cfg(not(windows))]
+    {
+        None
+    }
+}
+
+/// A channel that receives a message whenever the clipboard changes, so the
+/// monitor can wait for changes instead of polling. Each call replaces the
+/// previous channel.
+///
+/// Windows only (`AddClipboardFormatListener`); `None` elsewhere or if the
+/// listener couldn't be registered, and the caller should poll.
+pub fn watch_clipboard() -> Option<mpsc::Receiver<()>> {
+    #[cfg(windows)]
+    {
+        clipboard_listener::watch()
+    }
+    #[cfg(not(windows))]
+    {
+        None
+    }
+}
+
+/// A hidden message-only window receiving `WM_CLIPBOARDUPDATE`, on its own
+/// thread for the lifetime of the process.
+#[cfg(windows)]
+mod clipboard_listener {
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::sync::{Mutex, OnceLock};
+    use std::thread;
+
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::System::DataExchange::AddClipboardFormatListener;
+    use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW,
+        HWND_MESSAGE, MSG, WM_CLIPBOARDUPDATE, WNDCLASSW,
+    };
+
+    use crate::clipboard::lock_recovering;
+
+    /// Where notifications go; swapped out when the monitor is restarted.
+    static SENDER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+    /// Whether the listener window was set up.
+    static STARTED: OnceLock<bool> = OnceLock::new();
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_CLIPBOARDUPDATE {
+            if let Some(sender) = &*lock_recovering(&SENDER) {
+                let _ = sender.send(());
+            }
+            return 0;
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    pub fn watch() -> Option<Receiver<()>> {
+        let started = *STARTED.get_or_init(|| {
+            let (ready_tx, ready_rx) = mpsc::channel();
+            thread::spawn(move || run(ready_tx));
+            ready_rx.recv().unwrap_or(false)
+        });
+        if !started {
+            return None;
+        }
+        let (sender, receiver) = mpsc::channel();
+        *lock_recovering(&SENDER) = Some(sender);
+        Some(receiver)
+    }
+
+    /// Create the window, report on `ready` whether that worked, then pump
+    /// its messages forever.
+    fn run(ready: Sender<bool>) {
+        let class_name: Vec<u16> = "ClipboardHistoryListener"
+            .encode_utf16()
+            .chain([0])
+            .collect();
+        unsafe {
+            let instance = GetModuleHandleW(std::ptr::null());
+            let mut class = std::mem::zeroed::<WNDCLASSW>();
+            class.lpfnWndProc = Some(window_proc);
+            class.hInstance = instance;
+            class.lpszClassName = class_name.as_ptr();
+            let hwnd = if RegisterClassW(&class) == 0 {
+                std::ptr::null_mut()
+            } else {
+                CreateWindowExW(
+                    0,
+                    class_name.as_ptr(),
+                    std::ptr::null(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    HWND_MESSAGE,
+                    std::ptr::null_mut(),
+                    instance,
+                    std::ptr::null(),
+                )
+            };
+            if hwnd.is_null() || AddClipboardFormatListener(hwnd) == 0 {
+                eprintln!("Failed to register for clipboard notifications; polling instead");
+                let _ = ready.send(false);
+                return;
+            }
+            let _ = ready.send(true);
+
+            let mut msg = std::mem::zeroed::<MSG>();
+            while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+}
+
+/// Locate the main window by its title (see `window_title`).
+#[cfg(windows)]
+fn find_main_window() -> Option<windows_sys::Win32::Foundation::HWND> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::FindWindowW;
+
+    let title: Vec<u16> = window_title().encode_utf16().chain([0]).collect();
+    let hwnd = unsafe { FindWindowW(std::ptr::null(), title.as_ptr()) };
+    (!hwnd.is_null()).then_some(hwnd)
+}
+
+/// Suspend/resume notifications through a callback, which needs no window
+/// to receive `WM_POWERBROADCAST` on.
+#[cfg(windows)]
+mod power {
+    use std::ffi::c_void;
+
+    use windows_sys::Win32::System::Power::{
+        PowerRegisterSuspendResumeNotification, DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        DEVICE_NOTIFY_CALLBACK, PBT_APMRESUMEAUTOMATIC, PBT_APMSUSPEND,
+    };
+
+    use super::{PowerEvent, POWER_CALLBACK};
+
+    unsafe extern "system" fn notify(
+        _context: *const c_void,
+        kind: u32,
+        _setting: *const c_void,
+    ) -> u32 {
+        let event = match kind {
+            PBT_APMSUSPEND => PowerEvent::Suspend,
+            // Sent on every wake, unlike PBT_APMRESUMESUSPEND which needs user input
+            PBT_APMRESUMEAUTOMATIC => PowerEvent::Resume,
+            _ => return 0,
+        };
+        if let Some(callback) = POWER_CALLBACK.get() {
+            callback(event);
+        }
+        0
+    }
+
+    pub fn register() {
+        // Registered for the lifetime of the process, so the parameters are leaked
+        let params = Box::leak(Box::new(DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+            Callback: Some(notify),
+            Context: std::ptr::null_mut(),
+        }));
+        let mut registration = std::ptr::null_mut();
+        let err = unsafe {
+            PowerRegisterSuspendResumeNotification(
+                DEVICE_NOTIFY_CALLBACK,
+                params as *mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS as *mut c_void,
+                &mut registration,
+            )
+        };
+        if err != 0 {
+            eprintln!("Failed to register for suspend/resume notifications: error {err}");
+        }
+    }
+}
+
+/// Minimal binding for the documented `IVirtualDesktopManager` COM interface.
+/// windows-sys has no COM interface types, so the vtable is declared by hand.
+#[cfg(windows)]
+mod virtual_desktop {
+    use std::ffi::c_void;
+
+    use windows_sys::core::{GUID, HRESULT};
+    use windows_sys::Win32::Foundation::{BOOL, HWND};
+    use windows_sys::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+        COINIT_APARTMENTTHREADED,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    const CLSID_VIRTUAL_DESKTOP_MANAGER: GUID =
+        GUID::from_u128(0xaa509086_5ca9_4c25_8f95_589d3c07b48a);
+    const IID_IVIRTUAL_DESKTOP_MANAGER: GUID =
+        GUID::from_u128(0xa5cd92ff_29be_454c_8d04_d82879fb3f1b);
+
+    #[repr(C)]
+    struct Manager {
+        vtbl: *const ManagerVtbl,
+    }
+
+    #[repr(C)]
+    struct ManagerVtbl {
+        _query_interface: usize,
+        _add_ref: usize,
+        release: unsafe extern "system" fn(*mut Manager) -> u32,
+        is_window_on_current_virtual_desktop:
+            unsafe extern "system" fn(*mut Manager, HWND, *mut BOOL) -> HRESULT,
+        get_window_desktop_id: unsafe extern "system" fn(*mut Manager, HWND, *mut GUID) -> HRESULT,
+        move_window_to_desktop:
+            unsafe extern "system" fn(*mut Manager, HWND, *const GUID) -> HRESULT,
+    }
+
+    /// COM on the calling thread, initialized on first use there and
+    /// uninitialized when the thread exits.
+    struct ComThread {
+        initialized: bool,
+    }
+
+    impl Drop for ComThread {
+        fn drop(&mut self) {
+            if self.initialized {
+                unsafe { CoUninitialize() };
+            }
+        }
+    }
+
+    thread_local! {
+        // Fails harmlessly if this thread already initialized COM differently
+        static COM: ComThread = ComThread {
+            initialized: unsafe {
+                CoInitializeEx(std::ptr::null(), COINIT_APARTMENTTHREADED as u32) >= 0
+            },
+        };
+    }
+
+    /// Move `hwnd` onto the virtual desktop of the current foreground window
+    /// if it isn't already on the active desktop. Silently does nothing when
+    /// the API is unavailable (Windows before 10, COM failures).
+    pub fn move_to_current(hwnd: HWND) {
+        COM.with(|_| {});
+        unsafe {
+            let mut manager: *mut c_void = std::ptr::null_mut();
+            let hr = CoCreateInstance(
+                &CLSID_VIRTUAL_DESKTOP_MANAGER,
+                std::ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IID_IVIRTUAL_DESKTOP_MANAGER,
+                &mut manager,
+            );
+            if hr < 0 || manager.is_null() {
+                return;
+            }
+            let manager = manager as *mut Manager;
+            let vtbl = &*(*manager).vtbl;
+
+            let mut on_current: BOOL = 1;
+            let hr = (vtbl.is_window_on_current_virtual_desktop)(manager, hwnd, &mut on_current);
+            if hr >= 0 && on_current == 0 {
+                let foreground = GetForegroundWindow();
+                let mut desktop = std::mem::zeroed::<GUID>();
+                if !foreground.is_null()
+                    && (vtbl.get_window_desktop_id)(manager, foreground, &mut desktop) >= 0
+                {
+                    (vtbl.move_window_to_desktop)(manager, hwnd, &desktop);
+                }
+            }
+
+            (vtbl.release)(manager);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::SearchMode;
+use crate::fuzzy;
+use crate::history::ClipboardEntry;
+use crate::pattern::PatternError;
+
+/// Histories with at least this many entries are searched on the worker
+/// thread; smaller ones are quick enough to search on the UI thread.
+pub const BACKGROUND_MIN_ENTRIES: usize = 2000;
+/// A query is only sent to the worker once it has been left alone this long.
+pub const DEBOUNCE: Duration = Duration::from_millis(120);
+
+/// Entry ids and contents. An entry's content only changes by growing (see
+/// `Config::collapse_incremental`), so a snapshot stays valid for as long as
+/// the history holds the same ids with the same content lengths. Contents
+/// are shared with the previous snapshot, so a new one only copies new
+/// entries.
+type Snapshot = Arc<Vec<(u64, Arc<str>)>>;
+
+struct Request {
+    query: String,
+    mode: SearchMode,
+    case_sensitive: bool,
+    snapshot: Snapshot,
+}
+
+/// A finished search: the query it was for and the matching ids with scores,
+/// best first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Done {
+    pub query: String,
+    pub mode: SearchMode,
+    pub case_sensitive: bool,
+    pub ranked: Result<Vec<(u64, i64)>, PatternError>,
+}
+
+/// Searches a snapshot of the history on a worker thread so typing stays
+/// responsive with large histories. The UI keeps showing the last finished
+/// results until the ones for the current query arrive.
+pub struct SearchWorker {
+    requests: Sender<Request>,
+    done: Arc<Mutex<Option<Done>>>,
+    snapshot: Snapshot,
+    /// Query, mode and case sensitivity last sent to the worker.
+    sent: Option<(String, SearchMode, bool)>,
+    /// Query, mode and case sensitivity waiting out [`DEBOUNCE`], and when
+    /// they were typed.
+    typed: Option<(String, SearchMode, bool, Instant)>,
+}
+
+impl SearchWorker {
+    /// Start the worker thread. `on_done` is called after each search
+    /// finishes, to repaint the window.
+    pub fn start(on_done: impl Fn() + Send + 'static) -> SearchWorker {
+        let (requests, rx) = mpsc::channel();
+        let done = Arc::new(Mutex::new(None));
+        let results = Arc::clone(&done);
+        thread::spawn(move || run(rx, &results, on_done));
+        SearchWorker {
+            requests,
+            done,
+            snapshot: Arc::default(),
+            sent: None,
+            typed: None,
+        }
+    }
+
+    /// Feed the current query. Sends it to the worker once it has been
+    /// unchanged for [`DEBOUNCE`], or right away if only `entries` changed.
+    /// Returns how long to wait before calling again while a query is
+    /// still being debounced.
+    pub fn update(
+        &mut self,
+        query: &str,
+        mode: SearchMode,
+        case_sensitive: bool,
+        entries: &[ClipboardEntry],
+        now: Instant,
+    ) -> Option<Duration> {
+        let same_ids = self.snapshot.len() == entries.len()
+            && self
+                .snapshot
+                .iter()
+                .zip(entries)
+                .all(|(snapped, e)| is_snapshot_of(snapped, e));
+        let already_sent = self
+            .sent
+            .as_ref()
+            .is_some_and(|(q, m, c)| q == query && *m == mode && *c == case_sensitive);
+        if !same_ids {
+            self.snapshot = Arc::new(refresh_snapshot(&self.snapshot, entries));
+            if already_sent {
+                self.send(query, mode, case_sensitive);
+            }
+        }
+        if already_sent {
+            return None;
+        }
+        match &self.typed {
+            Some((q, m, c, at)) if q == query && *m == mode && *c == case_sensitive => {
+                let waited = now.saturating_duration_since(*at);
+                if waited >= DEBOUNCE {
+                    self.send(query, mode, case_sensitive);
+                    None
+                } else {
+                    Some(DEBOUNCE - waited)
+                }
+            }
+            _ => {
+                self.typed = Some((query.to_string(), mode, case_sensitive, now));
+                Some(DEBOUNCE)
+            }
+        }
+    }
+
+    /// The most recently finished search, which may be for an older query.
+    pub fn latest(&self) -> Option<Done> {
+        self.done.lock().unwrap().clone()
+    }
+
+    fn send(&mut self, query: &str, mode: SearchMode, case_sensitive: bool) {
+        self.typed = None;
+        self.sent = Some((query.to_string(), mode, case_sensitive));
+        let request = Request {
+            query: query.to_string(),
+            mode,
+            case_sensitive,
+            snapshot: Arc::clone(&self.snapshot),
+        };
+        if self.requests.send(request).is_err() {
+            eprintln!("Search worker stopped; searching on the UI thread");
+        }
+    }
+}
+
+/// Whether a snapshot item still holds `entry`'s content.
+fn is_snapshot_of((id, content): &(u64, Arc<str>), entry: &ClipboardEntry) -> bool {
+    *id == entry.id && content.len() == entry.content.len()
+}
+
+/// A snapshot of `entries`, reusing the contents `old` already has.
+fn refresh_snapshot(old: &[(u64, Arc<str>)], entries: &[ClipboardEntry]) -> Vec<(u64, Arc<str>)> {
+    let known: HashMap<u64, &(u64, Arc<str>)> = old.iter().map(|item| (item.0, item)).collect();
+    entries
+        .iter()
+        .map(|entry| match known.get(&entry.id) {
+            Some(item) if is_snapshot_of(item, entry) => (entry.id, Arc::clone(&item.1)),
+            _ => (entry.id, Arc::from(entry.content.as_str())),
+        })
+        .collect()
+}
+
+fn run(requests: Receiver<Request>, done: &Mutex<Option<Done>>, on_done: impl Fn()) {
+    while let Ok(mut request) = requests.recv() {
+        // Only the newest query matters; skip any typed while busy
+        while let Ok(newer) = requests.try_recv() {
+            request = newer;
+        }
+        let ranked = search_snapshot(
+            &request.query,
+            &request.snapshot,
+            request.mode,
+            request.case_sensitive,
+        );
+        *done.lock().unwrap() = Some(Done {
+            query: request.query,
+            mode: request.mode,
+            case_sensitive: request.case_sensitive,
+            ranked,
+        });
+        on_done();
+    }
+}
+
+fn search_snapshot(
+    query: &str,
+    snapshot: &[(u64, Arc<str>)],
+    mode: SearchMode,
+    case_sensitive: bool,
+) -> Result<Vec<(u64, i64)>, PatternError> {
+    let ranked = fuzzy::rank(
+        query,
+        snapshot,
+        |(_, content)| content,
+        mode,
+        case_sensitive,
+    )?;
+    Ok(ranked
+        .into_iter()
+        .map(|((id, _), score)| (*id, score))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synthetic_history(len: u64) -> Vec<ClipboardEntry> {
+        (0..len)
+            .map(|id| {
+                let content = format!("entry {id}: fn handler_{} returns {}", id % 97, id * 31);
+                ClipboardEntry::for_test(id, content)
+            })
+            .collect()
+    }
+
+    /// Poll until the worker has finished a search for `query`.
+    fn wait_for(worker: &SearchWorker, query: &str) -> Done {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            if let Some(done) = worker.latest().filter(|done| done.query == query) {
+                return done;
+            }
+            assert!(Instant::now() < deadline, "no results for {query:?}");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_large_history_matches_synchronous_search() {
+        let entries = synthetic_history(20_000);
+        let mut worker = SearchWorker::start(|| {});
+        let start = Instant::now();
+        assert_eq!(
+            worker.update("handler_42", SearchMode::Fuzzy, false, &entries, start),
+            Some(DEBOUNCE)
+        );
+        assert_eq!(
+            worker.update(
+                "handler_42",
+                SearchMode::Fuzzy,
+                false,
+                &entries,
+                start + DEBOUNCE
+            ),
+            None
+        );
+        let done = wait_for(&worker, "handler_42");
+
+        let expected: Vec<(u64, i64)> = fuzzy::search("handler_42", &entries, false)
+            .into_iter()
+            .map(|(entry, score)| (entry.id, score))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(done.ranked, Ok(expected));
+    }
+
+    #[test]
+    fn test_debounce_restarts_on_each_keystroke() {
+        let entries = synthetic_history(10);
+        let mut worker = SearchWorker::start(|| {});
+        let start = Instant::now();
+        let half = DEBOUNCE / 2;
+        assert_eq!(
+            worker.update("e", SearchMode::Fuzzy, false, &entries, start),
+            Some(DEBOUNCE)
+        );
+        assert_eq!(
+            worker.update("en", SearchMode::Fuzzy, false, &entries, start + half),
+            Some(DEBOUNCE)
+        );
+        assert_eq!(
+            worker.update("en", SearchMode::Fuzzy, false, &entries, start + DEBOUNCE),
+            Some(DEBOUNCE - half)
+        );
+        assert_eq!(
+            worker.update(
+                "en",
+                SearchMode::Fuzzy,
+                false,
+                &entries,
+                start + half + DEBOUNCE
+            ),
+            None
+        );
+        assert_eq!(wait_for(&worker, "en").mode, SearchMode::Fuzzy);
+        assert!(worker.latest().is_some_and(|done| done.query == "en"));
+    }
+
+    #[test]
+    fn test_changed_history_is_searched_again() {
+        let mut entries = synthetic_history(10);
+        let mut worker = SearchWorker::start(|| {});
+        let start = Instant::now();
+        worker.update("brand new", SearchMode::Substring, false, &entries, start);
+        worker.update(
+            "brand new",
+            SearchMode::Substring,
+            false,
+            &entries,
+            start + DEBOUNCE,
+        );
+        assert_eq!(wait_for(&worker, "brand new").ranked, Ok(vec![]));
+
+        entries.insert(
+            0,
+            ClipboardEntry::for_test(99, "a brand new entry".to_string()),
+        );
+        // No debounce: the query itself didn't change
+        assert_eq!(
+            worker.update(
+                "brand new",
+                SearchMode::Substring,
+                false,
+                &entries,
+                start + DEBOUNCE
+            ),
+            None
+        );
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while worker
+            .latest()
+            .is_some_and(|done| done.ranked == Ok(vec![]))
+        {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(worker.latest().unwrap().ranked, Ok(vec![(99, 0)]));
+    }
+
+    #[test]
+    fn test_snapshot_shares_unchanged_contents() {
+        let entries = vec![
+            ClipboardEntry::for_test(2, "two".to_string()),
+            ClipboardEntry::for_test(1, "one".to_string()),
+        ];
+        let old = refresh_snapshot(&[], &entries);
+        // A new entry on top, and entry 2 grown in place
+        let entries = vec![
+            ClipboardEntry::for_test(3, "three".to_string()),
+            ClipboardEntry::for_test(2, "two more".to_string()),
+            ClipboardEntry::for_test(1, "one".to_string()),
+        ];
+        assert!(!is_snapshot_of(&old[0], &entries[1]));
+        let new = refresh_snapshot(&old, &entries);
+        let contents: Vec<&str> = new.iter().map(|(_, content)| &**content).collect();
+        assert_eq!(contents, vec!["three", "two more", "one"]);
+        assert!(Arc::ptr_eq(&new[2].1, &old[1].1));
+    }
+
+    #[test]
+    fn test_invalid_regex_reports_error() {
+        let entries = synthetic_history(10);
+        let mut worker = SearchWorker::start(|| {});
+        let start = Instant::now();
+        worker.update("(", SearchMode::Regex, false, &entries, start);
+        worker.update("(", SearchMode::Regex, false, &entries, start + DEBOUNCE);
+        assert!(wait_for(&worker, "(").ranked.is_err());
+    }
+}
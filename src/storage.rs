@@ -1,43 +1,460 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 
-use crate::history::History;
+use crate::config::{Config, StorageFormat};
+use crate::history::{ClipboardEntry, History};
 
-/// Get the path to the history JSON file.
-/// On Windows: %APPDATA%/clipboard-history/history.json
-/// On other platforms: uses dirs::config_dir() equivalent.
+static FORMAT: OnceLock<StorageFormat> = OnceLock::new();
+/// `Config::data_dir`, resolved.
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Environment variable that moves the config file and, unless
+/// `Config::data_dir` says otherwise, the history, e.g. for a portable
+/// install on a USB stick.
+pub const DIR_ENV: &str = "CLIPBOARD_HISTORY_DIR";
+
+/// Capture the storage-related parts of `config`. Call once from `main`,
+/// before the first `load`.
+pub fn configure(config: &Config) {
+    let _ = FORMAT.set(config.storage_format);
+    if let Some(dir) = &config.data_dir {
+        let _ = DATA_DIR.set(resolve_relative(dir.clone(), exe_dir().as_deref()));
+    }
+}
+
+fn format() -> StorageFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Directory holding `config.toml`: [`DIR_ENV`] if set, otherwise
+/// `clipboard-history` in the OS config dir (`%APPDATA%` on Windows).
+pub fn config_dir() -> PathBuf {
+    let env = std::env::var_os(DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    resolve_config_dir(env, exe_dir().as_deref(), dirs::config_dir())
+}
+
+/// Directory holding the history file: `Config::data_dir` if set,
+/// otherwise the [`config_dir`].
+fn data_dir() -> PathBuf {
+    DATA_DIR.get().cloned().unwrap_or_else(config_dir)
+}
+
+/// [`config_dir`] for the given `env` override and OS config dir, falling
+/// back to the working directory if the OS has none.
+fn resolve_config_dir(
+    env: Option<PathBuf>,
+    exe_dir: Option<&Path>,
+    os_config_dir: Option<PathBuf>,
+) -> PathBuf {
+    match env {
+        Some(dir) => resolve_relative(dir, exe_dir),
+        None => os_config_dir
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("clipboard-history"),
+    }
+}
+
+/// Relative override paths are taken from the executable's directory, so a
+/// portable install keeps working wherever it is mounted.
+fn resolve_relative(dir: PathBuf, exe_dir: Option<&Path>) -> PathBuf {
+    match exe_dir {
+        Some(base) if dir.is_relative() => base.join(dir),
+        _ => dir,
+    }
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.parent()?.to_path_buf())
+}
+
+fn history_file(dir: &Path, format: StorageFormat) -> PathBuf {
+    dir.join(format!("history.{}", format.extension()))
+}
+
+/// Get the path to the history file, e.g. `history.json` for the default format.
 pub fn history_path() -> PathBuf {
-    let config_dir = dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("clipboard-history");
-    config_dir.join("history.json")
+    history_file(&data_dir(), format())
 }
 
-/// Load history from JSON file. Returns empty history if file doesn't exist or is corrupted.
+/// Load history from disk. Returns empty history if file doesn't exist or is corrupted.
+/// If the file only exists in another format (the format setting was changed),
+/// it is migrated to the configured one.
 pub fn load(max_size: usize) -> History {
-    let path = history_path();
+    let dir = data_dir();
+    let format = format();
+    let path = history_file(&dir, format);
+    if !path.exists() {
+        if let Some(history) = migrate(&dir, format) {
+            return history;
+        }
+    }
     match fs::read_to_string(&path) {
-        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|_| History::new(max_size)),
+        Ok(data) => deserialize(&data, format).unwrap_or_else(|_| History::new(max_size)),
         Err(_) => History::new(max_size),
     }
 }
 
-/// Save history to JSON file. Creates parent directories if needed.
+/// Load history and run the startup steps that depend on `config`: the
+/// backup, applying its runtime settings, the one-time system history
+/// import, the trash sweep, expiry and compaction.
+pub fn load_configured(config: &Config) -> History {
+    if config.max_backups > 0 {
+        if let Err(e) = backup(config.max_backups) {
+            eprintln!("Failed to back up history: {e}");
+        }
+    }
+    let mut history = load(config.max_size);
+    history.apply_config(config);
+    if config.import_system_history_once {
+        import_system_history_once(&mut history);
+    }
+    let mut removed = history.purge_trash();
+    removed += history.prune_expired(chrono::Utc::now());
+    if let Some(days) = config.compact_after_days {
+        let older_than = Duration::from_secs(days.saturating_mul(86_400));
+        removed += history.compact(older_than, config.compact_only_unused);
+    }
+    if removed > 0 {
+        if let Err(e) = save(&history) {
+            eprintln!("Failed to save compacted history: {e}");
+        }
+    }
+    history
+}
+
+/// Save history to disk in the configured format. Creates parent directories if needed.
 pub fn save(history: &History) -> Result<(), Box<dyn std::error::Error>> {
+    write_history(&history_path(), format(), history)
+}
+
+fn backup_dir() -> PathBuf {
+    data_dir().join("backups")
+}
+
+/// Copy the history file into the backups folder as
+/// `history-YYYYMMDD-HHMMSS.<ext>`, then prune to the newest `max_backups`.
+/// Does nothing if there is no history file yet.
+pub fn backup(max_backups: usize) -> io::Result<()> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(());
+    }
+    let dir = backup_dir();
+    fs::create_dir_all(&dir)?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    fs::copy(
+        &path,
+        dir.join(format!("history-{stamp}.{}", format().extension())),
+    )?;
+    prune_backups(max_backups).map(drop)
+}
+
+/// Delete all but the newest `max_backups` backups. Returns how many were deleted.
+pub fn prune_backups(max_backups: usize) -> io::Result<usize> {
+    prune_backups_in(&backup_dir(), max_backups)
+}
+
+fn prune_backups_in(dir: &Path, max_backups: usize) -> io::Result<usize> {
+    // Timestamped names sort oldest first
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("history-"))
+        })
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(max_backups);
+    for path in &backups[..excess] {
+        fs::remove_file(path)?;
+    }
+    Ok(excess)
+}
+
+/// Check that the history file can be written by saving a probe file next to
+/// it, the same way `save` writes. Returns the history file's path.
+pub fn check_writable() -> io::Result<PathBuf> {
     let path = history_path();
+    let dir = data_dir();
+    fs::create_dir_all(&dir)?;
+    let probe = dir.join(".write-probe");
+    write_atomic(&probe, "")?;
+    fs::remove_file(&probe)?;
+    Ok(path)
+}
+
+/// Check that an existing history file parses. A file that doesn't is
+/// silently replaced by an empty history on load.
+pub fn check_readable() -> Result<(), Box<dyn std::error::Error>> {
+    match fs::read_to_string(history_path()) {
+        Ok(data) => deserialize(&data, format()).map(|_| ()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_history(
+    path: &Path,
+    format: StorageFormat,
+    history: &History,
+) -> Result<(), Box<dyn std::error::Error>> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let data = serde_json::to_string_pretty(history)?;
-    fs::write(&path, data)?;
+    let data = serialize(history, format)?;
+    retry_transient(|| write_atomic(path, &data), RETRY_BACKOFF)?;
     Ok(())
 }
 
+/// Total attempts for a save hitting transient errors, and the delay before
+/// the first retry (doubled for each further one).
+const SAVE_ATTEMPTS: u32 = 4;
+const RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Write via a temp file and rename, so a failed or interrupted write never
+/// leaves a truncated history (or config) file behind.
+pub fn write_atomic(path: &Path, data: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+/// Run `write`, retrying with backoff on errors that usually clear up on
+/// their own: another process (antivirus, sync client) briefly holding the
+/// file, or an interrupted system call.
+fn retry_transient(mut write: impl FnMut() -> io::Result<()>, backoff: Duration) -> io::Result<()> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match write() {
+            Err(e) if attempt < SAVE_ATTEMPTS && is_transient(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::Interrupted
+    )
+}
+
+/// Look for a history file in any other format, rewrite it in `format` and
+/// remove the old file so a later switch back doesn't pick up stale data.
+fn migrate(dir: &Path, format: StorageFormat) -> Option<History> {
+    for other in StorageFormat::ALL.into_iter().filter(|f| *f != format) {
+        let old_path = history_file(dir, other);
+        let Ok(data) = fs::read_to_string(&old_path) else {
+            continue;
+        };
+        let Ok(history) = deserialize(&data, other) else {
+            continue;
+        };
+        match write_history(&history_file(dir, format), format, &history) {
+            Ok(()) => {
+                let _ = fs::remove_file(&old_path);
+            }
+            Err(e) => eprintln!("Failed to migrate history to {format:?}: {e}"),
+        }
+        return Some(history);
+    }
+    None
+}
+
+/// Where single-entry exports go: the Downloads folder, or an `exports`
+/// folder next to the history if there is none.
+pub fn export_dir() -> PathBuf {
+    dirs::download_dir().unwrap_or_else(|| data_dir().join("exports"))
+}
+
+/// File name for an exported entry, e.g. `clip-42-hello-world.txt`: the id
+/// keeps names unique, the slug of the first line makes them recognizable.
+pub fn export_file_name(entry: &ClipboardEntry) -> String {
+    let first_line = entry.content.lines().find(|l| !l.trim().is_empty());
+    let mut slug = String::new();
+    for c in first_line.unwrap_or_default().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= EXPORT_SLUG_CHARS {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        format!("clip-{}.txt", entry.id)
+    } else {
+        format!("clip-{}-{slug}.txt", entry.id)
+    }
+}
+
+/// Longest first-line slug kept in an export file name.
+const EXPORT_SLUG_CHARS: usize = 32;
+
+/// Write `entry`'s content to `path` as plain text. Creates parent
+/// directories if needed.
+pub fn export_entry(entry: &ClipboardEntry, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, &entry.content)
+}
+
+/// Merge the OS clipboard history into `history` the first time this runs.
+/// A marker file next to the history records that the import happened, so
+/// it is never repeated. Nothing is recorded while the OS history is empty
+/// (e.g. the feature is turned off), so a later launch can still import.
+pub fn import_system_history_once(history: &mut History) {
+    let marker = data_dir().join("system-history-imported");
+    if marker.exists() {
+        return;
+    }
+    let texts = crate::platform::read_system_clipboard_history();
+    if texts.is_empty() {
+        return;
+    }
+
+    let imported = texts
+        .into_iter()
+        .filter(|text| history.push(text.clone()))
+        .count();
+    if let Err(e) = save(history) {
+        eprintln!("Failed to save imported history: {e}");
+        return;
+    }
+    if let Err(e) = fs::write(&marker, format!("{imported}\n")) {
+        eprintln!("Failed to record system history import: {e}");
+    }
+}
+
+fn serialize(
+    history: &History,
+    format: StorageFormat,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match format {
+        StorageFormat::Json => Ok(serde_json::to_string_pretty(history)?),
+        #[cfg(feature = "yaml")]
+        StorageFormat::Yaml => Ok(serde_yaml::to_string(history)?),
+        #[cfg(feature = "toml")]
+        StorageFormat::Toml => Ok(toml::to_string_pretty(history)?),
+        #[allow(unreachable_patterns)]
+        other => Err(unsupported(other)),
+    }
+}
+
+fn deserialize(data: &str, format: StorageFormat) -> Result<History, Box<dyn std::error::Error>> {
+    let mut history: History = match format {
+        StorageFormat::Json => serde_json::from_str(data)?,
+        #[cfg(feature = "yaml")]
+        StorageFormat::Yaml => serde_yaml::from_str(data)?,
+        #[cfg(feature = "toml")]
+        StorageFormat::Toml => toml::from_str(data)?,
+        #[allow(unreachable_patterns)]
+        other => return Err(unsupported(other)),
+    };
+    history.repair_next_id();
+    history.classify_missing_kinds();
+    Ok(history)
+}
+
+#[allow(dead_code)]
+fn unsupported(format: StorageFormat) -> Box<dyn std::error::Error> {
+    format!(
+        "{format:?} storage requires building with the `{}` feature",
+        format.extension()
+    )
+    .into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::env;
 
+    fn entry(history: &mut History, content: &str) -> ClipboardEntry {
+        history.push(content.into());
+        history.entries()[0].clone()
+    }
+
+    #[test]
+    fn test_dir_overrides() {
+        // Absolute on every platform
+        let exe = env::temp_dir().join("usb").join("clip");
+        let os = Some(env::temp_dir().join("config"));
+        assert_eq!(
+            resolve_config_dir(None, Some(&exe), os.clone()),
+            env::temp_dir().join("config").join("clipboard-history")
+        );
+        // No OS config dir: the working directory
+        assert_eq!(
+            resolve_config_dir(None, Some(&exe), None),
+            Path::new(".").join("clipboard-history")
+        );
+
+        let clips = env::temp_dir().join("clips");
+        assert_eq!(
+            resolve_config_dir(Some(clips.clone()), Some(&exe), os.clone()),
+            clips
+        );
+        let portable = resolve_config_dir(Some("data".into()), Some(&exe), os);
+        assert_eq!(portable, exe.join("data"));
+        assert_eq!(
+            history_file(&portable, StorageFormat::Json),
+            exe.join("data").join("history.json")
+        );
+        // Kept relative without a known executable
+        assert_eq!(resolve_relative("data".into(), None), Path::new("data"));
+    }
+
+    #[test]
+    fn test_export_file_name() {
+        let mut history = History::new(100);
+        let hello = entry(&mut history, "\n  Hello, World!\nsecond line");
+        assert_eq!(
+            export_file_name(&hello),
+            format!("clip-{}-hello-world.txt", hello.id)
+        );
+        let symbols = entry(&mut history, "--> ...");
+        assert_eq!(
+            export_file_name(&symbols),
+            format!("clip-{}.txt", symbols.id)
+        );
+        let long = entry(&mut history, &"word ".repeat(20));
+        let name = export_file_name(&long);
+        let slug = name.trim_start_matches(&format!("clip-{}-", long.id));
+        assert_eq!(slug, "word-word-word-word-word-word-wo.txt");
+    }
+
+    #[test]
+    fn test_export_entry_writes_text() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-export-test");
+        let _ = fs::remove_dir_all(&tmp_dir);
+
+        let mut history = History::new(100);
+        let snippet = entry(&mut history, "fn main() {\n    println!(\"hi\");\n}\n");
+        let path = tmp_dir.join("nested").join(export_file_name(&snippet));
+        export_entry(&snippet, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), snippet.content);
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
     #[test]
     fn test_save_and_load_roundtrip() {
         // Use a temp directory for testing
@@ -90,4 +507,230 @@ mod tests {
 
         let _ = fs::remove_dir_all(&tmp_dir);
     }
+
+    fn sample_history() -> History {
+        let mut history = History::new(100);
+        history.push("single line".into());
+        history.push("multi\nline\ncontent".into());
+        history
+    }
+
+    fn assert_roundtrip(format: StorageFormat) {
+        let history = sample_history();
+        let data = serialize(&history, format).unwrap();
+        let loaded = deserialize(&data, format).unwrap();
+        assert_eq!(loaded.entries().len(), 2);
+        assert_eq!(loaded.entries()[0].content, "multi\nline\ncontent");
+        assert_eq!(loaded.entries()[1].content, "single line");
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        assert_roundtrip(StorageFormat::Json);
+    }
+
+    #[test]
+    fn test_legacy_history_without_next_id() {
+        let legacy = r#"{
+            "entries": [
+                {"id": 2, "content": "second", "created_at": "2024-01-02T00:00:00Z"},
+                {"id": 1, "content": "first", "created_at": "2024-01-01T00:00:00Z"}
+            ],
+            "max_size": 100
+        }"#;
+        let mut history = deserialize(legacy, StorageFormat::Json).unwrap();
+        history.push("third".into());
+        history.push("fourth".into());
+
+        let mut ids: Vec<u64> = history.entries().iter().map(|e| e.id).collect();
+        assert_eq!(ids[..2], [4, 3]);
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(history.get_by_id(1).unwrap().content, "first");
+        assert_eq!(history.get_by_id(3).unwrap().content, "third");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_roundtrip() {
+        assert_roundtrip(StorageFormat::Yaml);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_roundtrip() {
+        assert_roundtrip(StorageFormat::Toml);
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_disabled_format_is_an_error() {
+        assert!(serialize(&sample_history(), StorageFormat::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_history_file_uses_format_extension() {
+        let dir = Path::new("data");
+        assert_eq!(
+            history_file(dir, StorageFormat::Json),
+            dir.join("history.json")
+        );
+        assert_eq!(
+            history_file(dir, StorageFormat::Yaml),
+            dir.join("history.yaml")
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_migrate_json_to_toml() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-test-migrate");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        let json_path = history_file(&tmp_dir, StorageFormat::Json);
+        write_history(&json_path, StorageFormat::Json, &sample_history()).unwrap();
+
+        let migrated = migrate(&tmp_dir, StorageFormat::Toml).unwrap();
+        assert_eq!(migrated.entries().len(), 2);
+        assert!(!json_path.exists());
+        let toml_data = fs::read_to_string(history_file(&tmp_dir, StorageFormat::Toml)).unwrap();
+        assert_eq!(
+            deserialize(&toml_data, StorageFormat::Toml)
+                .unwrap()
+                .entries()
+                .len(),
+            2
+        );
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_migrate_without_old_file_returns_none() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-test-migrate-none");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+
+        assert!(migrate(&tmp_dir, StorageFormat::Json).is_none());
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    /// A write function failing with `kind` for its first `failures` calls.
+    fn flaky_write(
+        kind: io::ErrorKind,
+        failures: u32,
+        calls: &mut u32,
+    ) -> impl FnMut() -> io::Result<()> + '_ {
+        move || {
+            *calls += 1;
+            if *calls <= failures {
+                Err(io::Error::from(kind))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_recovers_from_transient_lock() {
+        let mut calls = 0;
+        let write = flaky_write(io::ErrorKind::PermissionDenied, 2, &mut calls);
+        assert!(retry_transient(write, Duration::ZERO).is_ok());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_max_attempts() {
+        let mut calls = 0;
+        let write = flaky_write(io::ErrorKind::Interrupted, 10, &mut calls);
+        let err = retry_transient(write, Duration::ZERO).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(calls, SAVE_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_no_retry_on_permanent_error() {
+        let mut calls = 0;
+        let write = flaky_write(io::ErrorKind::NotFound, 1, &mut calls);
+        assert!(retry_transient(write, Duration::ZERO).is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_prune_backups_keeps_newest() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-test-backups");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let names = [
+            "history-20240101-090000.json",
+            "history-20240102-090000.json",
+            "history-20240103-090000.json",
+            "history-20240103-100000.toml",
+        ];
+        for name in names {
+            fs::write(tmp_dir.join(name), "{}").unwrap();
+        }
+        fs::write(tmp_dir.join("notes.txt"), "").unwrap();
+
+        assert_eq!(prune_backups_in(&tmp_dir, 2).unwrap(), 2);
+        assert!(!tmp_dir.join(names[0]).exists());
+        assert!(!tmp_dir.join(names[1]).exists());
+        assert!(tmp_dir.join(names[2]).exists());
+        assert!(tmp_dir.join(names[3]).exists());
+        assert!(tmp_dir.join("notes.txt").exists());
+        // Already within the limit
+        assert_eq!(prune_backups_in(&tmp_dir, 2).unwrap(), 0);
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_write_atomic_leaves_no_temp_file() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-test-atomic");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        fs::create_dir_all(&tmp_dir).unwrap();
+        let path = tmp_dir.join("history.json");
+
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert!(!tmp_dir.join("history.json.tmp").exists());
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_interrupted_save_keeps_previous_file() {
+        let tmp_dir = env::temp_dir().join("clipboard-history-test-interrupted");
+        let _ = fs::remove_dir_all(&tmp_dir);
+        let path = tmp_dir.join("history.json");
+
+        let mut history = History::new(100);
+        history.push("good entry".into());
+        write_history(&path, StorageFormat::Json, &history).unwrap();
+
+        // A directory where the temp file goes makes the next save fail
+        let tmp_path = tmp_dir.join("history.json.tmp");
+        fs::create_dir(&tmp_path).unwrap();
+        history.push("newer entry".into());
+        assert!(write_history(&path, StorageFormat::Json, &history).is_err());
+        let loaded = deserialize(&fs::read_to_string(&path).unwrap(), StorageFormat::Json).unwrap();
+        assert_eq!(loaded.entries().len(), 1);
+        assert_eq!(loaded.entries()[0].content, "good entry");
+
+        // A save killed mid-write leaves a truncated temp file, which the
+        // next save replaces
+        fs::remove_dir(&tmp_path).unwrap();
+        let data = serialize(&history, StorageFormat::Json).unwrap();
+        fs::write(&tmp_path, &data[..data.len() / 2]).unwrap();
+        write_history(&path, StorageFormat::Json, &history).unwrap();
+        let loaded = deserialize(&fs::read_to_string(&path).unwrap(), StorageFormat::Json).unwrap();
+        assert_eq!(loaded.entries().len(), 2);
+        assert!(!tmp_path.exists());
+
+        let _ = fs::remove_dir_all(&tmp_dir);
+    }
 }
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Local};
+
+/// Values available to placeholders when a template entry is pasted.
+#[derive(Debug, Clone)]
+pub struct TemplateContext {
+    pub now: DateTime<Local>,
+    /// Text on the clipboard just before the paste.
+    pub clipboard: String,
+}
+
+/// Substitute the known placeholders in `content`:
+/// - `{date}` — `2024-05-31`
+/// - `{time}` — `14:05`
+/// - `{datetime}` — `2024-05-31 14:05`
+/// - `{clipboard}` — the current clipboard text
+/// - `{cursor}` — removed; marks where the caret would go, which a plain
+///   clipboard paste can't position
+///
+/// `{{` and `}}` produce literal braces. Unknown placeholders are left as is.
+pub fn expand_template(content: &str, ctx: &TemplateContext) -> String {
+    expand_template_with_caret(content, ctx).0
+}
+
+/// Like [`expand_template`], also returning where the first `{cursor}` was as
+/// a character offset into the expanded text, for integrations that can
+/// place the caret after pasting.
+pub fn expand_template_with_caret(content: &str, ctx: &TemplateContext) -> (String, Option<usize>) {
+    let mut out = String::with_capacity(content.len());
+    let mut caret = None;
+    let mut rest = content;
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(after) = rest.strip_prefix("{{") {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            out.push('}');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('}') {
+            out.push('}');
+            rest = after;
+        } else if let Some(end) = rest.find('}') {
+            if &rest[1..end] == "cursor" && caret.is_none() {
+                caret = Some(out.chars().count());
+            }
+            match expand_placeholder(&rest[1..end], ctx) {
+                Some(value) => out.push_str(&value),
+                None => out.push_str(&rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        } else {
+            // Unclosed '{'
+            break;
+        }
+    }
+    out.push_str(rest);
+    (out, caret)
+}
+
+fn expand_placeholder(name: &str, ctx: &TemplateContext) -> Option<String> {
+    let value = match name {
+        "date" => ctx.now.format("%Y-%m-%d").to_string(),
+        "time" => ctx.now.format("%H:%M").to_string(),
+        "datetime" => ctx.now.format("%Y-%m-%d %H:%M").to_string(),
+        "clipboard" => ctx.clipboard.clone(),
+        "cursor" => String::new(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ctx() -> TemplateContext {
+        TemplateContext {
+            now: Local.with_ymd_and_hms(2024, 5, 31, 14, 5, 9).unwrap(),
+            clipboard: "pasted".into(),
+        }
+    }
+
+    #[test]
+    fn test_date_and_time() {
+        assert_eq!(expand_template("{date}", &ctx()), "2024-05-31");
+        assert_eq!(expand_template("at {time}", &ctx()), "at 14:05");
+        assert_eq!(expand_template("{datetime}", &ctx()), "2024-05-31 14:05");
+    }
+
+    #[test]
+    fn test_clipboard() {
+        assert_eq!(expand_template("Re: {clipboard}!", &ctx()), "Re: pasted!");
+    }
+
+    #[test]
+    fn test_cursor_is_removed() {
+        assert_eq!(expand_template("fn {cursor}() {}", &ctx()), "fn () {}");
+    }
+
+    #[test]
+    fn test_caret_offset() {
+        let expand = |content| expand_template_with_caret(content, &ctx());
+        assert_eq!(expand("fn {cursor}() {}"), ("fn () {}".into(), Some(3)));
+        // Counted in characters of the expanded text, first marker wins
+        assert_eq!(
+            expand("é {clipboard} {cursor}{cursor}"),
+            ("é pasted ".into(), Some(9))
+        );
+        assert_eq!(
+            expand("{{cursor}} {date}"),
+            ("{cursor} 2024-05-31".into(), None)
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_unclosed_left_alone() {
+        assert_eq!(expand_template("{name} {date", &ctx()), "{name} {date");
+        assert_eq!(expand_template("a } b", &ctx()), "a } b");
+    }
+
+    #[test]
+    fn test_escaped_braces() {
+        assert_eq!(expand_template("{{date}}", &ctx()), "{date}");
+    }
+
+    #[test]
+    fn test_plain_text_untouched() {
+        assert_eq!(
+            expand_template("no placeholders", &ctx()),
+            "no placeholders"
+        );
+    }
+}
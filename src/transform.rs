@@ -0,0 +1,64 @@
+/// A change applied to an entry's text when pasting it. The stored entry
+/// keeps its original content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Uppercase,
+    Lowercase,
+    Trim,
+}
+
+impl Transform {
+    /// Order of the row context menu.
+    pub const ALL: [Transform; 3] = [Transform::Uppercase, Transform::Lowercase, Transform::Trim];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::Uppercase => "Paste UPPERCASE",
+            Transform::Lowercase => "Paste lowercase",
+            Transform::Trim => "Paste trimmed",
+        }
+    }
+
+    /// Unicode-aware: "ß" becomes "SS" and "ΑΣ" becomes "ας", so the result
+    /// can be longer or shorter than `text`.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Transform::Uppercase => text.to_uppercase(),
+            Transform::Lowercase => text.to_lowercase(),
+            Transform::Trim => text.trim().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_transforms() {
+        assert_eq!(Transform::Uppercase.apply("Hello, World"), "HELLO, WORLD");
+        assert_eq!(Transform::Lowercase.apply("Hello, World"), "hello, world");
+        // Digits, punctuation and line breaks are untouched
+        assert_eq!(Transform::Uppercase.apply("a1-b2\nc3"), "A1-B2\nC3");
+    }
+
+    #[test]
+    fn test_unicode_case_folding() {
+        assert_eq!(Transform::Uppercase.apply("straße"), "STRASSE");
+        assert_eq!(Transform::Uppercase.apply("éàü"), "ÉÀÜ");
+        assert_eq!(Transform::Lowercase.apply("ÉÀÜ"), "éàü");
+        // Final sigma
+        assert_eq!(Transform::Lowercase.apply("ΟΔΟΣ"), "οδος");
+        assert_eq!(Transform::Lowercase.apply("İ").chars().count(), 2);
+        assert_eq!(Transform::Uppercase.apply("日本語 😀"), "日本語 😀");
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(Transform::Trim.apply("  padded \t\n"), "padded");
+        assert_eq!(Transform::Trim.apply("\u{3000}wide\u{a0}"), "wide");
+        // Only the ends; inner whitespace and lines stay
+        assert_eq!(Transform::Trim.apply("\n a  b\n  c \n"), "a  b\n  c");
+        assert_eq!(Transform::Trim.apply(" \n "), "");
+    }
+}
@@ -1,8 +1,16 @@
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, Submenu};
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
+use crate::config::Theme;
+use crate::history::History;
+use crate::watchdog::Watchdog;
+
+/// How long Quit waits for the window to close before exiting directly.
+const QUIT_GRACE: Duration = Duration::from_secs(3);
+
 /// Create a simple 16x16 blue icon for the system tray.
 fn create_default_icon() -> Icon {
     let size = 16u32;
@@ -17,15 +25,111 @@ fn create_default_icon() -> Icon {
     Icon::from_rgba(rgba, size, size).expect("Failed to create tray icon")
 }
 
-/// Build and return the system tray icon with a simple menu.
-pub fn build_tray(visible: Arc<Mutex<bool>>, ctx: eframe::egui::Context) -> TrayIcon {
+/// Tray tooltip text: the entry count, then any thread `health` problems.
+pub fn tooltip(count: usize, health: Option<&str>) -> String {
+    let items = if count == 1 { "item" } else { "items" };
+    let mut tooltip = format!("Clipboard History — {count} {items}");
+    if let Some(health) = health {
+        tooltip.push_str(" — ");
+        tooltip.push_str(health);
+    }
+    tooltip
+}
+
+/// The tray icon, with what it needs to keep its tooltip current.
+pub struct TrayStatus {
+    icon: TrayIcon,
+    history: Arc<Mutex<History>>,
+    watchdog: Watchdog,
+    /// Text last set as the tooltip.
+    shown: String,
+}
+
+impl TrayStatus {
+    pub fn new(icon: TrayIcon, history: Arc<Mutex<History>>, watchdog: Watchdog) -> TrayStatus {
+        TrayStatus {
+            icon,
+            history,
+            watchdog,
+            shown: String::new(),
+        }
+    }
+
+    /// Where the icon is on screen, in physical pixels.
+    pub fn rect(&self) -> Option<tray_icon::Rect> {
+        self.icon.rect()
+    }
+
+    /// Show the entry count and thread restarts in the tooltip, if they
+    /// changed. The icon belongs to the UI thread, so call it there.
+    pub fn refresh(&mut self) {
+        let count = crate::clipboard::lock_recovering(&self.history)
+            .entries()
+            .len();
+        let tooltip = tooltip(count, self.watchdog.health().summary().as_deref());
+        if tooltip != self.shown {
+            if let Err(e) = self.icon.set_tooltip(Some(&tooltip)) {
+                eprintln!("Failed to update tray tooltip: {e}");
+            }
+            self.shown = tooltip;
+        }
+    }
+}
+
+/// Create a bare tray icon and remove it again, to check that the system
+/// tray is available.
+pub fn check() -> Result<(), tray_icon::Error> {
+    TrayIconBuilder::new()
+        .with_tooltip("Clipboard History")
+        .with_icon(create_default_icon())
+        .build()
+        .map(drop)
+}
+
+/// Build and return the system tray icon with a simple menu. Quitting sets
+/// `quit` and closes the window, which stops the clipboard monitor (flagged
+/// by `running`) after it saves captures still waiting for the save debounce.
+/// "Clear History" is a submenu whose only item confirms it, so a stray
+/// click can't wipe the history. Picking from the "Theme" submenu sets
+/// `theme`, which the UI applies and saves to the config. "Pause/Resume
+/// Capture" flips `paused` (see `clipboard::toggle_pause`), which the
+/// clipboard monitor checks.
+pub fn build_tray(
+    visible: Arc<Mutex<bool>>,
+    history: Arc<Mutex<History>>,
+    theme: Arc<Mutex<Theme>>,
+    paused: Arc<Mutex<bool>>,
+    quit: Arc<Mutex<bool>>,
+    running: Arc<Mutex<bool>>,
+    ctx: eframe::egui::Context,
+) -> TrayIcon {
     let menu = Menu::new();
     let show_item = MenuItem::new("Show/Hide", true, None);
+    let confirm_clear_item = MenuItem::new("Confirm: delete all unlocked entries", true, None);
+    let clear_menu = Submenu::with_items("Clear History", true, &[&confirm_clear_item]).unwrap();
+    let theme_items: Vec<(MenuItem, Theme)> = Theme::ALL
+        .into_iter()
+        .map(|theme| (MenuItem::new(theme.label(), true, None), theme))
+        .collect();
+    let theme_menu = Submenu::new("Theme", true);
+    for (item, _) in &theme_items {
+        theme_menu.append(item).unwrap();
+    }
+    let theme_ids: Vec<_> = theme_items
+        .iter()
+        .map(|(item, theme)| (item.id().clone(), *theme))
+        .collect();
+    let pause_item = MenuItem::new("Pause/Resume Capture", true, None);
     let quit_item = MenuItem::new("Quit", true, None);
     let show_id = show_item.id().clone();
+    let pause_id = pause_item.id().clone();
+    let clear_id = confirm_clear_item.id().clone();
     let quit_id = quit_item.id().clone();
 
     menu.append(&show_item).unwrap();
+    menu.append(&pause_item).unwrap();
+    menu.append(&clear_menu).unwrap();
+    menu.append(&theme_menu).unwrap();
     menu.append(&quit_item).unwrap();
 
     let tray = TrayIconBuilder::new()
@@ -55,8 +159,31 @@ pub fn build_tray(visible: Arc<Mutex<bool>>, ctx: eframe::egui::Context) -> Tray
                         crate::platform::hide_window_native();
                     }
 
+                    ctx.request_repaint();
+                } else if event.id() == &clear_id {
+                    // Locked entries stay; the rest go to the trash
+                    let mut history = crate::clipboard::lock_recovering(&history);
+                    history.clear();
+                    if let Err(e) = crate::storage::save(&history) {
+                        eprintln!("Failed to save history: {e}");
+                    }
+                    drop(history);
+                    ctx.request_repaint();
+                } else if let Some((_, picked)) = theme_ids.iter().find(|(id, _)| event.id() == id)
+                {
+                    *theme.lock().unwrap() = *picked;
+                    ctx.request_repaint();
+                } else if event.id() == &pause_id {
+                    crate::clipboard::toggle_pause(&paused);
                     ctx.request_repaint();
                 } else if event.id() == &quit_id {
+                    *crate::clipboard::lock_recovering(&quit) = true;
+                    ctx.send_viewport_cmd(eframe::egui::ViewportCommand::Close);
+                    ctx.request_repaint();
+                    // A hidden window may never run the frame that closes it
+                    // (see `App::on_exit`); shut down from here instead
+                    std::thread::sleep(QUIT_GRACE);
+                    crate::clipboard::shut_down(&history, &quit, &running);
                     std::process::exit(0);
                 }
             }
@@ -65,3 +192,19 @@ pub fn build_tray(visible: Arc<Mutex<bool>>, ctx: eframe::egui::Context) -> Tray
 
     tray
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip_counts_entries() {
+        assert_eq!(tooltip(0, None), "Clipboard History — 0 items");
+        assert_eq!(tooltip(1, None), "Clipboard History — 1 item");
+        assert_eq!(tooltip(42, None), "Clipboard History — 42 items");
+        assert_eq!(
+            tooltip(3, Some("monitor stopped")),
+            "Clipboard History — 3 items — monitor stopped"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a fetched (or failed) title is reused before the page is fetched again.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Give up on a page after this long.
+const FETCH_TIMEOUT_SECS: u32 = 5;
+/// Only the start of a page is read; `<title>` is in the head.
+const MAX_PAGE_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone)]
+enum TitleState {
+    Pending,
+    /// `None` when the page couldn't be fetched or has no title.
+    Done(Option<String>, Instant),
+}
+
+/// Page titles for URL entries with `Config::fetch_url_titles`, fetched on
+/// background threads and cached by URL.
+#[derive(Debug, Clone, Default)]
+pub struct TitleFetcher {
+    cache: Arc<Mutex<HashMap<String, TitleState>>>,
+}
+
+impl TitleFetcher {
+    /// The cached title of `url`. Starts a fetch and returns `None` if there
+    /// is none yet; `ctx` is repainted when it arrives.
+    pub fn get(&self, url: &str, ctx: &eframe::egui::Context) -> Option<String> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(url) {
+            Some(TitleState::Pending) => return None,
+            Some(TitleState::Done(title, fetched)) if fetched.elapsed() < CACHE_TTL => {
+                return title.clone();
+            }
+            _ => {}
+        }
+        cache.insert(url.to_string(), TitleState::Pending);
+        drop(cache);
+
+        let cache = Arc::clone(&self.cache);
+        let url = url.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let title = fetch_title(&url);
+            cache
+                .lock()
+                .unwrap()
+                .insert(url, TitleState::Done(title, Instant::now()));
+            ctx.request_repaint();
+        });
+        None
+    }
+}
+
+/// Whether `content` is a single http(s) URL.
+pub fn is_url(content: &str) -> bool {
+    let url = content.trim();
+    (url.starts_with("http://") || url.starts_with("https://"))
+        && !url.contains(char::is_whitespace)
+}
+
+/// Download the start of the page with the system `curl` (shipped with
+/// Windows 10+, macOS and most Linux distributions) and pull out its title.
+fn fetch_title(url: &str) -> Option<String> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--location", "--max-time"])
+        .arg(FETCH_TIMEOUT_SECS.to_string())
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    crate::platform::hide_console(&mut command);
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| eprintln!("Failed to run curl for URL titles: {e}"))
+        .ok()?;
+    let mut page = Vec::new();
+    let read = child
+        .stdout
+        .take()
+        .map(|stdout| stdout.take(MAX_PAGE_BYTES).read_to_end(&mut page));
+    let _ = child.kill();
+    let _ = child.wait();
+    read?.ok()?;
+    extract_title(&String::from_utf8_lossy(&page))
+}
+
+/// The text of the first `<title>` element, with whitespace collapsed and
+/// the common entities decoded. `None` if missing or empty.
+pub fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = html[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/a?b=c"));
+        assert!(is_url("  http://example.com\n"));
+        assert!(!is_url("example.com"));
+        assert!(!is_url("see https://example.com"));
+        assert!(!is_url("ftp://example.com"));
+    }
+
+    #[test]
+    fn test_extract_title() {
+        let html = "<html><head><TITLE lang=\"en\">\n  Rust &amp; egui\n</TITLE></head>";
+        assert_eq!(extract_title(html).as_deref(), Some("Rust & egui"));
+        assert_eq!(extract_title("<title>  </title>"), None);
+        assert_eq!(extract_title("<html>no title</html>"), None);
+        assert_eq!(extract_title("<title>cut off"), None);
+    }
+}
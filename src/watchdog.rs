@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::clipboard::lock_recovering;
+
+/// How often the watchdog looks for threads that have exited.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait before the first restart of a thread; doubled for each further one.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// When a thread that exited may be restarted.
+#[derive(Debug, Clone, Copy)]
+struct RestartPolicy {
+    max_restarts: u32,
+    first_backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// Wait before restart number `restarts + 1`, or `None` once the cap is reached.
+    fn delay(&self, restarts: u32) -> Option<Duration> {
+        if restarts >= self.max_restarts {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(restarts);
+        Some(self.first_backoff.saturating_mul(factor).min(MAX_BACKOFF))
+    }
+}
+
+/// Restart counts of the supervised threads, for the tray tooltip.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Health {
+    /// Restarts so far per thread name (only threads restarted at least once).
+    pub restarts: BTreeMap<&'static str, u32>,
+    /// Threads that died after using up their restarts.
+    pub stopped: Vec<&'static str>,
+}
+
+impl Health {
+    /// One line describing restarts and stopped threads, or `None` if all
+    /// threads have been running since startup.
+    pub fn summary(&self) -> Option<String> {
+        let mut parts: Vec<String> = self
+            .restarts
+            .iter()
+            .filter(|(name, _)| !self.stopped.contains(name))
+            .map(|(name, count)| format!("{name} restarted {count}×"))
+            .collect();
+        parts.extend(self.stopped.iter().map(|name| format!("{name} stopped")));
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+type Spawn = Box<dyn FnMut() -> JoinHandle<()> + Send>;
+
+struct Supervised {
+    name: &'static str,
+    spawn: Spawn,
+    handle: JoinHandle<()>,
+    restarts: u32,
+    /// When the watchdog first saw the thread finished.
+    died_at: Option<Instant>,
+    stopped: bool,
+}
+
+/// Restarts background threads (clipboard monitor, hotkey listener) that
+/// exit unexpectedly, with backoff and a cap on attempts. Cheap to clone;
+/// all clones share the same threads.
+#[derive(Clone)]
+pub struct Watchdog {
+    threads: Arc<Mutex<Vec<Supervised>>>,
+    health: Arc<Mutex<Health>>,
+    policy: RestartPolicy,
+}
+
+impl Watchdog {
+    /// Start the watchdog thread. Each watched thread is restarted at most
+    /// `max_restarts` times (0 never restarts, only reports it stopped).
+    pub fn start(max_restarts: u32) -> Watchdog {
+        let watchdog = Watchdog::new(RestartPolicy {
+            max_restarts,
+            first_backoff: FIRST_BACKOFF,
+        });
+        let checker = watchdog.clone();
+        thread::spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            checker.check(Instant::now());
+        });
+        watchdog
+    }
+
+    fn new(policy: RestartPolicy) -> Watchdog {
+        Watchdog {
+            threads: Arc::new(Mutex::new(Vec::new())),
+            health: Arc::new(Mutex::new(Health::default())),
+            policy,
+        }
+    }
+
+    /// Start a thread with `spawn` and keep it running: `spawn` is called
+    /// again whenever the thread it returned has exited.
+    pub fn watch(
+        &self,
+        name: &'static str,
+        mut spawn: impl FnMut() -> JoinHandle<()> + Send + 'static,
+    ) {
+        let handle = spawn();
+        lock_recovering(&self.threads).push(Supervised {
+            name,
+            spawn: Box::new(spawn),
+            handle,
+            restarts: 0,
+            died_at: None,
+            stopped: false,
+        });
+    }
+
+    pub fn health(&self) -> Health {
+        lock_recovering(&self.health).clone()
+    }
+
+    /// Restart the threads that have exited and waited out their backoff.
+    fn check(&self, now: Instant) {
+        let mut threads = lock_recovering(&self.threads);
+        for thread in threads.iter_mut().filter(|t| !t.stopped) {
+            if !thread.handle.is_finished() {
+                continue;
+            }
+            let died_at = *thread.died_at.get_or_insert_with(|| {
+                eprintln!("The {} thread exited", thread.name);
+                now
+            });
+            match self.policy.delay(thread.restarts) {
+                None => {
+                    eprintln!("Not restarting the {} thread again", thread.name);
+                    thread.stopped = true;
+                    lock_recovering(&self.health).stopped.push(thread.name);
+                }
+                Some(delay) if now.duration_since(died_at) >= delay => {
+                    thread.handle = (thread.spawn)();
+                    thread.restarts += 1;
+                    thread.died_at = None;
+                    lock_recovering(&self.health)
+                        .restarts
+                        .insert(thread.name, thread.restarts);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RestartPolicy {
+            max_restarts: 10,
+            first_backoff: Duration::from_secs(1),
+        };
+        let delays: Vec<_> = (0..4).map(|n| policy.delay(n).unwrap().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8]);
+        assert_eq!(policy.delay(9), Some(MAX_BACKOFF));
+        assert_eq!(policy.delay(10), None);
+    }
+
+    /// Wait until every watched thread has exited.
+    fn wait_finished(watchdog: &Watchdog) {
+        while !lock_recovering(&watchdog.threads)
+            .iter()
+            .all(|t| t.handle.is_finished())
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_dead_thread_is_restarted_until_the_cap() {
+        let watchdog = Watchdog::new(RestartPolicy {
+            max_restarts: 2,
+            first_backoff: Duration::from_secs(1),
+        });
+        let spawned = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&spawned);
+        // A thread that dies right away every time
+        watchdog.watch("monitor", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(|| {})
+        });
+        let t0 = Instant::now();
+
+        wait_finished(&watchdog);
+        watchdog.check(t0);
+        // Still backing off
+        watchdog.check(t0 + Duration::from_millis(500));
+        assert_eq!(spawned.load(Ordering::SeqCst), 1);
+        watchdog.check(t0 + Duration::from_secs(1));
+        assert_eq!(spawned.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            watchdog.health().summary().as_deref(),
+            Some("monitor restarted 1×")
+        );
+
+        // The second restart waits twice as long
+        wait_finished(&watchdog);
+        watchdog.check(t0 + Duration::from_secs(2));
+        watchdog.check(t0 + Duration::from_secs(3));
+        assert_eq!(spawned.load(Ordering::SeqCst), 2);
+        watchdog.check(t0 + Duration::from_secs(4));
+        assert_eq!(spawned.load(Ordering::SeqCst), 3);
+
+        // Out of restarts
+        wait_finished(&watchdog);
+        watchdog.check(t0 + Duration::from_secs(100));
+        watchdog.check(t0 + Duration::from_secs(200));
+        assert_eq!(spawned.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            watchdog.health().summary().as_deref(),
+            Some("monitor stopped")
+        );
+    }
+
+    #[test]
+    fn test_running_thread_is_left_alone() {
+        let watchdog = Watchdog::new(RestartPolicy {
+            max_restarts: 2,
+            first_backoff: Duration::ZERO,
+        });
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let mut stopped = Some(stopped);
+        watchdog.watch("listener", move || {
+            let stopped = stopped.take().unwrap();
+            thread::spawn(move || {
+                let _ = stopped.recv();
+            })
+        });
+        watchdog.check(Instant::now() + Duration::from_secs(10));
+        assert_eq!(watchdog.health(), Health::default());
+        assert_eq!(watchdog.health().summary(), None);
+        drop(stop);
+    }
+}